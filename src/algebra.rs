@@ -0,0 +1,407 @@
+//! Symbolic manipulation of algebraic expressions.
+//!
+//! [`canonicalize()`] puts an expression built from
+//! [`Plus`](https://reference.wolfram.com/language/ref/Plus.html) <sub>WL</sub>,
+//! [`Times`](https://reference.wolfram.com/language/ref/Times.html) <sub>WL</sub> and
+//! [`Power`](https://reference.wolfram.com/language/ref/Power.html) <sub>WL</sub> into
+//! the canonical form that the Wolfram Language evaluator produces for it, so that
+//! equivalent expressions compare equal.
+//!
+//! [`derivative()`] computes symbolic derivatives, and returns them in canonical form.
+//! [`numeric_eval()`] evaluates an expression numerically, using machine-precision
+//! arithmetic.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{algebra::canonicalize, wl};
+//!
+//! // x + 1 + x + 2
+//! let expr = wl!(Plus[Global::x, 1, Global::x, 2]);
+//!
+//! // 3 + 2 x
+//! assert_eq!(canonicalize(&expr), wl!(Plus[3, Times[2, Global::x]]));
+//! ```
+
+mod derivative;
+mod numeric;
+
+use std::cmp::Ordering;
+
+use crate::{attributes::canonical_order, Expr, ExprKind, Numeric, Symbol};
+
+pub use self::{
+    derivative::derivative,
+    numeric::{numeric_eval, NumericError},
+};
+
+
+/// Put an algebraic expression into canonical form.
+///
+/// Every subexpression is canonicalized, and then:
+///
+/// * Nested `Plus` and `Times` expressions are flattened.
+/// * Numeric constants are folded using exact [`Numeric`] arithmetic, and
+///   `Rational[n, d]` is reduced to lowest terms.
+/// * Like terms of a `Plus` are collected into a single term with a numeric
+///   coefficient, like `x + 2 x` to `3 x`, and terms with a zero coefficient are
+///   removed.
+/// * Factors of a `Times` with the same base are collected into a single `Power`,
+///   like `x x^2` to `x^3`.
+/// * `Power` with an integer exponent is applied to numbers, and distributed over
+///   `Times` and nested `Power` bases. `x^0` becomes `1`, and `x^1` becomes `x`.
+/// * `Subtract[a, b]`, `Minus[a]` and `Divide[a, b]` are rewritten in terms of `Plus`,
+///   `Times` and `Power`.
+/// * The operands of `Plus` and `Times` are sorted, with the numeric constant first.
+///   The remaining operands are ordered by their bases, and then by their exponents,
+///   using [`canonical_order()`][crate::attributes::canonical_order].
+///
+/// Expressions with other heads are left unchanged, apart from canonicalizing their
+/// elements.
+///
+/// Subexpressions are canonicalized without recursion, so this can be used on
+/// arbitrarily deep expressions without overflowing the stack.
+pub fn canonicalize(expr: &Expr) -> Expr {
+    // Canonical forms of completed subexpressions that have not been consumed by their
+    // parent.
+    let mut values: Vec<Expr> = Vec::new();
+
+    // Traverse `expr` in post-order, so that the head and elements of every normal
+    // expression are canonicalized before the expression itself.
+    let mut stack: Vec<(&Expr, bool)> = vec![(expr, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        let normal = match expr.kind() {
+            ExprKind::Normal(normal) => normal,
+            ExprKind::Integer(_)
+            | ExprKind::Real(_)
+            | ExprKind::String(_)
+            | ExprKind::Symbol(_) => {
+                values.push(expr.clone());
+                continue;
+            },
+        };
+
+        if !children_visited {
+            stack.push((expr, true));
+
+            for element in normal.elements().iter().rev() {
+                stack.push((element, false));
+            }
+            stack.push((normal.head(), false));
+
+            continue;
+        }
+
+        let elements = values.split_off(values.len() - normal.elements().len());
+        let head = values
+            .pop()
+            .expect("canonicalize: head of normal expression was not canonicalized");
+
+        values.push(canonicalize_normal(head, elements));
+    }
+
+    debug_assert!(values.len() == 1);
+
+    values
+        .pop()
+        .expect("canonicalize: no value computed for root expression")
+}
+
+/// Canonicalize `head[elements]`, where `head` and each element are already
+/// canonical.
+fn canonicalize_normal(head: Expr, elements: Vec<Expr>) -> Expr {
+    let name = head.try_as_symbol().map(|symbol| symbol.as_str());
+
+    match (name, elements.as_slice()) {
+        (Some("System`Plus"), _) => plus(elements),
+        (Some("System`Times"), _) => times(elements),
+        (Some("System`Power"), [base, exponent]) => power(base, exponent),
+        (Some("System`Subtract"), [a, b]) => {
+            plus(vec![a.clone(), times(vec![Expr::from(-1), b.clone()])])
+        },
+        (Some("System`Minus"), [a]) => times(vec![Expr::from(-1), a.clone()]),
+        (Some("System`Divide"), [a, b]) => {
+            times(vec![a.clone(), power(b, &Expr::from(-1))])
+        },
+        (Some("System`Rational"), [numerator, denominator]) => {
+            match (numerator.kind(), denominator.kind()) {
+                (ExprKind::Integer(numerator), ExprKind::Integer(denominator)) => {
+                    Expr::from(Numeric::rational(*numerator, *denominator))
+                },
+                _ => Expr::normal(head, elements),
+            }
+        },
+        _ => Expr::normal(head, elements),
+    }
+}
+
+//======================================
+// Plus, Times and Power
+//======================================
+
+/// Canonicalize `Plus[elements]`, where each element is already canonical.
+fn plus(elements: Vec<Expr>) -> Expr {
+    let head = crate::symbol!("System`Plus");
+
+    let mut constant = Numeric::Integer(0);
+    // Terms, as `(coefficient, rest)` pairs.
+    let mut terms: Vec<(Numeric, Expr)> = Vec::new();
+
+    for element in splice(elements, &head) {
+        if let Some(number) = element.try_as_numeric() {
+            constant = constant + number;
+            continue;
+        }
+
+        let (coefficient, rest) = split_coefficient(&element);
+
+        match terms.iter_mut().find(|(_, existing)| *existing == rest) {
+            Some((existing, _)) => *existing = *existing + coefficient,
+            None => terms.push((coefficient, rest)),
+        }
+    }
+
+    if let Numeric::Indeterminate | Numeric::ComplexInfinity = constant {
+        return Expr::from(constant);
+    }
+
+    terms.retain(|(coefficient, _)| !coefficient.is_zero());
+    terms.sort_by(|(_, a), (_, b)| monomial_order(a, b));
+
+    let mut operands: Vec<Expr> = terms
+        .into_iter()
+        .map(|(coefficient, rest)| with_coefficient(coefficient, rest))
+        .collect();
+
+    if !constant.is_zero() || operands.is_empty() {
+        operands.insert(0, Expr::from(constant));
+    }
+
+    build(head, operands)
+}
+
+/// Canonicalize `Times[elements]`, where each element is already canonical.
+fn times(elements: Vec<Expr>) -> Expr {
+    let head = crate::symbol!("System`Times");
+
+    let mut constant = Numeric::Integer(1);
+    // Factors, as `(base, exponent)` pairs.
+    let mut factors: Vec<(Expr, Expr)> = Vec::new();
+
+    for element in splice(elements, &head) {
+        if let Some(number) = element.try_as_numeric() {
+            constant = constant * number;
+            continue;
+        }
+
+        let (base, exponent) = split_power(&element);
+
+        match factors.iter_mut().find(|(existing, _)| *existing == base) {
+            Some((_, existing)) => *existing = plus(vec![existing.clone(), exponent]),
+            None => factors.push((base, exponent)),
+        }
+    }
+
+    let mut operands = Vec::with_capacity(factors.len());
+
+    // Combining exponents can produce numbers, like x^-1 x^1 == 1, or products, like
+    // (x y)^(1/2) (x y)^(1/2) == x y.
+    for (base, exponent) in factors {
+        let factor = power(&base, &exponent);
+
+        for factor in splice(vec![factor], &head) {
+            match factor.try_as_numeric() {
+                Some(number) => constant = constant * number,
+                None => operands.push(factor),
+            }
+        }
+    }
+
+    match constant {
+        Numeric::Indeterminate | Numeric::ComplexInfinity => return Expr::from(constant),
+        _ if constant.is_zero() => return Expr::from(constant),
+        _ => (),
+    }
+
+    operands.sort_by(monomial_order);
+
+    if constant != Numeric::Integer(1) || operands.is_empty() {
+        operands.insert(0, Expr::from(constant));
+    }
+
+    build(head, operands)
+}
+
+/// Canonicalize `Power[base, exponent]`, where `base` and `exponent` are already
+/// canonical.
+fn power(base: &Expr, exponent: &Expr) -> Expr {
+    match (base.try_as_numeric(), exponent.try_as_numeric()) {
+        (Some(base), Some(Numeric::Integer(exponent))) => {
+            return Expr::from(base.pow(exponent))
+        },
+        // Machine real contagion, for real results only.
+        (Some(base), Some(exponent)) if !(base.is_exact() && exponent.is_exact()) => {
+            if let (Some(base), Some(exponent)) = (base.to_f64(), exponent.to_f64()) {
+                if base >= 0.0 {
+                    return Expr::from(Numeric::real(base.powf(exponent)));
+                }
+            }
+        },
+        _ => (),
+    }
+
+    let integer_exponent = match exponent.kind() {
+        ExprKind::Integer(0) => return Expr::from(1),
+        ExprKind::Integer(1) => return base.clone(),
+        ExprKind::Integer(_) => true,
+        _ => false,
+    };
+
+    if let ExprKind::Integer(1) = base.kind() {
+        return Expr::from(1);
+    }
+
+    if integer_exponent {
+        if let Some(normal) = base.try_as_normal() {
+            match (head_name(base), normal.elements()) {
+                // (b^e)^n == b^(e n), for integer n
+                (Some("System`Power"), [inner_base, inner_exponent]) => {
+                    let exponent = times(vec![inner_exponent.clone(), exponent.clone()]);
+                    return power(inner_base, &exponent);
+                },
+                // (a b)^n == a^n b^n, for integer n
+                (Some("System`Times"), factors) => {
+                    let factors = factors
+                        .iter()
+                        .map(|factor| power(factor, exponent))
+                        .collect();
+                    return times(factors);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    Expr::normal(crate::symbol!("System`Power"), vec![
+        base.clone(),
+        exponent.clone(),
+    ])
+}
+
+//======================================
+// Helpers
+//======================================
+
+/// Replace elements with the head `head` by their own elements.
+fn splice(elements: Vec<Expr>, head: &Symbol) -> Vec<Expr> {
+    let has_head = |element: &Expr| match element.try_as_normal() {
+        Some(normal) => normal.has_head(head),
+        None => false,
+    };
+
+    if !elements.iter().any(has_head) {
+        return elements;
+    }
+
+    let mut spliced = Vec::with_capacity(elements.len());
+
+    for element in elements {
+        match element.try_as_normal() {
+            Some(normal) if normal.has_head(head) => {
+                spliced.extend_from_slice(normal.elements())
+            },
+            _ => spliced.push(element),
+        }
+    }
+
+    spliced
+}
+
+/// Construct `head[operands]`, or the single operand if there is only one.
+fn build(head: Symbol, mut operands: Vec<Expr>) -> Expr {
+    if operands.len() == 1 {
+        return operands.pop().unwrap();
+    }
+
+    Expr::normal(head, operands)
+}
+
+/// Split a canonical term into its numeric coefficient and the remaining factors, like
+/// `2 x y` into `2` and `x y`.
+fn split_coefficient(term: &Expr) -> (Numeric, Expr) {
+    if let (Some("System`Times"), Some(normal)) = (head_name(term), term.try_as_normal())
+    {
+        if let Some((first, rest)) = normal.elements().split_first() {
+            if let Some(coefficient) = first.try_as_numeric() {
+                let rest = build(crate::symbol!("System`Times"), rest.to_vec());
+                return (coefficient, rest);
+            }
+        }
+    }
+
+    (Numeric::Integer(1), term.clone())
+}
+
+/// Multiply a canonical product of factors by a numeric coefficient.
+fn with_coefficient(coefficient: Numeric, rest: Expr) -> Expr {
+    if coefficient == Numeric::Integer(1) {
+        return rest;
+    }
+
+    let mut operands = vec![Expr::from(coefficient)];
+
+    match rest.try_as_normal() {
+        Some(normal) if head_name(&rest) == Some("System`Times") => {
+            operands.extend_from_slice(normal.elements())
+        },
+        _ => operands.push(rest),
+    }
+
+    Expr::normal(crate::symbol!("System`Times"), operands)
+}
+
+/// Split a canonical factor into its base and exponent, like `x^2` into `x` and `2`.
+fn split_power(factor: &Expr) -> (Expr, Expr) {
+    if let (Some("System`Power"), Some(normal)) =
+        (head_name(factor), factor.try_as_normal())
+    {
+        if let [base, exponent] = normal.elements() {
+            return (base.clone(), exponent.clone());
+        }
+    }
+
+    (factor.clone(), Expr::from(1))
+}
+
+/// Order monomials by the bases and then the exponents of their factors, so that
+/// `x` comes before `x^2`, which comes before `y`.
+fn monomial_order(a: &Expr, b: &Expr) -> Ordering {
+    fn factors(expr: &Expr) -> Vec<(Expr, Expr)> {
+        match expr.try_as_normal() {
+            Some(normal) if head_name(expr) == Some("System`Times") => {
+                normal.elements().iter().map(split_power).collect()
+            },
+            _ => vec![split_power(expr)],
+        }
+    }
+
+    let (a, b) = (factors(a), factors(b));
+
+    a.iter()
+        .zip(&b)
+        .map(|((a_base, a_exponent), (b_base, b_exponent))| {
+            canonical_order(a_base, b_base)
+                .then_with(|| canonical_order(a_exponent, b_exponent))
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// If `expr` is a normal expression with a symbol head, return the name of the head.
+fn head_name(expr: &Expr) -> Option<&str> {
+    expr.try_as_normal()?
+        .head()
+        .try_as_symbol()
+        .map(|symbol| symbol.as_str())
+}
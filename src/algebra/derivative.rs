@@ -0,0 +1,371 @@
+use crate::{Expr, ExprKind, Normal, Symbol};
+
+use super::{canonicalize, head_name};
+
+
+/// Compute the partial derivative of `expr` with respect to `x`, like
+/// [`D`](https://reference.wolfram.com/language/ref/D.html)`[expr, x]` <sub>WL</sub>.
+///
+/// Derivatives of the following are supported:
+///
+/// * `Plus`, `Times`, `Power`, `Subtract`, `Minus` and `Divide`
+/// * `Exp`, `Log` and `Sqrt`
+/// * `Sin`, `Cos`, `Tan`, `Cot`, `Sec`, `Csc`, `ArcSin`, `ArcCos` and `ArcTan`
+/// * `Sinh`, `Cosh`, `Tanh`, `Coth`, `Sech` and `Csch`
+/// * Lists, element-wise
+///
+/// Any other function `f` whose head does not contain `x`, including heads like
+/// `f[a]` that are not symbols, is treated as an unknown function, and is
+/// differentiated using the chain rule, like `f[g[x]]` to
+/// `Derivative[1][f][g[x]] g'[x]`.
+///
+/// Any subexpression that does not contain `x` has a derivative of zero. The
+/// derivative of an expression whose head contains `x`, like `f[x][x]` or `x[1]`, is
+/// left unevaluated as `D[expr, x]`. The result is put into canonical form using
+/// [`canonicalize()`].
+///
+/// Subexpressions are differentiated without recursion, so this can be used on
+/// arbitrarily deep expressions without overflowing the stack.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{algebra::derivative, wl, Symbol};
+///
+/// let x = Symbol::new("Global`x");
+///
+/// // D[x^3 + Sin[x], x]
+/// let expr = wl!(Plus[Power[Global::x, 3], Sin[Global::x]]);
+///
+/// // 3 x^2 + Cos[x]
+/// assert_eq!(
+///     derivative(&expr, &x),
+///     wl!(Plus[Times[3, Power[Global::x, 2]], Cos[Global::x]])
+/// );
+/// ```
+pub fn derivative(expr: &Expr, x: &Symbol) -> Expr {
+    canonicalize(&differentiate(&canonicalize(expr), x))
+}
+
+/// Differentiate `expr` without canonicalizing the result.
+fn differentiate(expr: &Expr, x: &Symbol) -> Expr {
+    // Derivatives of completed subexpressions that have not been consumed by their
+    // parent, and whether each subexpression contains `x`.
+    let mut values: Vec<(Expr, bool)> = Vec::new();
+
+    let mut stack: Vec<(&Expr, bool)> = vec![(expr, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        let normal = match expr.kind() {
+            ExprKind::Normal(normal) => normal,
+            ExprKind::Symbol(symbol) if symbol == x => {
+                values.push((Expr::from(1), true));
+                continue;
+            },
+            ExprKind::Integer(_)
+            | ExprKind::Real(_)
+            | ExprKind::String(_)
+            | ExprKind::Symbol(_) => {
+                values.push((Expr::from(0), false));
+                continue;
+            },
+        };
+
+        if !children_visited {
+            stack.push((expr, true));
+
+            for element in normal.elements().iter().rev() {
+                stack.push((element, false));
+            }
+            stack.push((normal.head(), false));
+
+            continue;
+        }
+
+        let elements = values.split_off(values.len() - normal.elements().len());
+        let (_, head_contains) = values
+            .pop()
+            .expect("differentiate: head of normal expression was not visited");
+
+        let contains: Vec<bool> =
+            elements.iter().map(|(_, contains)| *contains).collect();
+        let derivatives: Vec<Expr> = elements
+            .into_iter()
+            .map(|(derivative, _)| derivative)
+            .collect();
+
+        let value = if head_contains {
+            // The derivative of an expression like `x[1]` can't be computed from
+            // the derivatives of its elements.
+            (unevaluated(expr, x), true)
+        } else if contains.contains(&true) {
+            (
+                differentiate_normal(expr, normal, &derivatives, &contains),
+                true,
+            )
+        } else {
+            (Expr::from(0), false)
+        };
+
+        values.push(value);
+    }
+
+    debug_assert!(values.len() == 1);
+
+    values
+        .pop()
+        .expect("differentiate: no value computed for root expression")
+        .0
+}
+
+/// Differentiate `expr`, a normal expression whose elements contain `x` but whose head
+/// does not, given the derivatives of its elements and whether each element contains
+/// `x`.
+fn differentiate_normal(
+    expr: &Expr,
+    normal: &Normal,
+    derivatives: &[Expr],
+    contains: &[bool],
+) -> Expr {
+    match (head_name(expr), normal.elements()) {
+        (Some("System`Plus"), _) => plus(derivatives.to_vec()),
+        (Some("System`Times"), factors) => product_rule(factors, derivatives, contains),
+        (Some("System`Power"), [base, exponent]) => {
+            power_rule(base, exponent, derivatives, contains)
+        },
+        (Some("System`List"), _) => Expr::list(derivatives.to_vec()),
+        (Some(name), [u]) => match elementary(name, u) {
+            Some(outer) => times(vec![outer, derivatives[0].clone()]),
+            None => chain_rule(normal, derivatives, contains),
+        },
+        (Some("System`Log"), [base, u]) if !contains[0] => {
+            // Log[b, u]' == u' / (u Log[b])
+            times(vec![
+                derivatives[1].clone(),
+                power(u.clone(), -1),
+                power(log(base.clone()), -1),
+            ])
+        },
+        _ => chain_rule(normal, derivatives, contains),
+    }
+}
+
+/// Returns the derivative of `name[u]` with respect to `u`, if `name` is a supported
+/// elementary function of one argument.
+fn elementary(name: &str, u: &Expr) -> Option<Expr> {
+    let call = |head: Symbol| apply(head, u.clone());
+    let negative = |expr: Expr| times(vec![Expr::from(-1), expr]);
+    let u2 = || power(u.clone(), 2);
+
+    let derivative = match name {
+        "System`Exp" => call(crate::symbol!("System`Exp")),
+        "System`Log" => power(u.clone(), -1),
+        "System`Sqrt" => times(vec![
+            rational(1, 2),
+            Expr::normal(crate::symbol!("System`Power"), vec![
+                u.clone(),
+                rational(-1, 2),
+            ]),
+        ]),
+
+        "System`Sin" => call(crate::symbol!("System`Cos")),
+        "System`Cos" => negative(call(crate::symbol!("System`Sin"))),
+        "System`Tan" => power(call(crate::symbol!("System`Sec")), 2),
+        "System`Cot" => negative(power(call(crate::symbol!("System`Csc")), 2)),
+        "System`Sec" => times(vec![
+            call(crate::symbol!("System`Sec")),
+            call(crate::symbol!("System`Tan")),
+        ]),
+        "System`Csc" => negative(times(vec![
+            call(crate::symbol!("System`Csc")),
+            call(crate::symbol!("System`Cot")),
+        ])),
+
+        // 1/Sqrt[1 - u^2]
+        "System`ArcSin" => Expr::normal(crate::symbol!("System`Power"), vec![
+            plus(vec![Expr::from(1), negative(u2())]),
+            rational(-1, 2),
+        ]),
+        "System`ArcCos" => negative(Expr::normal(crate::symbol!("System`Power"), vec![
+            plus(vec![Expr::from(1), negative(u2())]),
+            rational(-1, 2),
+        ])),
+        "System`ArcTan" => power(plus(vec![Expr::from(1), u2()]), -1),
+
+        "System`Sinh" => call(crate::symbol!("System`Cosh")),
+        "System`Cosh" => call(crate::symbol!("System`Sinh")),
+        "System`Tanh" => power(call(crate::symbol!("System`Sech")), 2),
+        "System`Coth" => negative(power(call(crate::symbol!("System`Csch")), 2)),
+        "System`Sech" => negative(times(vec![
+            call(crate::symbol!("System`Sech")),
+            call(crate::symbol!("System`Tanh")),
+        ])),
+        "System`Csch" => negative(times(vec![
+            call(crate::symbol!("System`Csch")),
+            call(crate::symbol!("System`Coth")),
+        ])),
+
+        _ => return None,
+    };
+
+    Some(derivative)
+}
+
+/// `(a b c)' == a' b c + a b' c + a b c'`
+fn product_rule(factors: &[Expr], derivatives: &[Expr], contains: &[bool]) -> Expr {
+    let terms = (0..factors.len())
+        .filter(|index| contains[*index])
+        .map(|index| {
+            let mut product = factors.to_vec();
+            product[index] = derivatives[index].clone();
+            times(product)
+        })
+        .collect();
+
+    plus(terms)
+}
+
+/// Differentiate `base^exponent`, given the derivatives of `base` and `exponent`.
+fn power_rule(
+    base: &Expr,
+    exponent: &Expr,
+    derivatives: &[Expr],
+    contains: &[bool],
+) -> Expr {
+    let pow = || {
+        Expr::normal(crate::symbol!("System`Power"), vec![
+            base.clone(),
+            exponent.clone(),
+        ])
+    };
+
+    let (d_base, d_exponent) = (derivatives[0].clone(), derivatives[1].clone());
+
+    match (contains[0], contains[1]) {
+        // (u^n)' == n u^(n - 1) u'
+        (true, false) => times(vec![
+            exponent.clone(),
+            Expr::normal(crate::symbol!("System`Power"), vec![
+                base.clone(),
+                plus(vec![exponent.clone(), Expr::from(-1)]),
+            ]),
+            d_base,
+        ]),
+        // (b^v)' == b^v Log[b] v'
+        (false, true) => times(vec![pow(), log(base.clone()), d_exponent]),
+        // (u^v)' == u^v (v' Log[u] + v u' / u)
+        (true, true) => times(vec![
+            pow(),
+            plus(vec![
+                times(vec![d_exponent, log(base.clone())]),
+                times(vec![exponent.clone(), d_base, power(base.clone(), -1)]),
+            ]),
+        ]),
+        (false, false) => Expr::from(0),
+    }
+}
+
+/// Differentiate `f[u1, u2, ...]` for an unknown function `f`, producing
+/// `Derivative[1, 0, ...][f][u1, u2, ...] u1' + Derivative[0, 1, ...][f][...] u2' + ...`.
+///
+/// If `f` is itself `Derivative[n1, n2, ...][g]`, the orders are incremented instead.
+fn chain_rule(normal: &Normal, derivatives: &[Expr], contains: &[bool]) -> Expr {
+    let elements = normal.elements();
+
+    let (function, orders) = match derivative_head(normal) {
+        Some((function, orders)) if orders.len() == elements.len() => (function, orders),
+        _ => (normal.head().clone(), vec![0; elements.len()]),
+    };
+
+    let terms = (0..elements.len())
+        .filter(|index| contains[*index])
+        .map(|index| {
+            let mut orders = orders.clone();
+            orders[index] += 1;
+
+            let orders = orders.into_iter().map(Expr::from).collect();
+            let derivative = Expr::normal(crate::symbol!("System`Derivative"), orders);
+            let derivative = Expr::normal(
+                Expr::normal(derivative, vec![function.clone()]),
+                elements.to_vec(),
+            );
+
+            times(vec![derivative, derivatives[index].clone()])
+        })
+        .collect();
+
+    plus(terms)
+}
+
+/// If the head of `normal` is `Derivative[n1, n2, ...][f]`, return `f` and the
+/// orders `n1, n2, ...`.
+fn derivative_head(normal: &Normal) -> Option<(Expr, Vec<i64>)> {
+    let head = normal.head().try_as_normal()?;
+    let function = match head.elements() {
+        [function] => function,
+        _ => return None,
+    };
+
+    let derivative = head.head();
+    if head_name(derivative) != Some("System`Derivative") {
+        return None;
+    }
+
+    let orders = derivative
+        .try_as_normal()?
+        .elements()
+        .iter()
+        .map(|order| match order.kind() {
+            ExprKind::Integer(order) => Some(*order),
+            _ => None,
+        })
+        .collect::<Option<Vec<i64>>>()?;
+
+    Some((function.clone(), orders))
+}
+
+//======================================
+// Helpers
+//======================================
+
+/// `D[expr, x]`, left unevaluated.
+fn unevaluated(expr: &Expr, x: &Symbol) -> Expr {
+    Expr::normal(crate::symbol!("System`D"), vec![
+        expr.clone(),
+        Expr::from(x.clone()),
+    ])
+}
+
+fn apply(head: Symbol, argument: Expr) -> Expr {
+    Expr::normal(head, vec![argument])
+}
+
+fn plus(terms: Vec<Expr>) -> Expr {
+    Expr::normal(crate::symbol!("System`Plus"), terms)
+}
+
+fn times(factors: Vec<Expr>) -> Expr {
+    Expr::normal(crate::symbol!("System`Times"), factors)
+}
+
+fn power(base: Expr, exponent: i64) -> Expr {
+    Expr::normal(crate::symbol!("System`Power"), vec![
+        base,
+        Expr::from(exponent),
+    ])
+}
+
+fn rational(numerator: i64, denominator: i64) -> Expr {
+    Expr::normal(crate::symbol!("System`Rational"), vec![
+        Expr::from(numerator),
+        Expr::from(denominator),
+    ])
+}
+
+/// `Log[expr]`, with `Log[E]` simplified to `1`.
+fn log(expr: Expr) -> Expr {
+    match expr.try_as_symbol() {
+        Some(symbol) if symbol.as_str() == "System`E" => Expr::from(1),
+        _ => apply(crate::symbol!("System`Log"), expr),
+    }
+}
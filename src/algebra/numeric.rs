@@ -0,0 +1,534 @@
+use std::{
+    collections::HashMap,
+    f64::consts,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::{Expr, ExprKind, Number, Symbol};
+
+use super::head_name;
+
+
+/// Error returned by [`numeric_eval()`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumericError {
+    /// A symbol with no numeric value remained in the expression.
+    UnknownSymbol(Symbol),
+    /// An expression with an unsupported head or number of elements, or a string,
+    /// remained in the expression.
+    Unsupported(Expr),
+    /// The result has a nonzero imaginary part.
+    ComplexResult {
+        /// Real part of the result.
+        re: f64,
+        /// Imaginary part of the result.
+        im: f64,
+    },
+    /// The expression divides by zero, producing `ComplexInfinity`.
+    ComplexInfinity,
+    /// The expression has no well-defined value, like `0^0`.
+    Indeterminate,
+}
+
+/// Numerically evaluate `expr` using machine-precision arithmetic, like
+/// [`N`](https://reference.wolfram.com/language/ref/N.html)`[expr]` <sub>WL</sub>.
+///
+/// Symbols are replaced by their value in `values`. The following are supported:
+///
+/// * Integers, reals, `Rational[n, d]` and `Complex[re, im]`
+/// * The constants `Pi`, `E`, `Degree`, `I`, `EulerGamma`, `GoldenRatio` and `Catalan`
+/// * `Plus`, `Times`, `Power`, `Subtract`, `Minus`, `Divide` and `Sqrt`
+/// * `Exp`, and `Log` with one or two arguments
+/// * `Sin`, `Cos`, `Tan`, `Cot`, `Sec`, `Csc`, `ArcSin`, `ArcCos`, and `ArcTan` with one
+///   or two arguments
+/// * `Sinh`, `Cosh`, `Tanh`, `Coth`, `Sech`, `Csch`, `ArcSinh`, `ArcCosh` and `ArcTanh`
+/// * `Abs`, `Re`, `Im`, `Arg` and `Conjugate`
+///
+/// Intermediate results may be complex, but the result must be real: a result with a
+/// nonzero imaginary part returns [`NumericError::ComplexResult`]. Use `Re`, `Im`,
+/// `Abs` or `Arg` to compute a real value from a complex one.
+///
+/// The result is always a [`Number::Real`]. It may be infinite if the computation
+/// overflows, or if it takes the logarithm of zero.
+///
+/// Subexpressions are evaluated without recursion, so this can be used on arbitrarily
+/// deep expressions without overflowing the stack.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use wolfram_expr::{algebra::numeric_eval, wl, Number, Symbol};
+///
+/// let mut values = HashMap::new();
+/// values.insert(Symbol::new("Global`x"), 2.0);
+///
+/// // x^2 + Cos[Pi]
+/// let expr = wl!(Plus[Power[Global::x, 2], Cos[Pi]]);
+///
+/// assert_eq!(numeric_eval(&expr, &values), Ok(Number::real(3.0)));
+///
+/// // Re[Exp[I Pi]]
+/// let expr = wl!(Re[Exp[Times[I, Pi]]]);
+///
+/// assert_eq!(numeric_eval(&expr, &values), Ok(Number::real(-1.0)));
+/// ```
+pub fn numeric_eval(
+    expr: &Expr,
+    values: &HashMap<Symbol, f64>,
+) -> Result<Number, NumericError> {
+    let Complex { re, im } = eval(expr, values)?;
+
+    if re.is_nan() || im.is_nan() {
+        return Err(NumericError::Indeterminate);
+    }
+
+    if im != 0.0 {
+        return Err(NumericError::ComplexResult { re, im });
+    }
+
+    Ok(Number::real(re))
+}
+
+/// Evaluate `expr`, without recursion.
+fn eval(expr: &Expr, values: &HashMap<Symbol, f64>) -> Result<Complex, NumericError> {
+    // Values of completed subexpressions that have not been consumed by their parent.
+    let mut results: Vec<Complex> = Vec::new();
+
+    // Traverse `expr` in post-order, so that the elements of every normal expression
+    // are evaluated, from left to right, before the expression itself.
+    let mut stack: Vec<(&Expr, bool)> = vec![(expr, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        let value = match expr.kind() {
+            ExprKind::Integer(int) => Complex::real(*int as f64),
+            ExprKind::Real(real) => Complex::real(**real),
+            ExprKind::Symbol(symbol) => symbol_value(symbol, values)?,
+            ExprKind::String(_) => return Err(NumericError::Unsupported(expr.clone())),
+            ExprKind::Normal(normal) if !children_visited => {
+                stack.push((expr, true));
+
+                for element in normal.elements().iter().rev() {
+                    stack.push((element, false));
+                }
+
+                continue;
+            },
+            ExprKind::Normal(normal) => {
+                let args = results.split_off(results.len() - normal.elements().len());
+                eval_normal(expr, &args)?
+            },
+        };
+
+        results.push(value);
+    }
+
+    debug_assert!(results.len() == 1);
+
+    Ok(results
+        .pop()
+        .expect("numeric_eval: no value computed for root expression"))
+}
+
+/// Evaluate the normal expression `expr`, given the values of its elements.
+fn eval_normal(expr: &Expr, args: &[Complex]) -> Result<Complex, NumericError> {
+    let unsupported = || NumericError::Unsupported(expr.clone());
+
+    let name = match head_name(expr) {
+        Some(name) => name,
+        None => return Err(unsupported()),
+    };
+
+    let value = match (name, args) {
+        ("System`Plus", args) => args.iter().fold(Complex::real(0.0), |a, b| a + *b),
+        ("System`Times", args) => args.iter().fold(Complex::real(1.0), |a, b| a * *b),
+        ("System`Subtract", [a, b]) => *a - *b,
+        ("System`Minus", [a]) => -*a,
+        ("System`Divide" | "System`Rational", [a, b]) => a.div(*b)?,
+        ("System`Complex", [re, im]) => *re + Complex::I * *im,
+        ("System`Power", [base, exponent]) => base.pow(*exponent)?,
+        ("System`Sqrt", [z]) => z.sqrt(),
+
+        ("System`Exp", [z]) => z.exp(),
+        ("System`Log", [z]) => z.ln(),
+        ("System`Log", [base, z]) => z.ln().div(base.ln())?,
+
+        ("System`Sin", [z]) => z.sin(),
+        ("System`Cos", [z]) => z.cos(),
+        ("System`Tan", [z]) => z.sin().div(z.cos())?,
+        ("System`Cot", [z]) => z.cos().div(z.sin())?,
+        ("System`Sec", [z]) => Complex::real(1.0).div(z.cos())?,
+        ("System`Csc", [z]) => Complex::real(1.0).div(z.sin())?,
+        ("System`ArcSin", [z]) => z.arcsin(),
+        ("System`ArcCos", [z]) => z.arccos(),
+        ("System`ArcTan", [z]) => z.arctan(),
+        ("System`ArcTan", [x, y]) if x.is_real() && y.is_real() => {
+            Complex::real(y.re.atan2(x.re))
+        },
+
+        ("System`Sinh", [z]) => z.sinh(),
+        ("System`Cosh", [z]) => z.cosh(),
+        ("System`Tanh", [z]) => z.sinh().div(z.cosh())?,
+        ("System`Coth", [z]) => z.cosh().div(z.sinh())?,
+        ("System`Sech", [z]) => Complex::real(1.0).div(z.cosh())?,
+        ("System`Csch", [z]) => Complex::real(1.0).div(z.sinh())?,
+        ("System`ArcSinh", [z]) => z.arcsinh(),
+        ("System`ArcCosh", [z]) => z.arccosh(),
+        ("System`ArcTanh", [z]) => z.arctanh(),
+
+        ("System`Abs", [z]) => Complex::real(z.abs()),
+        ("System`Re", [z]) => Complex::real(z.re),
+        ("System`Im", [z]) => Complex::real(z.im),
+        ("System`Arg", [z]) => Complex::real(z.arg()),
+        ("System`Conjugate", [z]) => Complex {
+            re: z.re,
+            im: -z.im,
+        },
+
+        _ => return Err(unsupported()),
+    };
+
+    Ok(value)
+}
+
+fn symbol_value(
+    symbol: &Symbol,
+    values: &HashMap<Symbol, f64>,
+) -> Result<Complex, NumericError> {
+    if let Some(value) = values.get(symbol) {
+        return Ok(Complex::real(*value));
+    }
+
+    let value = match symbol.as_str() {
+        "System`Pi" => consts::PI,
+        "System`E" => consts::E,
+        "System`Degree" => consts::PI / 180.0,
+        "System`EulerGamma" => 0.577_215_664_901_532_9,
+        "System`GoldenRatio" => 1.618_033_988_749_895,
+        "System`Catalan" => 0.915_965_594_177_219,
+        "System`I" => return Ok(Complex::I),
+        _ => return Err(NumericError::UnknownSymbol(symbol.clone())),
+    };
+
+    Ok(Complex::real(value))
+}
+
+//======================================
+// Complex arithmetic
+//======================================
+
+/// Machine-precision complex number.
+///
+/// Operations on values with a zero imaginary part use real arithmetic, so that real
+/// results are not polluted by rounding errors or NaNs from the imaginary part.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    fn real(re: f64) -> Self {
+        Complex { re, im: 0.0 }
+    }
+
+    fn is_real(self) -> bool {
+        self.im == 0.0
+    }
+
+    fn is_zero(self) -> bool {
+        self.re == 0.0 && self.im == 0.0
+    }
+
+    fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    fn div(self, other: Complex) -> Result<Complex, NumericError> {
+        if other.is_zero() {
+            return Err(if self.is_zero() {
+                NumericError::Indeterminate
+            } else {
+                NumericError::ComplexInfinity
+            });
+        }
+
+        if self.is_real() && other.is_real() {
+            return Ok(Complex::real(self.re / other.re));
+        }
+
+        let denominator = other.re * other.re + other.im * other.im;
+
+        Ok(Complex {
+            re: (self.re * other.re + self.im * other.im) / denominator,
+            im: (self.im * other.re - self.re * other.im) / denominator,
+        })
+    }
+
+    fn pow(self, exponent: Complex) -> Result<Complex, NumericError> {
+        if self.is_zero() {
+            return match exponent.re {
+                re if re > 0.0 => Ok(Complex::real(0.0)),
+                re if re < 0.0 => Err(NumericError::ComplexInfinity),
+                _ => Err(NumericError::Indeterminate),
+            };
+        }
+
+        if exponent.is_real() {
+            let n = exponent.re;
+
+            if self.is_real() && (self.re > 0.0 || n.fract() == 0.0) {
+                return Ok(Complex::real(self.re.powf(n)));
+            }
+
+            // Repeated multiplication is exact for powers of I.
+            if n.fract() == 0.0 && n.abs() <= f64::from(i32::MAX) {
+                let power = self.powi(n.abs() as u32);
+                return if n < 0.0 {
+                    Complex::real(1.0).div(power)
+                } else {
+                    Ok(power)
+                };
+            }
+        }
+
+        Ok((exponent * self.ln()).exp())
+    }
+
+    fn powi(self, mut n: u32) -> Complex {
+        let mut base = self;
+        let mut result = Complex::real(1.0);
+
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            n >>= 1;
+        }
+
+        result
+    }
+
+    fn sqrt(self) -> Complex {
+        if self.is_real() && self.re >= 0.0 {
+            return Complex::real(self.re.sqrt());
+        }
+
+        let r = self.abs();
+        let im = ((r - self.re) / 2.0).sqrt();
+
+        Complex {
+            re: ((r + self.re) / 2.0).sqrt(),
+            im: if self.im < 0.0 { -im } else { im },
+        }
+    }
+
+    fn exp(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.exp());
+        }
+
+        let scale = self.re.exp();
+
+        Complex {
+            re: scale * self.im.cos(),
+            im: scale * self.im.sin(),
+        }
+    }
+
+    /// Principal value of the natural logarithm.
+    fn ln(self) -> Complex {
+        if self.is_real() && self.re >= 0.0 {
+            return Complex::real(self.re.ln());
+        }
+
+        Complex {
+            re: self.abs().ln(),
+            im: self.arg(),
+        }
+    }
+
+    fn sin(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.sin());
+        }
+
+        Complex {
+            re: self.re.sin() * self.im.cosh(),
+            im: self.re.cos() * self.im.sinh(),
+        }
+    }
+
+    fn cos(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.cos());
+        }
+
+        Complex {
+            re: self.re.cos() * self.im.cosh(),
+            im: -self.re.sin() * self.im.sinh(),
+        }
+    }
+
+    fn sinh(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.sinh());
+        }
+
+        Complex {
+            re: self.re.sinh() * self.im.cos(),
+            im: self.re.cosh() * self.im.sin(),
+        }
+    }
+
+    fn cosh(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.cosh());
+        }
+
+        Complex {
+            re: self.re.cosh() * self.im.cos(),
+            im: self.re.sinh() * self.im.sin(),
+        }
+    }
+
+    /// `-I Log[I z + Sqrt[1 - z^2]]`
+    fn arcsin(self) -> Complex {
+        if self.is_real() && self.re.abs() <= 1.0 {
+            return Complex::real(self.re.asin());
+        }
+
+        let one = Complex::real(1.0);
+        -Complex::I * (Complex::I * self + (one - self * self).sqrt()).ln()
+    }
+
+    /// `Pi/2 - ArcSin[z]`
+    fn arccos(self) -> Complex {
+        if self.is_real() && self.re.abs() <= 1.0 {
+            return Complex::real(self.re.acos());
+        }
+
+        Complex::real(consts::FRAC_PI_2) - self.arcsin()
+    }
+
+    /// `I/2 (Log[1 - I z] - Log[1 + I z])`
+    fn arctan(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.atan());
+        }
+
+        let one = Complex::real(1.0);
+        let iz = Complex::I * self;
+        Complex { re: 0.0, im: 0.5 } * ((one - iz).ln() - (one + iz).ln())
+    }
+
+    /// `Log[z + Sqrt[z^2 + 1]]`
+    fn arcsinh(self) -> Complex {
+        if self.is_real() {
+            return Complex::real(self.re.asinh());
+        }
+
+        (self + (self * self + Complex::real(1.0)).sqrt()).ln()
+    }
+
+    /// `Log[z + Sqrt[z + 1] Sqrt[z - 1]]`
+    fn arccosh(self) -> Complex {
+        if self.is_real() && self.re >= 1.0 {
+            return Complex::real(self.re.acosh());
+        }
+
+        let one = Complex::real(1.0);
+        (self + (self + one).sqrt() * (self - one).sqrt()).ln()
+    }
+
+    /// `(Log[1 + z] - Log[1 - z]) / 2`
+    fn arctanh(self) -> Complex {
+        if self.is_real() && self.re.abs() < 1.0 {
+            return Complex::real(self.re.atanh());
+        }
+
+        let one = Complex::real(1.0);
+        Complex::real(0.5) * ((one + self).ln() - (one - self).ln())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        if self.is_real() && other.is_real() {
+            return Complex::real(self.re * other.re);
+        }
+
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex {
+            re: -self.re,
+            im: -self.im,
+        }
+    }
+}
+
+//======================================
+// Formatting
+//======================================
+
+impl fmt::Display for NumericError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NumericError::UnknownSymbol(symbol) => {
+                write!(f, "symbol {} has no numeric value", symbol)
+            },
+            NumericError::Unsupported(expr) => {
+                write!(f, "cannot numerically evaluate {}", expr)
+            },
+            NumericError::ComplexResult { re, im } => {
+                write!(f, "result is complex: {:?} + {:?} I", re, im)
+            },
+            NumericError::ComplexInfinity => write!(f, "division by zero"),
+            NumericError::Indeterminate => write!(f, "result is indeterminate"),
+        }
+    }
+}
+
+impl std::error::Error for NumericError {}
@@ -0,0 +1,109 @@
+//! Generation of arbitrary expressions for property-based testing.
+//!
+//! The `Arbitrary` implementations are split into one submodule per supported
+//! property-testing crate, each enabled by the cargo feature of the same name.
+
+#[cfg(feature = "proptest")]
+mod proptest_impls;
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls;
+
+
+/// Configuration of the size of arbitrary expressions generated for property-based
+/// testing.
+///
+/// `ArbitraryConfig` is used by the `Arbitrary` implementations for [`Expr`] and
+/// [`ExprKind`], which are available when the `proptest` or `quickcheck` cargo feature
+/// is enabled.
+///
+/// * With `proptest`, `ArbitraryConfig` is the `Arbitrary::Parameters` type of `Expr`
+///   and `ExprKind`, so a custom configuration can be used with
+///   `any_with::<Expr>(config)`.
+/// * With `quickcheck`, use
+///   [`ArbitraryConfig::arbitrary_expr()`][ArbitraryConfig::arbitrary_expr] to generate
+///   an expression using a custom configuration.
+///
+/// Symbols in generated expressions are always valid, and have a context path of 1 to
+/// 3 components. Shrinking a generated expression tries its subexpressions first,
+/// then normal expressions with fewer elements, and finally smaller atoms.
+///
+/// [`Expr`]: crate::Expr
+/// [`ExprKind`]: crate::ExprKind
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "proptest")] {
+/// use proptest::prelude::*;
+/// use wolfram_expr::{ArbitraryConfig, Expr};
+///
+/// let config = ArbitraryConfig {
+///     max_nesting: 3,
+///     ..ArbitraryConfig::default()
+/// };
+///
+/// proptest!(|(expr in any_with::<Expr>(config))| {
+///     prop_assert!(expr.nesting() <= 3);
+/// });
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ArbitraryConfig {
+    /// Maximum nesting of a generated expression, counting heads, as measured by
+    /// [`Expr::nesting()`][crate::Expr::nesting].
+    ///
+    /// This is consistent with [`Limits::max_nesting`][crate::Limits::max_nesting].
+    pub max_nesting: usize,
+    /// Maximum number of elements of each generated normal expression.
+    pub max_width: usize,
+    /// Maximum length in characters of each generated string.
+    pub max_string_length: usize,
+}
+
+/// The default configuration generates expressions that are small enough to keep
+/// property tests fast:
+///
+/// Field               | Default
+/// --------------------|---------
+/// `max_nesting`       | 4
+/// `max_width`         | 6
+/// `max_string_length` | 16
+impl Default for ArbitraryConfig {
+    fn default() -> Self {
+        ArbitraryConfig {
+            max_nesting: 4,
+            max_width: 6,
+            max_string_length: 16,
+        }
+    }
+}
+
+/// Characters that can start a generated symbol name.
+///
+/// Shrinking moves towards the start of this list, so it starts with simple ASCII
+/// letters.
+#[rustfmt::skip]
+const SYMBOL_START: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
+    'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M',
+    'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+    '$', 'α', 'é', 'Σ',
+];
+
+/// Characters that can appear after the first character of a generated symbol name.
+#[rustfmt::skip]
+const SYMBOL_CONTINUE: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
+    'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M',
+    'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+    '$', 'α', 'é', 'Σ',
+];
+
+/// Maximum length in characters of each generated symbol name or context component.
+const MAX_SYMBOL_NAME_LENGTH: usize = 8;
+
+/// Maximum number of components of each generated context.
+const MAX_CONTEXT_LENGTH: usize = 3;
@@ -0,0 +1,145 @@
+use std::{convert::TryFrom, iter};
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    prop_oneof,
+    sample::select,
+    strategy::{BoxedStrategy, Strategy},
+};
+
+use crate::{
+    symbol::{Context, SymbolName},
+    Expr, ExprKind, Number, Symbol, F64,
+};
+
+use super::{
+    ArbitraryConfig, MAX_CONTEXT_LENGTH, MAX_SYMBOL_NAME_LENGTH, SYMBOL_CONTINUE,
+    SYMBOL_START,
+};
+
+
+//======================================
+// Symbols
+//======================================
+
+/// Strategy for a single symbol name, like `x` or `$Var1`.
+fn symbol_name_string() -> impl Strategy<Value = String> {
+    (
+        select(SYMBOL_START),
+        vec(select(SYMBOL_CONTINUE), 0..MAX_SYMBOL_NAME_LENGTH),
+    )
+        .prop_map(|(first, rest)| iter::once(first).chain(rest).collect())
+}
+
+/// Strategy for an absolute context, like `` Global` `` or `` A`B` ``.
+fn context_string() -> impl Strategy<Value = String> {
+    vec(symbol_name_string(), 1..=MAX_CONTEXT_LENGTH)
+        .prop_map(|components| format!("{}`", components.join("`")))
+}
+
+impl Arbitrary for SymbolName {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<SymbolName>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        symbol_name_string()
+            .prop_map(|name| SymbolName::try_new(&name).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for Context {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Context>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        context_string()
+            .prop_map(|context| Context::new(&context))
+            .boxed()
+    }
+}
+
+impl Arbitrary for Symbol {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Symbol>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (context_string(), symbol_name_string())
+            .prop_map(|(context, name)| Symbol::new(&format!("{}{}", context, name)))
+            .boxed()
+    }
+}
+
+//======================================
+// Numbers
+//======================================
+
+impl Arbitrary for Number {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Number>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![
+            any::<i64>().prop_map(Number::Integer),
+            any::<f64>().prop_filter_map("real is NaN", |real| {
+                F64::new(real).ok().map(Number::Real)
+            }),
+        ]
+        .boxed()
+    }
+}
+
+//======================================
+// Expressions
+//======================================
+
+impl Arbitrary for Expr {
+    type Parameters = ArbitraryConfig;
+    type Strategy = BoxedStrategy<Expr>;
+
+    fn arbitrary_with(config: ArbitraryConfig) -> Self::Strategy {
+        let ArbitraryConfig {
+            max_nesting,
+            max_width,
+            max_string_length,
+        } = config;
+
+        let leaf = prop_oneof![
+            any::<Number>().prop_map(Expr::number),
+            vec(any::<char>(), 0..=max_string_length)
+                .prop_map(|chars| Expr::string(chars.into_iter().collect::<String>())),
+            any::<Symbol>().prop_map(Expr::symbol),
+        ];
+
+        if max_nesting <= 1 {
+            return leaf.boxed();
+        }
+
+        // The first level of the expression is generated by `leaf`, so recursion
+        // adds the remaining levels.
+        let depth = u32::try_from(max_nesting - 1).unwrap_or(u32::MAX);
+        let width = u32::try_from(max_width).unwrap_or(u32::MAX);
+
+        leaf.prop_recursive(depth, depth.saturating_mul(width), width, move |inner| {
+            // Most heads are symbols, as in typical expressions.
+            let head = prop_oneof![
+                3 => any::<Symbol>().prop_map(Expr::symbol),
+                1 => inner.clone(),
+            ];
+
+            (head, vec(inner, 0..=max_width))
+                .prop_map(|(head, elements)| Expr::normal(head, elements))
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for ExprKind {
+    type Parameters = ArbitraryConfig;
+    type Strategy = BoxedStrategy<ExprKind>;
+
+    fn arbitrary_with(config: ArbitraryConfig) -> Self::Strategy {
+        Expr::arbitrary_with(config).prop_map(Expr::to_kind).boxed()
+    }
+}
@@ -0,0 +1,259 @@
+use std::iter;
+
+use quickcheck::{Arbitrary, Gen};
+
+use crate::{
+    symbol::{Context, SymbolName},
+    Expr, ExprKind, Number, Symbol, F64,
+};
+
+use super::{
+    ArbitraryConfig, MAX_CONTEXT_LENGTH, MAX_SYMBOL_NAME_LENGTH, SYMBOL_CONTINUE,
+    SYMBOL_START,
+};
+
+
+impl ArbitraryConfig {
+    /// Generate an arbitrary expression using this configuration.
+    ///
+    /// The [`Arbitrary`] implementation for [`Expr`] uses
+    /// [`ArbitraryConfig::default()`].
+    ///
+    /// ```
+    /// use quickcheck::Gen;
+    /// use wolfram_expr::ArbitraryConfig;
+    ///
+    /// let config = ArbitraryConfig {
+    ///     max_nesting: 2,
+    ///     ..ArbitraryConfig::default()
+    /// };
+    ///
+    /// let expr = config.arbitrary_expr(&mut Gen::new(10));
+    ///
+    /// assert!(expr.nesting() <= 2);
+    /// ```
+    pub fn arbitrary_expr(&self, g: &mut Gen) -> Expr {
+        self.arbitrary_expr_with_nesting(g, self.max_nesting)
+    }
+
+    /// Generate an expression whose nesting, counting heads, is at most `nesting`.
+    fn arbitrary_expr_with_nesting(&self, g: &mut Gen, nesting: usize) -> Expr {
+        // Generate atoms and normal expressions with equal probability, as long as
+        // `nesting` allows it.
+        if nesting <= 1 || bool::arbitrary(g) {
+            return match below(g, 3) {
+                0 => Expr::number(Number::arbitrary(g)),
+                1 => {
+                    let len = below(g, self.max_string_length + 1);
+                    let string: String = (0..len).map(|_| char::arbitrary(g)).collect();
+                    Expr::string(string)
+                },
+                _ => Expr::symbol(Symbol::arbitrary(g)),
+            };
+        }
+
+        // Most heads are symbols, as in typical expressions.
+        let head = match below(g, 4) {
+            0 => self.arbitrary_expr_with_nesting(g, nesting - 1),
+            _ => Expr::symbol(Symbol::arbitrary(g)),
+        };
+
+        let len = below(g, self.max_width + 1);
+        let elements = (0..len)
+            .map(|_| self.arbitrary_expr_with_nesting(g, nesting - 1))
+            .collect();
+
+        Expr::normal(head, elements)
+    }
+}
+
+/// Generate an arbitrary integer in the range `0..bound`.
+fn below(g: &mut Gen, bound: usize) -> usize {
+    usize::arbitrary(g) % bound
+}
+
+//======================================
+// Symbols
+//======================================
+
+/// Generate a single symbol name, like `x` or `$Var1`.
+fn arbitrary_symbol_name(g: &mut Gen) -> String {
+    let first = *g.choose(SYMBOL_START).unwrap();
+    let len = below(g, MAX_SYMBOL_NAME_LENGTH);
+
+    iter::once(first)
+        .chain((0..len).map(|_| *g.choose(SYMBOL_CONTINUE).unwrap()))
+        .collect()
+}
+
+/// Generate the components of an absolute context.
+fn arbitrary_context_components(g: &mut Gen) -> Vec<String> {
+    let len = 1 + below(g, MAX_CONTEXT_LENGTH);
+
+    (0..len).map(|_| arbitrary_symbol_name(g)).collect()
+}
+
+/// Shrink a symbol-like value made up of `components`, by removing components while
+/// at least `min_len` remain, and by truncating each component to its first character.
+///
+/// The results are still made up of valid symbol names.
+fn shrink_components(components: &[&str], min_len: usize) -> Vec<Vec<String>> {
+    let to_owned = |components: &[&str]| -> Vec<String> {
+        components
+            .iter()
+            .map(|component| component.to_string())
+            .collect()
+    };
+
+    let mut shrunk = Vec::new();
+
+    if components.len() > min_len {
+        for index in 0..components.len() {
+            let mut components = to_owned(components);
+            components.remove(index);
+            shrunk.push(components);
+        }
+    }
+
+    for (index, component) in components.iter().enumerate() {
+        let mut chars = component.chars();
+        let first = chars.next();
+
+        if let (Some(first), Some(_)) = (first, chars.next()) {
+            let mut components = to_owned(components);
+            components[index] = first.to_string();
+            shrunk.push(components);
+        }
+    }
+
+    shrunk
+}
+
+impl Arbitrary for SymbolName {
+    fn arbitrary(g: &mut Gen) -> Self {
+        SymbolName::try_new(&arbitrary_symbol_name(g)).unwrap()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let shrunk = shrink_components(&[self.as_str()], 1)
+            .into_iter()
+            .map(|components| SymbolName::try_new(&components[0]).unwrap());
+
+        Box::new(shrunk)
+    }
+}
+
+impl Arbitrary for Context {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Context::new(&format!("{}`", arbitrary_context_components(g).join("`")))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let components: Vec<&str> = self.components().map(|c| c.as_str()).collect();
+
+        let shrunk = shrink_components(&components, 1)
+            .into_iter()
+            .map(|components| Context::new(&format!("{}`", components.join("`"))));
+
+        Box::new(shrunk)
+    }
+}
+
+impl Arbitrary for Symbol {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut components = arbitrary_context_components(g);
+        components.push(arbitrary_symbol_name(g));
+
+        Symbol::new(&components.join("`"))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let components: Vec<&str> = self.as_str().split('`').collect();
+
+        let shrunk = shrink_components(&components, 2)
+            .into_iter()
+            .map(|components| Symbol::new(&components.join("`")));
+
+        Box::new(shrunk)
+    }
+}
+
+//======================================
+// Numbers
+//======================================
+
+impl Arbitrary for Number {
+    fn arbitrary(g: &mut Gen) -> Self {
+        if bool::arbitrary(g) {
+            Number::Integer(i64::arbitrary(g))
+        } else {
+            // NaN is not a valid `Number`.
+            Number::Real(F64::new(f64::arbitrary(g)).unwrap_or_default())
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        match *self {
+            Number::Integer(int) => Box::new(int.shrink().map(Number::Integer)),
+            Number::Real(real) => Box::new(
+                real.into_inner()
+                    .shrink()
+                    .filter_map(|real| F64::new(real).ok())
+                    .map(Number::Real),
+            ),
+        }
+    }
+}
+
+//======================================
+// Expressions
+//======================================
+
+impl Arbitrary for Expr {
+    fn arbitrary(g: &mut Gen) -> Self {
+        ArbitraryConfig::default().arbitrary_expr(g)
+    }
+
+    /// Shrinks a normal expression to its head and each of its elements first, then to
+    /// normal expressions with fewer or smaller elements, and finally to normal
+    /// expressions with a smaller head.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        match self.kind() {
+            ExprKind::Integer(int) => Box::new(int.shrink().map(Expr::from)),
+            ExprKind::Real(real) => {
+                Box::new(Number::Real(*real).shrink().map(Expr::number))
+            },
+            ExprKind::String(string) => {
+                Box::new(string.to_string().shrink().map(Expr::string))
+            },
+            ExprKind::Symbol(symbol) => Box::new(symbol.shrink().map(Expr::symbol)),
+            ExprKind::Normal(normal) => {
+                let head = normal.head().clone();
+                let elements = normal.elements().to_vec();
+
+                let subexpressions = iter::once(head.clone()).chain(elements.clone());
+
+                let smaller_elements = elements.shrink().map({
+                    let head = head.clone();
+                    move |elements| Expr::normal(head.clone(), elements)
+                });
+
+                let smaller_heads = head
+                    .shrink()
+                    .map(move |head| Expr::normal(head, elements.clone()));
+
+                Box::new(subexpressions.chain(smaller_elements).chain(smaller_heads))
+            },
+        }
+    }
+}
+
+impl Arbitrary for ExprKind {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Expr::arbitrary(g).to_kind()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(Expr::new(self.clone()).shrink().map(Expr::to_kind))
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    convert, eq_iteratively, fmt_iteratively, fmt_kind, fmt_normal, hash_iteratively,
+    Expr, ExprKind, ExprPtr, FmtTask, Normal, Number, SmallString, Symbol,
+};
+
+
+/// Arena that owns the nodes of [`ArenaExpr`] expressions.
+///
+/// Nodes are bump-allocated in large chunks, and are all freed at once when the arena
+/// is dropped. This makes `ExprArena` suitable for workloads that construct and then
+/// discard very large numbers of short-lived expressions.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{ArenaExpr, Expr, ExprArena, Symbol};
+///
+/// let arena = ExprArena::new();
+///
+/// // f[x, x]
+/// let x = arena.symbol(Symbol::new("Global`x"));
+/// let f_x_x = arena.normal(arena.symbol(Symbol::new("Global`f")), vec![x, x]);
+///
+/// assert_eq!(f_x_x.to_string(), "Global`f[Global`x, Global`x]");
+///
+/// let expr: Expr = f_x_x.to_expr();
+/// assert_eq!(arena.import(&expr), f_x_x);
+/// ```
+pub struct ExprArena<'a> {
+    nodes: typed_arena::Arena<ExprKind<ArenaExpr<'a>>>,
+}
+
+/// Wolfram Language expression whose nodes are allocated in an [`ExprArena`].
+///
+/// An `ArenaExpr` is a shared reference to a node owned by the arena, so it is `Copy`,
+/// and the head and elements of a normal `ArenaExpr` are themselves `ArenaExpr`
+/// references into the same arena. Nodes may be shared by any number of parent
+/// expressions.
+///
+/// `ArenaExpr` uses the same [`ExprKind`] and [`Normal`] types as [`Expr`], as
+/// `ExprKind<ArenaExpr>` and `Normal<ArenaExpr>`.
+#[derive(Copy, Clone)]
+pub struct ArenaExpr<'a> {
+    kind: &'a ExprKind<ArenaExpr<'a>>,
+}
+
+impl<'a> ExprArena<'a> {
+    /// Construct a new, empty arena.
+    pub fn new() -> Self {
+        ExprArena {
+            nodes: typed_arena::Arena::new(),
+        }
+    }
+
+    /// Construct a new arena with space preallocated for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ExprArena {
+            nodes: typed_arena::Arena::with_capacity(capacity),
+        }
+    }
+
+    /// Number of nodes allocated in this arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if no nodes have been allocated in this arena.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Allocate a new expression from an [`ExprKind`].
+    pub fn alloc(&'a self, kind: ExprKind<ArenaExpr<'a>>) -> ArenaExpr<'a> {
+        ArenaExpr {
+            kind: self.nodes.alloc(kind),
+        }
+    }
+
+    /// Copy `expr` into this arena.
+    ///
+    /// Subexpressions that are shared within `expr` are allocated only once, and are
+    /// shared in the returned expression.
+    pub fn import(&'a self, expr: &Expr) -> ArenaExpr<'a> {
+        convert(expr, |kind| self.alloc(kind))
+    }
+
+    //==================================
+    // Constructors
+    //==================================
+
+    /// Allocate a new normal expression from the head and elements.
+    pub fn normal(
+        &'a self,
+        head: ArenaExpr<'a>,
+        contents: Vec<ArenaExpr<'a>>,
+    ) -> ArenaExpr<'a> {
+        self.alloc(ExprKind::Normal(Normal { head, contents }))
+    }
+
+    /// Allocate a new expression from a [`Symbol`].
+    pub fn symbol<S: Into<Symbol>>(&'a self, s: S) -> ArenaExpr<'a> {
+        self.alloc(ExprKind::Symbol(s.into()))
+    }
+
+    /// Allocate a new expression from a [`Number`].
+    pub fn number(&'a self, num: Number) -> ArenaExpr<'a> {
+        self.alloc(ExprKind::from(num))
+    }
+
+    /// Allocate a new expression from a string.
+    pub fn string<S: Into<SmallString>>(&'a self, s: S) -> ArenaExpr<'a> {
+        self.alloc(ExprKind::String(s.into()))
+    }
+
+    /// Allocate a new expression from a floating-point number.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `real` is NaN.
+    pub fn real(&'a self, real: f64) -> ArenaExpr<'a> {
+        self.number(Number::real(real))
+    }
+
+    /// Allocate a new `List[...]`(`{...}`) expression from it's elements.
+    pub fn list(&'a self, elements: Vec<ArenaExpr<'a>>) -> ArenaExpr<'a> {
+        let head = self.symbol(crate::symbol!("System`List"));
+        self.normal(head, elements)
+    }
+}
+
+impl<'a> Default for ExprArena<'a> {
+    fn default() -> Self {
+        ExprArena::new()
+    }
+}
+
+impl<'a> ArenaExpr<'a> {
+    /// Get the [`ExprKind`] representing this expression.
+    ///
+    /// The returned reference is valid for as long as the arena that owns this
+    /// expression.
+    pub fn kind(&self) -> &'a ExprKind<ArenaExpr<'a>> {
+        self.kind
+    }
+
+    /// Convert this expression into an [`Expr`].
+    ///
+    /// Subexpressions that are shared within `self` are converted only once, and are
+    /// shared in the returned expression.
+    pub fn to_expr(&self) -> Expr {
+        convert(self, Expr::new)
+    }
+
+    /// Returns `true` if `self` and `other` are the same node in the arena.
+    pub fn ptr_eq(&self, other: &ArenaExpr) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl<'a> ExprPtr for ArenaExpr<'a> {
+    fn kind_ref(&self) -> &ExprKind<ArenaExpr<'a>> {
+        self.kind
+    }
+
+    fn addr(&self) -> *const () {
+        self.kind as *const ExprKind<ArenaExpr<'a>> as *const ()
+    }
+
+    /// Nodes do not track how many times they are referenced, so assume that any node
+    /// may be shared.
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
+
+//=======================================
+// Display & Debug impl/s
+//=======================================
+
+impl<'a> fmt::Debug for ArenaExpr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Formats the same way as the [`Display`][fmt::Display] impl for [`Expr`].
+impl<'a> fmt::Display for ArenaExpr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_iteratively(f, vec![FmtTask::Expr(self)])
+    }
+}
+
+impl<'a> fmt::Display for ExprKind<ArenaExpr<'a>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_kind(self, f)
+    }
+}
+
+impl<'a> fmt::Debug for ExprKind<ArenaExpr<'a>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl<'a> fmt::Display for Normal<ArenaExpr<'a>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_normal(self, f)
+    }
+}
+
+impl<'a> fmt::Debug for ExprArena<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExprArena")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+//======================================
+// Comparision trait impls
+//======================================
+
+impl<'a> PartialEq for ArenaExpr<'a> {
+    fn eq(&self, other: &ArenaExpr<'a>) -> bool {
+        eq_iteratively(self, other)
+    }
+}
+
+impl<'a> Eq for ArenaExpr<'a> {}
+
+impl<'a> Hash for ArenaExpr<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_iteratively(self, state)
+    }
+}
+
+impl<'a> PartialEq<Symbol> for ArenaExpr<'a> {
+    fn eq(&self, other: &Symbol) -> bool {
+        match self.kind() {
+            ExprKind::Symbol(self_sym) => self_sym == other,
+            _ => false,
+        }
+    }
+}
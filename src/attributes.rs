@@ -0,0 +1,611 @@
+//! Symbol attributes, and the evaluation transformations they control.
+//!
+//! In the Wolfram Language, the
+//! [`Attributes`](https://reference.wolfram.com/language/ref/Attributes.html)
+//! <sub>WL</sub> of a symbol change how expressions with that symbol as their head are
+//! evaluated. [`Attributes`] is a set of attributes, and [`AttributeRegistry`] maps
+//! symbols to their attributes. [`AttributeRegistry::new()`] is prepopulated with the
+//! attributes of common `` System` `` symbols.
+//!
+//! The transformations performed by the `Flat`, `Orderless` and `Listable` attributes
+//! are available as [`flatten()`], [`sort_orderless()`] and [`thread_listable()`].
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{
+//!     attributes::{self, AttributeRegistry, Attributes},
+//!     wl, Symbol,
+//! };
+//!
+//! let registry = AttributeRegistry::new();
+//!
+//! let plus = registry.get(&Symbol::new("System`Plus"));
+//! assert!(plus.contains(Attributes::FLAT | Attributes::ORDERLESS));
+//!
+//! // Plus[c, Plus[b, a]]
+//! let expr = wl!(Plus[Global::c, Plus[Global::b, Global::a]]);
+//!
+//! let expr = attributes::sort_orderless(&attributes::flatten(&expr));
+//!
+//! assert_eq!(expr, wl!(Plus[Global::a, Global::b, Global::c]));
+//! ```
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    ops::{BitOr, BitOrAssign},
+    sync::Arc,
+};
+
+use crate::{Expr, ExprKind, Normal, Symbol};
+
+
+/// Set of symbol attributes.
+///
+/// Attributes can be combined using `|`.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Attributes(u16);
+
+/// Registry of the attributes of symbols.
+///
+/// Symbols that have not been assigned any attributes have [`Attributes::empty()`].
+#[derive(Debug, Clone)]
+pub struct AttributeRegistry {
+    attributes: HashMap<Symbol, Attributes>,
+}
+
+impl Attributes {
+    /// [`Constant`](https://reference.wolfram.com/language/ref/Constant.html)
+    /// <sub>WL</sub>: the symbol is a mathematical constant, with a derivative of zero.
+    pub const CONSTANT: Attributes = Attributes(1 << 0);
+    /// [`Flat`](https://reference.wolfram.com/language/ref/Flat.html) <sub>WL</sub>:
+    /// the function is associative, so nested applications are flattened.
+    pub const FLAT: Attributes = Attributes(1 << 1);
+    /// [`HoldAll`](https://reference.wolfram.com/language/ref/HoldAll.html)
+    /// <sub>WL</sub>: none of the elements are evaluated.
+    pub const HOLD_ALL: Attributes = Attributes(1 << 2);
+    /// [`HoldAllComplete`](https://reference.wolfram.com/language/ref/HoldAllComplete.html)
+    /// <sub>WL</sub>: none of the elements are evaluated, and `Sequence[...]` elements
+    /// are not spliced.
+    pub const HOLD_ALL_COMPLETE: Attributes = Attributes(1 << 3);
+    /// [`HoldFirst`](https://reference.wolfram.com/language/ref/HoldFirst.html)
+    /// <sub>WL</sub>: the first element is not evaluated.
+    pub const HOLD_FIRST: Attributes = Attributes(1 << 4);
+    /// [`HoldRest`](https://reference.wolfram.com/language/ref/HoldRest.html)
+    /// <sub>WL</sub>: all but the first element are not evaluated.
+    pub const HOLD_REST: Attributes = Attributes(1 << 5);
+    /// [`Listable`](https://reference.wolfram.com/language/ref/Listable.html)
+    /// <sub>WL</sub>: the function is threaded over lists that appear as elements.
+    pub const LISTABLE: Attributes = Attributes(1 << 6);
+    /// [`NumericFunction`](https://reference.wolfram.com/language/ref/NumericFunction.html)
+    /// <sub>WL</sub>: the function returns a number when its elements are numbers.
+    pub const NUMERIC_FUNCTION: Attributes = Attributes(1 << 7);
+    /// [`OneIdentity`](https://reference.wolfram.com/language/ref/OneIdentity.html)
+    /// <sub>WL</sub>: `f[x]` is equivalent to `x` for the purposes of pattern matching.
+    pub const ONE_IDENTITY: Attributes = Attributes(1 << 8);
+    /// [`Orderless`](https://reference.wolfram.com/language/ref/Orderless.html)
+    /// <sub>WL</sub>: the function is commutative, so its elements are sorted into
+    /// [`canonical_order()`].
+    pub const ORDERLESS: Attributes = Attributes(1 << 9);
+    /// [`Protected`](https://reference.wolfram.com/language/ref/Protected.html)
+    /// <sub>WL</sub>: definitions cannot be made for the symbol.
+    pub const PROTECTED: Attributes = Attributes(1 << 10);
+    /// [`SequenceHold`](https://reference.wolfram.com/language/ref/SequenceHold.html)
+    /// <sub>WL</sub>: `Sequence[...]` elements are not spliced.
+    pub const SEQUENCE_HOLD: Attributes = Attributes(1 << 11);
+
+    /// The empty set of attributes.
+    pub const fn empty() -> Self {
+        Attributes(0)
+    }
+
+    /// Returns `true` if this set contains no attributes.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if this set contains every attribute in `other`.
+    pub const fn contains(self, other: Attributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if this set contains any of the attributes in `other`.
+    pub const fn intersects(self, other: Attributes) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns the set of attributes in either `self` or `other`.
+    pub const fn union(self, other: Attributes) -> Self {
+        Attributes(self.0 | other.0)
+    }
+
+    /// Add the attributes in `other` to this set.
+    pub fn insert(&mut self, other: Attributes) {
+        self.0 |= other.0;
+    }
+
+    /// Remove the attributes in `other` from this set.
+    pub fn remove(&mut self, other: Attributes) {
+        self.0 &= !other.0;
+    }
+
+    /// Returns `true` if these attributes prevent the element at `index` (starting
+    /// from 0) of an expression from being evaluated.
+    pub const fn holds(self, index: usize) -> bool {
+        self.intersects(Attributes::HOLD_ALL.union(Attributes::HOLD_ALL_COMPLETE))
+            || (index == 0 && self.contains(Attributes::HOLD_FIRST))
+            || (index > 0 && self.contains(Attributes::HOLD_REST))
+    }
+
+    /// Get the attribute named by `symbol`, like `` System`Flat ``.
+    pub fn from_symbol(symbol: &Symbol) -> Option<Attributes> {
+        let name = symbol.as_str().strip_prefix("System`")?;
+
+        NAMES
+            .iter()
+            .find(|(_, attribute_name)| *attribute_name == name)
+            .map(|(attribute, _)| *attribute)
+    }
+
+    /// Iterate over the names of the attributes in this set, in alphabetical order.
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        NAMES
+            .iter()
+            .filter(move |(attribute, _)| self.contains(*attribute))
+            .map(|(_, name)| *name)
+    }
+
+    /// Construct a list of the symbols naming the attributes in this set, like
+    /// `{Flat, Orderless}`.
+    pub fn to_expr(self) -> Expr {
+        let symbols = NAMES
+            .iter()
+            .filter(|(attribute, _)| self.contains(*attribute))
+            .map(|(attribute, _)| Expr::symbol(attribute.to_symbol()))
+            .collect();
+
+        Expr::list(symbols)
+    }
+
+    /// The symbol naming this attribute, which must be a single attribute.
+    fn to_symbol(self) -> Symbol {
+        use crate::symbol;
+
+        match self {
+            Attributes::CONSTANT => symbol!("System`Constant"),
+            Attributes::FLAT => symbol!("System`Flat"),
+            Attributes::HOLD_ALL => symbol!("System`HoldAll"),
+            Attributes::HOLD_ALL_COMPLETE => symbol!("System`HoldAllComplete"),
+            Attributes::HOLD_FIRST => symbol!("System`HoldFirst"),
+            Attributes::HOLD_REST => symbol!("System`HoldRest"),
+            Attributes::LISTABLE => symbol!("System`Listable"),
+            Attributes::NUMERIC_FUNCTION => symbol!("System`NumericFunction"),
+            Attributes::ONE_IDENTITY => symbol!("System`OneIdentity"),
+            Attributes::ORDERLESS => symbol!("System`Orderless"),
+            Attributes::PROTECTED => symbol!("System`Protected"),
+            Attributes::SEQUENCE_HOLD => symbol!("System`SequenceHold"),
+            _ => panic!("Attributes::to_symbol: not a single attribute: {:?}", self),
+        }
+    }
+}
+
+/// Names of each attribute, in alphabetical order.
+const NAMES: &[(Attributes, &str)] = &[
+    (Attributes::CONSTANT, "Constant"),
+    (Attributes::FLAT, "Flat"),
+    (Attributes::HOLD_ALL, "HoldAll"),
+    (Attributes::HOLD_ALL_COMPLETE, "HoldAllComplete"),
+    (Attributes::HOLD_FIRST, "HoldFirst"),
+    (Attributes::HOLD_REST, "HoldRest"),
+    (Attributes::LISTABLE, "Listable"),
+    (Attributes::NUMERIC_FUNCTION, "NumericFunction"),
+    (Attributes::ONE_IDENTITY, "OneIdentity"),
+    (Attributes::ORDERLESS, "Orderless"),
+    (Attributes::PROTECTED, "Protected"),
+    (Attributes::SEQUENCE_HOLD, "SequenceHold"),
+];
+
+impl BitOr for Attributes {
+    type Output = Attributes;
+
+    fn bitor(self, other: Attributes) -> Attributes {
+        self.union(other)
+    }
+}
+
+impl BitOrAssign for Attributes {
+    fn bitor_assign(&mut self, other: Attributes) {
+        self.insert(other)
+    }
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+//======================================
+// Registry
+//======================================
+
+impl AttributeRegistry {
+    /// Construct a registry containing the attributes of common `` System` `` symbols,
+    /// like `Plus`, `Hold` and `Set`.
+    pub fn new() -> Self {
+        let attributes = builtins()
+            .into_iter()
+            .map(|(symbol, attributes)| (symbol, attributes.union(Attributes::PROTECTED)))
+            .collect();
+
+        AttributeRegistry { attributes }
+    }
+
+    /// Construct a registry in which no symbol has any attributes.
+    pub fn empty() -> Self {
+        AttributeRegistry {
+            attributes: HashMap::new(),
+        }
+    }
+
+    /// Get the attributes of `symbol`.
+    pub fn get(&self, symbol: &Symbol) -> Attributes {
+        self.attributes.get(symbol).copied().unwrap_or_default()
+    }
+
+    /// Replace the attributes of `symbol`, like `Attributes[symbol] = {...}`.
+    pub fn set(&mut self, symbol: Symbol, attributes: Attributes) {
+        if attributes.is_empty() {
+            self.attributes.remove(&symbol);
+        } else {
+            self.attributes.insert(symbol, attributes);
+        }
+    }
+
+    /// Add `attributes` to the attributes of `symbol`, like
+    /// `SetAttributes[symbol, {...}]`.
+    pub fn insert(&mut self, symbol: Symbol, attributes: Attributes) {
+        if !attributes.is_empty() {
+            *self.attributes.entry(symbol).or_default() |= attributes;
+        }
+    }
+
+    /// Remove all attributes of `symbol`, like
+    /// `ClearAttributes[symbol, Attributes[symbol]]`.
+    pub fn clear(&mut self, symbol: &Symbol) {
+        self.attributes.remove(symbol);
+    }
+}
+
+impl Default for AttributeRegistry {
+    fn default() -> Self {
+        AttributeRegistry::new()
+    }
+}
+
+const HOLD_ALL: Attributes = Attributes::HOLD_ALL;
+const HOLD_FIRST: Attributes = Attributes::HOLD_FIRST;
+const HOLD_REST: Attributes = Attributes::HOLD_REST;
+const HOLD_ALL_COMPLETE: Attributes = Attributes::HOLD_ALL_COMPLETE;
+const LISTABLE_NUMERIC: Attributes =
+    Attributes::LISTABLE.union(Attributes::NUMERIC_FUNCTION);
+const ASSOCIATIVE: Attributes = Attributes::FLAT.union(Attributes::ONE_IDENTITY);
+const COMMUTATIVE: Attributes = ASSOCIATIVE.union(Attributes::ORDERLESS);
+
+/// Attributes of `` System` `` symbols, in addition to `Protected`.
+#[rustfmt::skip]
+fn builtins() -> Vec<(Symbol, Attributes)> {
+    use crate::symbol;
+
+    vec![
+        // Arithmetic
+        (symbol!("System`Plus"), COMMUTATIVE.union(LISTABLE_NUMERIC)),
+        (symbol!("System`Times"), COMMUTATIVE.union(LISTABLE_NUMERIC)),
+        (symbol!("System`Power"), LISTABLE_NUMERIC.union(Attributes::ONE_IDENTITY)),
+        (symbol!("System`Subtract"), LISTABLE_NUMERIC),
+        (symbol!("System`Divide"), LISTABLE_NUMERIC),
+        (symbol!("System`Minus"), LISTABLE_NUMERIC),
+        (symbol!("System`Sqrt"), LISTABLE_NUMERIC),
+        (symbol!("System`Abs"), LISTABLE_NUMERIC),
+        (symbol!("System`Mod"), LISTABLE_NUMERIC),
+        (symbol!("System`Quotient"), LISTABLE_NUMERIC),
+        (symbol!("System`Max"), COMMUTATIVE.union(Attributes::NUMERIC_FUNCTION)),
+        (symbol!("System`Min"), COMMUTATIVE.union(Attributes::NUMERIC_FUNCTION)),
+        (symbol!("System`GCD"), COMMUTATIVE.union(Attributes::LISTABLE)),
+        (symbol!("System`LCM"), COMMUTATIVE.union(Attributes::LISTABLE)),
+        (symbol!("System`Dot"), ASSOCIATIVE),
+
+        // Elementary functions
+        (symbol!("System`Exp"), LISTABLE_NUMERIC),
+        (symbol!("System`Log"), LISTABLE_NUMERIC),
+        (symbol!("System`Sin"), LISTABLE_NUMERIC),
+        (symbol!("System`Cos"), LISTABLE_NUMERIC),
+        (symbol!("System`Tan"), LISTABLE_NUMERIC),
+        (symbol!("System`Cot"), LISTABLE_NUMERIC),
+        (symbol!("System`Sec"), LISTABLE_NUMERIC),
+        (symbol!("System`Csc"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcSin"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcCos"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcTan"), LISTABLE_NUMERIC),
+        (symbol!("System`Sinh"), LISTABLE_NUMERIC),
+        (symbol!("System`Cosh"), LISTABLE_NUMERIC),
+        (symbol!("System`Tanh"), LISTABLE_NUMERIC),
+        (symbol!("System`Coth"), LISTABLE_NUMERIC),
+        (symbol!("System`Sech"), LISTABLE_NUMERIC),
+        (symbol!("System`Csch"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcSinh"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcCosh"), LISTABLE_NUMERIC),
+        (symbol!("System`ArcTanh"), LISTABLE_NUMERIC),
+
+        // Constants
+        (symbol!("System`Pi"), Attributes::CONSTANT),
+        (symbol!("System`E"), Attributes::CONSTANT),
+        (symbol!("System`Degree"), Attributes::CONSTANT),
+        (symbol!("System`EulerGamma"), Attributes::CONSTANT),
+        (symbol!("System`GoldenRatio"), Attributes::CONSTANT),
+        (symbol!("System`Catalan"), Attributes::CONSTANT),
+
+        // Logic
+        (symbol!("System`And"), HOLD_ALL.union(ASSOCIATIVE)),
+        (symbol!("System`Or"), HOLD_ALL.union(ASSOCIATIVE)),
+        (symbol!("System`Not"), Attributes::empty()),
+
+        // Strings and lists
+        (symbol!("System`StringJoin"), ASSOCIATIVE),
+        (symbol!("System`Join"), ASSOCIATIVE),
+        (symbol!("System`List"), Attributes::empty()),
+
+        // Assignment
+        (symbol!("System`Set"), HOLD_FIRST.union(Attributes::SEQUENCE_HOLD)),
+        (symbol!("System`SetDelayed"), HOLD_ALL.union(Attributes::SEQUENCE_HOLD)),
+        (symbol!("System`Unset"), HOLD_FIRST),
+        (symbol!("System`Clear"), HOLD_ALL),
+
+        // Rules and patterns
+        (symbol!("System`Rule"), Attributes::SEQUENCE_HOLD),
+        (symbol!("System`RuleDelayed"), HOLD_REST.union(Attributes::SEQUENCE_HOLD)),
+        (symbol!("System`Pattern"), HOLD_FIRST),
+        (symbol!("System`HoldPattern"), HOLD_ALL),
+        (symbol!("System`Condition"), HOLD_ALL),
+        (symbol!("System`PatternTest"), HOLD_REST),
+
+        // Control flow
+        (symbol!("System`CompoundExpression"), HOLD_ALL),
+        (symbol!("System`If"), HOLD_REST),
+        (symbol!("System`Which"), HOLD_ALL),
+        (symbol!("System`Switch"), HOLD_REST),
+        (symbol!("System`While"), HOLD_ALL),
+        (symbol!("System`Do"), HOLD_ALL),
+        (symbol!("System`For"), HOLD_ALL),
+
+        // Scoping and iteration
+        (symbol!("System`Function"), HOLD_ALL),
+        (symbol!("System`Module"), HOLD_ALL),
+        (symbol!("System`With"), HOLD_ALL),
+        (symbol!("System`Block"), HOLD_ALL),
+        (symbol!("System`Table"), HOLD_ALL),
+        (symbol!("System`Sum"), HOLD_ALL),
+        (symbol!("System`Product"), HOLD_ALL),
+
+        // Holding
+        (symbol!("System`Hold"), HOLD_ALL),
+        (symbol!("System`HoldForm"), HOLD_ALL),
+        (symbol!("System`HoldComplete"), HOLD_ALL_COMPLETE),
+        (symbol!("System`Unevaluated"), HOLD_ALL_COMPLETE),
+        (symbol!("System`Defer"), HOLD_ALL),
+    ]
+}
+
+//======================================
+// Transformations
+//======================================
+
+/// Flatten nested applications of the head of `expr`, as for a head with the `Flat`
+/// attribute.
+///
+/// `f[a, f[b, f[c]], d]` becomes `f[a, b, c, d]`. Only elements with the same head as
+/// `expr` are flattened.
+pub fn flatten(expr: &Expr) -> Expr {
+    let normal = match expr.try_as_normal() {
+        Some(normal) => normal,
+        None => return expr.clone(),
+    };
+
+    let has_same_head = |element: &Expr| match element.try_as_normal() {
+        Some(inner) => inner.head() == normal.head(),
+        None => false,
+    };
+
+    if !normal.elements().iter().any(has_same_head) {
+        return expr.clone();
+    }
+
+    let mut elements = Vec::with_capacity(normal.elements().len());
+    // Iterators over the elements of each enclosing application of the head.
+    let mut stack = vec![normal.elements().iter()];
+
+    while let Some(iter) = stack.last_mut() {
+        match iter.next() {
+            Some(element) if has_same_head(element) => {
+                let inner = element.try_as_normal().unwrap();
+                stack.push(inner.elements().iter());
+            },
+            Some(element) => elements.push(element.clone()),
+            None => {
+                stack.pop();
+            },
+        }
+    }
+
+    Expr::normal(normal.head().clone(), elements)
+}
+
+/// Sort the elements of `expr` into [`canonical_order()`], as for a head with the
+/// `Orderless` attribute.
+pub fn sort_orderless(expr: &Expr) -> Expr {
+    let normal = match expr.try_as_normal() {
+        Some(normal) => normal,
+        None => return expr.clone(),
+    };
+
+    let is_sorted = normal
+        .elements()
+        .windows(2)
+        .all(|pair| canonical_order(&pair[0], &pair[1]) != Ordering::Greater);
+
+    if is_sorted {
+        return expr.clone();
+    }
+
+    let mut elements = normal.elements().to_vec();
+    elements.sort_by(canonical_order);
+
+    Expr::normal(normal.head().clone(), elements)
+}
+
+/// Thread the head of `expr` over the lists that appear as its elements, as for a
+/// head with the `Listable` attribute.
+///
+/// `f[{a, b}, {c, d}, x]` becomes `{f[a, c, x], f[b, d, x]}`. Returns `None` if no
+/// element of `expr` is a list, or if the lists have different lengths.
+pub fn thread_listable(expr: &Expr) -> Option<Expr> {
+    let normal = expr.try_as_normal()?;
+
+    fn as_list(element: &Expr) -> Option<&Normal> {
+        element
+            .try_as_normal()
+            .filter(|list| list.has_head(&crate::symbol!("System`List")))
+    }
+
+    let mut len = None;
+
+    for list in normal.elements().iter().filter_map(as_list) {
+        match len {
+            None => len = Some(list.elements().len()),
+            Some(len) if len != list.elements().len() => return None,
+            Some(_) => (),
+        }
+    }
+
+    let threaded = (0..len?)
+        .map(|index| {
+            let elements = normal
+                .elements()
+                .iter()
+                .map(|element| match as_list(element) {
+                    Some(list) => list.elements()[index].clone(),
+                    None => element.clone(),
+                })
+                .collect();
+
+            Expr::normal(normal.head().clone(), elements)
+        })
+        .collect();
+
+    Some(Expr::list(threaded))
+}
+
+//======================================
+// Canonical order
+//======================================
+
+/// Compare two expressions using the canonical order used to sort the elements of
+/// `Orderless` functions.
+///
+/// This approximates the order used by
+/// [`Sort`](https://reference.wolfram.com/language/ref/Sort.html) <sub>WL</sub>:
+///
+/// 1. Numbers come first, in order of their value. An integer comes before a real
+///    with the same value.
+/// 2. Strings come next, in alphabetical order. Letters are compared ignoring case
+///    first, and lowercase letters come before uppercase letters.
+/// 3. Symbols come next, in alphabetical order of their names, and then of their
+///    contexts.
+/// 4. Normal expressions come last. Expressions with fewer elements come first, then
+///    expressions are compared by head, and then by each element in turn.
+///
+/// The comparison does not recurse, so arbitrarily deep expressions can be compared
+/// without overflowing the stack.
+pub fn canonical_order(a: &Expr, b: &Expr) -> Ordering {
+    fn rank(expr: &Expr) -> u8 {
+        match expr.kind() {
+            ExprKind::Integer(_) | ExprKind::Real(_) => 0,
+            ExprKind::String(_) => 1,
+            ExprKind::Symbol(_) => 2,
+            ExprKind::Normal(_) => 3,
+        }
+    }
+
+    // Pairs of subexpressions that remain to be compared, in reverse order.
+    let mut stack: Vec<(&Expr, &Expr)> = vec![(a, b)];
+
+    while let Some((a, b)) = stack.pop() {
+        if Arc::ptr_eq(&a.inner, &b.inner) {
+            continue;
+        }
+
+        let ordering = match (a.kind(), b.kind()) {
+            (ExprKind::Integer(a), ExprKind::Integer(b)) => a.cmp(b),
+            (ExprKind::Real(a), ExprKind::Real(b)) => a.cmp(b),
+            (ExprKind::Integer(a), ExprKind::Real(b)) => {
+                compare_numbers(*a as f64, b.into_inner()).then(Ordering::Less)
+            },
+            (ExprKind::Real(a), ExprKind::Integer(b)) => {
+                compare_numbers(a.into_inner(), *b as f64).then(Ordering::Greater)
+            },
+            (ExprKind::String(a), ExprKind::String(b)) => compare_strings(a, b),
+            (ExprKind::Symbol(a), ExprKind::Symbol(b)) => {
+                compare_strings(a.symbol_name().as_str(), b.symbol_name().as_str())
+                    .then_with(|| a.context().as_str().cmp(b.context().as_str()))
+            },
+            (ExprKind::Normal(a), ExprKind::Normal(b)) => {
+                let ordering = a.elements().len().cmp(&b.elements().len());
+
+                if ordering == Ordering::Equal {
+                    // Compare the heads, and then each element in turn.
+                    for pair in a.elements().iter().zip(b.elements()).rev() {
+                        stack.push(pair);
+                    }
+                    stack.push((a.head(), b.head()));
+                }
+
+                ordering
+            },
+            _ => rank(a).cmp(&rank(b)),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+fn compare_numbers(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+/// Compare strings ignoring case, and then with lowercase letters before uppercase.
+fn compare_strings(a: &str, b: &str) -> Ordering {
+    let folded = |string: &str| {
+        string
+            .chars()
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<char>>()
+    };
+
+    let case = |string: &str| {
+        string
+            .chars()
+            .map(char::is_uppercase)
+            .collect::<Vec<bool>>()
+    };
+
+    folded(a)
+        .cmp(&folded(b))
+        .then_with(|| case(a).cmp(&case(b)))
+        .then_with(|| a.cmp(b))
+}
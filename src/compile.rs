@@ -0,0 +1,654 @@
+//! Compilation of numeric expressions into register programs.
+//!
+//! [`compile()`] and [`compile_function()`] translate a numeric expression, like
+//! `x^2 + Sin[x y]`, into a [`CompiledFunction`]: a flat list of [`Instruction`]s that
+//! can be evaluated repeatedly with different argument values, without traversing the
+//! expression again.
+//!
+//! Each instruction writes its result to its own register, identified by the index of
+//! the instruction. Instructions are interned using structural hashing, so a repeated
+//! subexpression, like `x y` in `Sin[x y] + Cos[x y]`, is computed only once. Operations
+//! whose operands are all constants are evaluated when the program is compiled.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{compile::compile_function, wl};
+//!
+//! // Function[{x, y}, Sin[x y] + Cos[x y]]
+//! let function = wl!(Function[
+//!     {Global::x, Global::y},
+//!     Plus[Sin[Times[Global::x, Global::y]], Cos[Times[Global::x, Global::y]]]
+//! ]);
+//!
+//! let compiled = compile_function(&function)?;
+//!
+//! assert_eq!(compiled.eval(&[0.0, 2.0]), 1.0);
+//!
+//! // x, y, x y, Sin[x y], Cos[x y] and their sum.
+//! assert_eq!(compiled.instructions().len(), 6);
+//! # Ok::<(), wolfram_expr::compile::CompileError>(())
+//! ```
+
+use std::{collections::HashMap, convert::TryFrom, f64::consts, fmt, sync::Arc};
+
+use crate::{
+    function::{parameter_list, replace_slots, slot_function_body, SlotValue},
+    Expr, ExprKind, ExprNode, Normal, Symbol, F64,
+};
+
+
+/// Program compiled from a numeric expression.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    parameters: Vec<Symbol>,
+    instructions: Vec<Instruction>,
+    result: usize,
+}
+
+/// Single instruction of a [`CompiledFunction`].
+///
+/// Operands are the registers written by earlier instructions, identified by the index
+/// of the instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Load a constant.
+    Constant(F64),
+    /// Load the argument at the given index.
+    Parameter(usize),
+    /// Apply a function to one operand.
+    Unary(UnaryOp, usize),
+    /// Apply a function to two operands.
+    Binary(BinaryOp, usize, usize),
+    /// Raise an operand to an integer power.
+    Powi(usize, i32),
+}
+
+/// Function of one operand.
+#[allow(missing_docs)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Abs,
+    Sqrt,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Cot,
+    Sec,
+    Csc,
+    ArcSin,
+    ArcCos,
+    ArcTan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Coth,
+    Sech,
+    Csch,
+    ArcSinh,
+    ArcCosh,
+    ArcTanh,
+}
+
+/// Function of two operands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Sub,
+    /// `a * b`
+    Mul,
+    /// `a / b`
+    Div,
+    /// `a^b`
+    Pow,
+    /// `ArcTan[a, b]`, the angle of the point `(a, b)`.
+    ArcTan,
+}
+
+/// Error returned when an expression cannot be compiled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// A symbol that is not a parameter or a known constant appeared in the
+    /// expression.
+    UnknownSymbol(Symbol),
+    /// An expression with an unsupported head or number of elements, or a string,
+    /// appeared in the expression.
+    Unsupported(Expr),
+    /// The expression passed to [`compile_function()`] is not a `Function` with a list
+    /// of parameter symbols, or uses slots that cannot be compiled.
+    InvalidFunction(Expr),
+}
+
+/// Compile `expr` into a function of `parameters`.
+///
+/// `expr` may contain numbers, the parameters, the constants `Pi`, `E`, `Degree`,
+/// `EulerGamma`, `GoldenRatio` and `Catalan`, and the real-valued functions supported by
+/// [`numeric_eval()`][crate::algebra::numeric_eval]. Complex numbers are not
+/// supported.
+pub fn compile(
+    expr: &Expr,
+    parameters: &[Symbol],
+) -> Result<CompiledFunction, CompileError> {
+    let mut compiler = Compiler {
+        parameters,
+        instructions: Vec::new(),
+        interned: HashMap::new(),
+        constants: HashMap::new(),
+        compiled: HashMap::new(),
+    };
+
+    let result = compiler.compile(expr)?;
+
+    Ok(CompiledFunction {
+        parameters: parameters.to_vec(),
+        instructions: compiler.instructions,
+        result,
+    })
+}
+
+/// Compile a `Function` expression.
+///
+/// The parameters of the function may be named, like `Function[{x, y}, body]` or
+/// `Function[x, body]`, or be slots, like `Function[#1 + #2]` or
+/// `Function[Null, #1 + #2, attributes]`. The number of parameters of a function that
+/// uses slots is the largest slot number used. Slots inside a nested `Function[body]`
+/// belong to that function, as in [`apply_function()`][crate::function::apply_function].
+///
+/// Functions that use `#0`, `##` or `#name` slots cannot be compiled, and return
+/// [`CompileError::InvalidFunction`].
+pub fn compile_function(function: &Expr) -> Result<CompiledFunction, CompileError> {
+    let invalid = || CompileError::InvalidFunction(function.clone());
+
+    let normal = match function.try_as_normal() {
+        Some(normal) if normal.has_head(&crate::symbol!("System`Function")) => normal,
+        _ => return Err(invalid()),
+    };
+
+    if let Some(body) = slot_function_body(normal) {
+        let mut max_slot = 0;
+
+        // Replace each `Slot[n]` with the symbol for the `n`-th parameter.
+        let body = replace_slots(body, |slot, _| {
+            let n = slot_number(slot).ok_or_else(invalid)?;
+            max_slot = max_slot.max(n);
+
+            Ok(SlotValue::Single(Expr::symbol(slot_symbol(n))))
+        })?;
+        let parameters: Vec<Symbol> = (1..=max_slot).map(slot_symbol).collect();
+
+        return compile(&body, &parameters);
+    }
+
+    match normal.elements() {
+        [parameters, body] | [parameters, body, _] => {
+            let parameters = parameter_list(parameters).ok_or_else(invalid)?;
+            compile(body, &parameters)
+        },
+        _ => Err(invalid()),
+    }
+}
+
+impl CompiledFunction {
+    /// Evaluate the compiled function with the given argument values.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of arguments is not equal to the number
+    /// of parameters.
+    pub fn eval(&self, args: &[f64]) -> f64 {
+        assert_eq!(
+            args.len(),
+            self.parameters.len(),
+            "CompiledFunction::eval: wrong number of arguments"
+        );
+
+        let mut registers = Vec::with_capacity(self.instructions.len());
+
+        for instruction in &self.instructions {
+            let value = match *instruction {
+                Instruction::Constant(value) => *value,
+                Instruction::Parameter(index) => args[index],
+                Instruction::Unary(op, a) => op.apply(registers[a]),
+                Instruction::Binary(op, a, b) => op.apply(registers[a], registers[b]),
+                Instruction::Powi(a, n) => registers[a].powi(n),
+            };
+
+            registers.push(value);
+        }
+
+        registers[self.result]
+    }
+
+    /// Convert this compiled function into a closure.
+    pub fn into_fn(self) -> impl Fn(&[f64]) -> f64 {
+        move |args| self.eval(args)
+    }
+
+    /// Parameters of the function, in the order their values are passed to
+    /// [`eval()`][CompiledFunction::eval].
+    ///
+    /// The parameters of a function that uses slots are `` System`Slot1 ``,
+    /// `` System`Slot2 ``, etc.
+    pub fn parameters(&self) -> &[Symbol] {
+        &self.parameters
+    }
+
+    /// Instructions of the program, in the order they are executed.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Register containing the result of the program.
+    pub fn result(&self) -> usize {
+        self.result
+    }
+}
+
+impl UnaryOp {
+    /// Apply this function to `a`.
+    pub fn apply(self, a: f64) -> f64 {
+        match self {
+            UnaryOp::Neg => -a,
+            UnaryOp::Abs => a.abs(),
+            UnaryOp::Sqrt => a.sqrt(),
+            UnaryOp::Exp => a.exp(),
+            UnaryOp::Log => a.ln(),
+            UnaryOp::Sin => a.sin(),
+            UnaryOp::Cos => a.cos(),
+            UnaryOp::Tan => a.tan(),
+            UnaryOp::Cot => a.tan().recip(),
+            UnaryOp::Sec => a.cos().recip(),
+            UnaryOp::Csc => a.sin().recip(),
+            UnaryOp::ArcSin => a.asin(),
+            UnaryOp::ArcCos => a.acos(),
+            UnaryOp::ArcTan => a.atan(),
+            UnaryOp::Sinh => a.sinh(),
+            UnaryOp::Cosh => a.cosh(),
+            UnaryOp::Tanh => a.tanh(),
+            UnaryOp::Coth => a.tanh().recip(),
+            UnaryOp::Sech => a.cosh().recip(),
+            UnaryOp::Csch => a.sinh().recip(),
+            UnaryOp::ArcSinh => a.asinh(),
+            UnaryOp::ArcCosh => a.acosh(),
+            UnaryOp::ArcTanh => a.atanh(),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<UnaryOp> {
+        let op = match name {
+            "System`Minus" => UnaryOp::Neg,
+            "System`Abs" => UnaryOp::Abs,
+            "System`Sqrt" => UnaryOp::Sqrt,
+            "System`Exp" => UnaryOp::Exp,
+            "System`Log" => UnaryOp::Log,
+            "System`Sin" => UnaryOp::Sin,
+            "System`Cos" => UnaryOp::Cos,
+            "System`Tan" => UnaryOp::Tan,
+            "System`Cot" => UnaryOp::Cot,
+            "System`Sec" => UnaryOp::Sec,
+            "System`Csc" => UnaryOp::Csc,
+            "System`ArcSin" => UnaryOp::ArcSin,
+            "System`ArcCos" => UnaryOp::ArcCos,
+            "System`ArcTan" => UnaryOp::ArcTan,
+            "System`Sinh" => UnaryOp::Sinh,
+            "System`Cosh" => UnaryOp::Cosh,
+            "System`Tanh" => UnaryOp::Tanh,
+            "System`Coth" => UnaryOp::Coth,
+            "System`Sech" => UnaryOp::Sech,
+            "System`Csch" => UnaryOp::Csch,
+            "System`ArcSinh" => UnaryOp::ArcSinh,
+            "System`ArcCosh" => UnaryOp::ArcCosh,
+            "System`ArcTanh" => UnaryOp::ArcTanh,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+}
+
+impl BinaryOp {
+    /// Apply this function to `a` and `b`.
+    pub fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Pow => a.powf(b),
+            BinaryOp::ArcTan => b.atan2(a),
+        }
+    }
+
+    fn is_commutative(self) -> bool {
+        matches!(self, BinaryOp::Add | BinaryOp::Mul)
+    }
+}
+
+//======================================
+// Compiler
+//======================================
+
+struct Compiler<'p> {
+    parameters: &'p [Symbol],
+    instructions: Vec<Instruction>,
+    /// Register written by each distinct instruction, other than constants.
+    interned: HashMap<Instruction, usize>,
+    /// Register containing each distinct constant, keyed by its bits.
+    ///
+    /// `F64` considers `0.0` and `-0.0` to be equal, so constants can't be interned
+    /// by value.
+    constants: HashMap<u64, usize>,
+    /// Register containing the value of each compiled subexpression, keyed by its
+    /// allocation, so that shared subexpressions are only traversed once.
+    ///
+    /// Structurally equal subexpressions are merged by `interned` instead, because
+    /// `Expr` equality also considers `0.0` and `-0.0` to be equal.
+    compiled: HashMap<*const ExprNode, usize>,
+}
+
+/// Operation computed by a normal expression from the registers of its operands.
+#[derive(Copy, Clone)]
+enum Operation {
+    /// Apply a binary operation to each operand in turn, like `a + b + c`.
+    Fold(BinaryOp),
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+    /// `Log[base, a]`, with the operands `base` and `a`.
+    LogBase,
+    Powi(i32),
+}
+
+impl<'p> Compiler<'p> {
+    /// Compile `expr`, and return the register containing its value.
+    ///
+    /// Subexpressions are compiled in post-order, without recursion, so this can be
+    /// used on arbitrarily deep expressions without overflowing the stack.
+    fn compile(&mut self, expr: &Expr) -> Result<usize, CompileError> {
+        // Registers of completed subexpressions that have not been consumed by their
+        // parent.
+        let mut registers: Vec<usize> = Vec::new();
+
+        // Expressions to compile, and for expressions whose operands have already been
+        // pushed, the operation to apply to them and the number of operands.
+        let mut stack: Vec<(&Expr, Option<(Operation, usize)>)> = vec![(expr, None)];
+
+        while let Some((expr, operation)) = stack.pop() {
+            let register = match operation {
+                Some((operation, count)) => {
+                    let operands = registers.split_off(registers.len() - count);
+                    self.apply(operation, &operands)
+                },
+                None => {
+                    if let Some(register) = self.compiled.get(&Arc::as_ptr(&expr.inner)) {
+                        registers.push(*register);
+                        continue;
+                    }
+
+                    match expr.kind() {
+                        ExprKind::Integer(int) => self.constant(*int as f64),
+                        ExprKind::Real(real) => self.constant(**real),
+                        ExprKind::Symbol(symbol) => self.symbol(symbol)?,
+                        ExprKind::String(_) => {
+                            return Err(CompileError::Unsupported(expr.clone()))
+                        },
+                        ExprKind::Normal(normal) => {
+                            let (operation, operands) = operation_of(expr, normal)?;
+
+                            stack.push((expr, Some((operation, operands.len()))));
+                            for operand in operands.iter().rev() {
+                                stack.push((operand, None));
+                            }
+
+                            continue;
+                        },
+                    }
+                },
+            };
+
+            self.compiled.insert(Arc::as_ptr(&expr.inner), register);
+            registers.push(register);
+        }
+
+        debug_assert!(registers.len() == 1);
+
+        Ok(registers
+            .pop()
+            .expect("Compiler::compile: no register computed for root expression"))
+    }
+
+    /// Emit the instructions that apply `operation` to the registers `operands`.
+    fn apply(&mut self, operation: Operation, operands: &[usize]) -> usize {
+        match (operation, operands) {
+            (Operation::Fold(op), [first, rest @ ..]) => {
+                rest.iter().fold(*first, |result, register| {
+                    self.emit(Instruction::Binary(op, result, *register))
+                })
+            },
+            (Operation::Binary(op), [a, b]) => self.emit(Instruction::Binary(op, *a, *b)),
+            (Operation::Unary(op), [a]) => self.emit(Instruction::Unary(op, *a)),
+            (Operation::LogBase, [base, a]) => {
+                let a = self.emit(Instruction::Unary(UnaryOp::Log, *a));
+                let base = self.emit(Instruction::Unary(UnaryOp::Log, *base));
+                self.emit(Instruction::Binary(BinaryOp::Div, a, base))
+            },
+            (Operation::Powi(n), [a]) => self.emit(Instruction::Powi(*a, n)),
+            _ => panic!("Compiler::apply: wrong number of operands"),
+        }
+    }
+
+    fn symbol(&mut self, symbol: &Symbol) -> Result<usize, CompileError> {
+        if let Some(index) = self.parameters.iter().position(|param| param == symbol) {
+            return Ok(self.emit(Instruction::Parameter(index)));
+        }
+
+        let value = match symbol.as_str() {
+            "System`Pi" => consts::PI,
+            "System`E" => consts::E,
+            "System`Degree" => consts::PI / 180.0,
+            "System`EulerGamma" => 0.577_215_664_901_532_9,
+            "System`GoldenRatio" => 1.618_033_988_749_895,
+            "System`Catalan" => 0.915_965_594_177_219,
+            _ => return Err(CompileError::UnknownSymbol(symbol.clone())),
+        };
+
+        Ok(self.constant(value))
+    }
+
+    fn constant(&mut self, value: f64) -> usize {
+        let value = F64::new(value).expect("compiled constant is NaN");
+        self.emit(Instruction::Constant(value))
+    }
+
+    /// Append `instruction` to the program, unless an identical instruction has
+    /// already been emitted, and return the register containing its result.
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let instruction = match self.fold_constants(instruction) {
+            Some(value) => return self.emit(Instruction::Constant(value)),
+            None => normalize(instruction),
+        };
+
+        if let Instruction::Constant(value) = instruction {
+            let bits = value.to_bits();
+
+            if let Some(register) = self.constants.get(&bits) {
+                return *register;
+            }
+
+            let register = self.push(instruction);
+            self.constants.insert(bits, register);
+
+            return register;
+        }
+
+        if let Some(register) = self.interned.get(&instruction) {
+            return *register;
+        }
+
+        let register = self.push(instruction);
+        self.interned.insert(instruction, register);
+
+        register
+    }
+
+    fn push(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    /// If every operand of `instruction` is a constant, compute its value.
+    ///
+    /// Instructions that produce NaN are not folded, and are evaluated at run time
+    /// instead.
+    fn fold_constants(&self, instruction: Instruction) -> Option<F64> {
+        let constant = |register: usize| match self.instructions[register] {
+            Instruction::Constant(value) => Some(*value),
+            _ => None,
+        };
+
+        let value = match instruction {
+            Instruction::Constant(_) | Instruction::Parameter(_) => return None,
+            Instruction::Unary(op, a) => op.apply(constant(a)?),
+            Instruction::Binary(op, a, b) => op.apply(constant(a)?, constant(b)?),
+            Instruction::Powi(a, n) => constant(a)?.powi(n),
+        };
+
+        F64::new(value).ok()
+    }
+}
+
+/// Returns the operation computed by `expr`, and its operands, in the order they are
+/// compiled.
+fn operation_of<'e>(
+    expr: &Expr,
+    normal: &'e Normal,
+) -> Result<(Operation, Vec<&'e Expr>), CompileError> {
+    let unsupported = || CompileError::Unsupported(expr.clone());
+
+    let name = match normal.head().try_as_symbol() {
+        Some(head) => head.as_str(),
+        None => return Err(unsupported()),
+    };
+
+    let elements = normal.elements();
+
+    let (operation, operands) = match (name, elements) {
+        ("System`Plus", [_, ..]) => (Operation::Fold(BinaryOp::Add), elements),
+        ("System`Times", [_, ..]) => (Operation::Fold(BinaryOp::Mul), elements),
+        ("System`Subtract", [_, _]) => (Operation::Binary(BinaryOp::Sub), elements),
+        ("System`Divide" | "System`Rational", [_, _]) => {
+            (Operation::Binary(BinaryOp::Div), elements)
+        },
+        ("System`ArcTan", [_, _]) => (Operation::Binary(BinaryOp::ArcTan), elements),
+        ("System`Log", [_, _]) => (Operation::LogBase, elements),
+        ("System`Power", [base, exponent]) => {
+            let integer = match exponent.kind() {
+                ExprKind::Integer(int) => i32::try_from(*int).ok(),
+                _ => None,
+            };
+
+            match integer {
+                Some(n) => (Operation::Powi(n), &elements[..1]),
+                None if exponent.try_as_numeric()
+                    == Some(crate::Numeric::rational(1, 2)) =>
+                {
+                    (Operation::Unary(UnaryOp::Sqrt), std::slice::from_ref(base))
+                },
+                None => (Operation::Binary(BinaryOp::Pow), elements),
+            }
+        },
+        (name, [_]) => match UnaryOp::from_name(name) {
+            Some(op) => (Operation::Unary(op), elements),
+            None => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+
+    Ok((operation, operands.iter().collect()))
+}
+
+/// Order the operands of commutative operations, so that `x y` and `y x` are
+/// interned as the same instruction.
+fn normalize(instruction: Instruction) -> Instruction {
+    match instruction {
+        Instruction::Binary(op, a, b) if op.is_commutative() && b < a => {
+            Instruction::Binary(op, b, a)
+        },
+        _ => instruction,
+    }
+}
+
+//======================================
+// Slots
+//======================================
+
+/// Symbol used as the parameter for `Slot[n]`.
+fn slot_symbol(n: usize) -> Symbol {
+    Symbol::new(&format!("System`Slot{}", n))
+}
+
+/// If `normal` is `Slot[n]`, with `n >= 1`, return `n`.
+fn slot_number(normal: &Normal) -> Option<usize> {
+    if !normal.has_head(&crate::symbol!("System`Slot")) {
+        return None;
+    }
+
+    match normal.elements() {
+        [n] => match n.kind() {
+            ExprKind::Integer(n) if *n >= 1 => usize::try_from(*n).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//======================================
+// Formatting
+//======================================
+
+/// Formats the program as a listing, with one instruction per line.
+impl fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (register, instruction) in self.instructions.iter().enumerate() {
+            write!(f, "r{} = ", register)?;
+
+            match *instruction {
+                Instruction::Constant(value) => writeln!(f, "{:?}", *value)?,
+                Instruction::Parameter(index) => {
+                    writeln!(f, "{}", self.parameters[index])?
+                },
+                Instruction::Unary(op, a) => writeln!(f, "{:?} r{}", op, a)?,
+                Instruction::Binary(op, a, b) => writeln!(f, "{:?} r{} r{}", op, a, b)?,
+                Instruction::Powi(a, n) => writeln!(f, "Powi r{} {}", a, n)?,
+            }
+        }
+
+        write!(f, "return r{}", self.result)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::UnknownSymbol(symbol) => {
+                write!(
+                    f,
+                    "symbol {} is not a parameter or a known constant",
+                    symbol
+                )
+            },
+            CompileError::Unsupported(expr) => write!(f, "cannot compile {}", expr),
+            CompileError::InvalidFunction(expr) => {
+                write!(f, "cannot compile function: {}", expr)
+            },
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -4,7 +4,153 @@ use super::*;
 impl Expr {
     /// If this is a [`Normal`] expression, return that. Otherwise return None.
     pub fn try_as_normal(&self) -> Option<&Normal> {
+        self.kind().try_as_normal()
+    }
+
+    /// If this is a [`True`](http://reference.wolfram.com/language/ref/True.html)
+    /// or [`False`](http://reference.wolfram.com/language/ref/False.html) symbol,
+    /// return that. Otherwise return None.
+    pub fn try_as_bool(&self) -> Option<bool> {
+        self.kind().try_as_bool()
+    }
+
+    /// If this is a [`ExprKind::String`] expression, return that. Otherwise return None.
+    pub fn try_as_str(&self) -> Option<&str> {
+        self.kind().try_as_str()
+    }
+
+    /// If this is a [`Symbol`] expression, return that. Otherwise return None.
+    pub fn try_as_symbol(&self) -> Option<&Symbol> {
+        self.kind().try_as_symbol()
+    }
+
+    /// If this is a [`Number`] expression, return that. Otherwise return None.
+    pub fn try_as_number(&self) -> Option<Number> {
+        self.kind().try_as_number()
+    }
+
+    /// If this is a real number, return it as an `f64`. Otherwise return None.
+    ///
+    /// This is the inverse of the `From<f64>` impl for `Expr`, and also recognizes the
+    /// symbol `Infinity`:
+    ///
+    /// Expression                      | Result
+    /// --------------------------------|-------------------
+    /// machine real `x`                | `Some(x)`
+    /// `Indeterminate`                 | `Some(f64::NAN)`
+    /// `DirectedInfinity[1]`           | `Some(f64::INFINITY)`
+    /// `Infinity`                      | `Some(f64::INFINITY)`
+    /// `DirectedInfinity[-1]`          | `Some(f64::NEG_INFINITY)`
+    /// `ComplexInfinity`, integers, .. | `None`
+    ///
+    /// `ComplexInfinity` (`DirectedInfinity[]`), and infinities in any other direction,
+    /// have no `f64` representation. Use [`Expr::try_as_number()`] to also accept
+    /// integers.
+    ///
+    /// ```
+    /// # use wolfram_expr::Expr;
+    /// assert_eq!(Expr::real(2.5).try_as_real(), Some(2.5));
+    /// assert_eq!(Expr::from(f64::NEG_INFINITY).try_as_real(), Some(f64::NEG_INFINITY));
+    /// assert!(Expr::from(f64::NAN).try_as_real().unwrap().is_nan());
+    /// assert_eq!(Expr::from(2).try_as_real(), None);
+    /// ```
+    pub fn try_as_real(&self) -> Option<f64> {
+        match self.kind() {
+            ExprKind::Real(real) => Some(real.into_inner()),
+            ExprKind::Symbol(symbol) => match symbol.as_str() {
+                "System`Indeterminate" => Some(f64::NAN),
+                "System`Infinity" => Some(f64::INFINITY),
+                _ => None,
+            },
+            ExprKind::Normal(normal) => {
+                let head = normal.head().try_as_symbol()?;
+
+                if head.as_str() != "System`DirectedInfinity" {
+                    return None;
+                }
+
+                match normal.elements() {
+                    [direction] => match direction.kind() {
+                        ExprKind::Integer(1) => Some(f64::INFINITY),
+                        ExprKind::Integer(-1) => Some(f64::NEG_INFINITY),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            },
+            ExprKind::Integer(_) | ExprKind::String(_) => None,
+        }
+    }
+
+    /// If this is an integer, real, `Rational[n, d]`, `Indeterminate` or
+    /// `ComplexInfinity` expression, return the equivalent [`Numeric`] value.
+    ///
+    /// `DirectedInfinity[]` is equivalent to `ComplexInfinity`.
+    ///
+    /// ```
+    /// # use wolfram_expr::{Expr, Numeric};
+    /// let half = Expr::from(Numeric::rational(1, 2));
+    ///
+    /// assert_eq!(half.try_as_numeric(), Some(Numeric::rational(1, 2)));
+    /// assert_eq!(Expr::from(3).try_as_numeric(), Some(Numeric::Integer(3)));
+    /// assert_eq!(Expr::string("3").try_as_numeric(), None);
+    /// ```
+    pub fn try_as_numeric(&self) -> Option<Numeric> {
         match self.kind() {
+            ExprKind::Integer(int) => Some(Numeric::Integer(*int)),
+            ExprKind::Real(real) => Some(Numeric::Real(*real)),
+            ExprKind::Symbol(symbol) => match symbol.as_str() {
+                "System`Indeterminate" => Some(Numeric::Indeterminate),
+                "System`ComplexInfinity" => Some(Numeric::ComplexInfinity),
+                _ => None,
+            },
+            ExprKind::Normal(normal) => {
+                match (normal.head().try_as_symbol()?.as_str(), normal.elements()) {
+                    ("System`Rational", [numerator, denominator]) => {
+                        match (numerator.kind(), denominator.kind()) {
+                            (ExprKind::Integer(n), ExprKind::Integer(d)) => {
+                                Some(Numeric::rational(*n, *d))
+                            },
+                            _ => None,
+                        }
+                    },
+                    ("System`DirectedInfinity", []) => Some(Numeric::ComplexInfinity),
+                    _ => None,
+                }
+            },
+            ExprKind::String(_) => None,
+        }
+    }
+
+    //---------------------------------------------------------------------------
+    // SEMVER: These methods have been replaced; remove them in a future version.
+    //---------------------------------------------------------------------------
+
+    #[deprecated(note = "Use Expr::try_as_normal() instead")]
+    #[allow(missing_docs)]
+    pub fn try_normal(&self) -> Option<&Normal> {
+        self.try_as_normal()
+    }
+
+    #[deprecated(note = "Use Expr::try_as_symbol() instead")]
+    #[allow(missing_docs)]
+    pub fn try_symbol(&self) -> Option<&Symbol> {
+        self.try_as_symbol()
+    }
+
+    #[deprecated(note = "Use Expr::try_as_number() instead")]
+    #[allow(missing_docs)]
+    pub fn try_number(&self) -> Option<Number> {
+        self.try_as_number()
+    }
+}
+
+/// Accessors shared by every expression representation, including [`Expr`] and
+/// [`LocalExpr`].
+impl<E> ExprKind<E> {
+    /// If this is a [`Normal`] expression, return that. Otherwise return None.
+    pub fn try_as_normal(&self) -> Option<&Normal<E>> {
+        match self {
             ExprKind::Normal(ref normal) => Some(normal),
             ExprKind::Symbol(_)
             | ExprKind::String(_)
@@ -29,7 +175,7 @@ impl Expr {
 
     /// If this is a [`ExprKind::String`] expression, return that. Otherwise return None.
     pub fn try_as_str(&self) -> Option<&str> {
-        match self.kind() {
+        match self {
             ExprKind::String(ref string) => Some(string.as_str()),
             _ => None,
         }
@@ -37,7 +183,7 @@ impl Expr {
 
     /// If this is a [`Symbol`] expression, return that. Otherwise return None.
     pub fn try_as_symbol(&self) -> Option<&Symbol> {
-        match self.kind() {
+        match self {
             ExprKind::Symbol(ref symbol) => Some(symbol),
             ExprKind::Normal(_)
             | ExprKind::String(_)
@@ -48,34 +194,12 @@ impl Expr {
 
     /// If this is a [`Number`] expression, return that. Otherwise return None.
     pub fn try_as_number(&self) -> Option<Number> {
-        match self.kind() {
+        match self {
             ExprKind::Integer(int) => Some(Number::Integer(*int)),
             ExprKind::Real(real) => Some(Number::Real(*real)),
             ExprKind::Normal(_) | ExprKind::String(_) | ExprKind::Symbol(_) => None,
         }
     }
-
-    //---------------------------------------------------------------------------
-    // SEMVER: These methods have been replaced; remove them in a future version.
-    //---------------------------------------------------------------------------
-
-    #[deprecated(note = "Use Expr::try_as_normal() instead")]
-    #[allow(missing_docs)]
-    pub fn try_normal(&self) -> Option<&Normal> {
-        self.try_as_normal()
-    }
-
-    #[deprecated(note = "Use Expr::try_as_symbol() instead")]
-    #[allow(missing_docs)]
-    pub fn try_symbol(&self) -> Option<&Symbol> {
-        self.try_as_symbol()
-    }
-
-    #[deprecated(note = "Use Expr::try_as_number() instead")]
-    #[allow(missing_docs)]
-    pub fn try_number(&self) -> Option<Number> {
-        self.try_as_number()
-    }
 }
 
 //=======================================
@@ -96,17 +220,44 @@ impl From<&Symbol> for Expr {
 
 impl From<Normal> for Expr {
     fn from(normal: Normal) -> Expr {
-        Expr {
-            inner: Arc::new(ExprKind::Normal(normal)),
+        Expr::new(ExprKind::Normal(normal))
+    }
+}
+
+/// Converts finite values to machine reals, and values that have no machine real
+/// representation to their symbolic equivalents:
+///
+/// Value                | Expression
+/// ---------------------|-------------------------
+/// finite `x`           | machine real `x`
+/// NaN                  | `Indeterminate`
+/// `f64::INFINITY`      | `DirectedInfinity[1]`
+/// `f64::NEG_INFINITY`  | `DirectedInfinity[-1]`
+///
+/// [`Expr::try_as_real()`] performs the reverse conversion.
+impl From<f64> for Expr {
+    fn from(real: f64) -> Expr {
+        if real.is_nan() {
+            return Expr::symbol(crate::symbol!("System`Indeterminate"));
         }
+
+        if real.is_infinite() {
+            let direction = if real > 0.0 { 1 } else { -1 };
+
+            return Expr::normal(crate::symbol!("System`DirectedInfinity"), vec![
+                Expr::from(direction),
+            ]);
+        }
+
+        Expr::real(real)
     }
 }
 
 impl From<bool> for Expr {
     fn from(value: bool) -> Expr {
         match value {
-            true => Expr::symbol(Symbol::new("System`True")),
-            false => Expr::symbol(Symbol::new("System`False")),
+            true => Expr::symbol(crate::symbol!("System`True")),
+            false => Expr::symbol(crate::symbol!("System`False")),
         }
     }
 }
@@ -183,8 +334,8 @@ impl From<i64> for Expr {
 //     }
 // }
 
-impl From<Number> for ExprKind {
-    fn from(number: Number) -> ExprKind {
+impl<E> From<Number> for ExprKind<E> {
+    fn from(number: Number) -> ExprKind<E> {
         match number {
             Number::Integer(int) => ExprKind::Integer(int),
             Number::Real(real) => ExprKind::Real(real),
@@ -0,0 +1,555 @@
+//! Compact binary serialization of expressions that preserves shared subexpressions.
+//!
+//! [`Expr`] uses reference counting, so the same subexpression allocation can appear
+//! many times within a single expression. A naive serializer would write a shared
+//! subexpression once for every place it appears. The functions in this module
+//! instead write every shared allocation exactly once, and encode later occurrences
+//! as back-references to the earlier one. When the data is read back in, the
+//! resulting expression has the same sharing structure as the original.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{dag, Expr};
+//!
+//! let big = Expr::list((0..1000).map(Expr::from).collect());
+//!
+//! // {big, big, big}, where each element is the same allocation.
+//! let expr = Expr::list(vec![big.clone(), big.clone(), big.clone()]);
+//!
+//! let bytes = dag::to_bytes(&expr);
+//!
+//! let decoded = dag::from_bytes(&bytes).unwrap();
+//!
+//! assert_eq!(decoded, expr);
+//!
+//! // The shared element was only encoded once.
+//! assert!(bytes.len() < 2 * dag::to_bytes(&big).len());
+//!
+//! // ... and is shared again after decoding.
+//! let elements = decoded.try_as_normal().unwrap().elements();
+//! assert!(std::ptr::eq(elements[0].kind(), elements[2].kind()));
+//! ```
+//!
+//! # Format
+//!
+//! The encoding begins with the 4-byte header `b"WXD\x01"`, followed by the root
+//! expression. Each expression is encoded as a tag byte followed by a payload:
+//!
+//! Tag | Expression     | Payload
+//! ----|----------------|------------------------------------------------------------
+//! `1` | `Integer`      | zigzag-encoded varint
+//! `2` | `Real`         | 8 byte little-endian IEEE 754 `f64`
+//! `3` | `String`       | varint byte length, followed by UTF-8 data
+//! `4` | `Symbol`       | varint byte length, followed by UTF-8 data
+//! `5` | `Normal`       | varint element count, followed by the head, then each element
+//! `6` | back-reference | varint index of a previously defined shared expression
+//!
+//! If the high bit (`0x80`) of the tag of an `Integer`, `Real`, `String`, `Symbol` or
+//! `Normal` is set, the expression is *shared*: once it has been fully read, it is
+//! assigned the next available shared expression index, starting from 0, and may be
+//! referred to by later back-references.
+//!
+//! Varints use the unsigned LEB128 encoding.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    io::{self, Read, Write},
+    mem,
+};
+
+use crate::{
+    limits::Budget, metrics::ALLOCATION_OVERHEAD, ptr_cmp::ExprRefCmp, Expr, ExprKind,
+    LimitExceeded, Limits, Normal, Number, Symbol,
+};
+
+
+const HEADER: &[u8; 4] = b"WXD\x01";
+
+const TAG_INTEGER: u8 = 1;
+const TAG_REAL: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_SYMBOL: u8 = 4;
+const TAG_NORMAL: u8 = 5;
+const TAG_BACK_REFERENCE: u8 = 6;
+
+const SHARED_FLAG: u8 = 0x80;
+
+/// Error returned when decoding an expression fails.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// An I/O error occurred while reading the encoded data. This includes reaching
+    /// the end of the input before a complete expression was read.
+    Io(io::Error),
+    /// The input did not begin with the expected header.
+    InvalidHeader,
+    /// An unrecognized tag byte was encountered.
+    UnknownTag(u8),
+    /// A varint was too large to fit in 64 bits.
+    VarintOverflow,
+    /// A string or symbol contained invalid UTF-8 data.
+    InvalidUtf8,
+    /// A symbol was not a valid absolute Wolfram Language symbol.
+    InvalidSymbol(String),
+    /// A real number was NaN.
+    NaN,
+    /// A back-reference referred to a shared expression that has not been defined.
+    InvalidBackReference(u64),
+    /// A byte slice contained additional data after the encoded expression.
+    TrailingData,
+    /// The expression exceeded one of the [`Limits`] specified when decoding.
+    LimitExceeded(LimitExceeded),
+}
+
+//======================================
+// Encoding
+//======================================
+
+/// Encode `expr` as a vector of bytes.
+pub fn to_bytes(expr: &Expr) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    write(expr, &mut bytes).expect("dag::to_bytes(): writing to Vec<u8> failed");
+
+    bytes
+}
+
+/// Encode `expr` and write it to `writer`.
+///
+/// Every subexpression allocation that appears more than once in `expr` is written
+/// only once.
+///
+/// This function performs many small writes; if `writer` is not already buffered,
+/// consider wrapping it in a [`BufWriter`][std::io::BufWriter].
+pub fn write<W: Write>(expr: &Expr, mut writer: W) -> io::Result<()> {
+    enum Task<'e> {
+        Visit(&'e Expr),
+        Define(&'e Expr),
+    }
+
+    writer.write_all(HEADER)?;
+
+    // Indices assigned to shared allocations that have already been written.
+    let mut defined: HashMap<ExprRefCmp, u64> = HashMap::new();
+
+    let mut stack = vec![Task::Visit(expr)];
+
+    while let Some(task) = stack.pop() {
+        let expr = match task {
+            Task::Visit(expr) => expr,
+            Task::Define(expr) => {
+                let index = defined.len() as u64;
+                defined.insert(ExprRefCmp(expr.clone()), index);
+                continue;
+            },
+        };
+
+        // An allocation with only one reference cannot appear more than once in the
+        // expression being written, so there is no need to define it.
+        let is_shared = expr.ref_count() > 1;
+
+        if is_shared {
+            if let Some(index) = defined.get(&ExprRefCmp(expr.clone())) {
+                writer.write_all(&[TAG_BACK_REFERENCE])?;
+                write_varint(&mut writer, *index)?;
+                continue;
+            }
+        }
+
+        let flag = if is_shared { SHARED_FLAG } else { 0 };
+
+        match expr.kind() {
+            ExprKind::Integer(int) => {
+                writer.write_all(&[TAG_INTEGER | flag])?;
+                write_varint(&mut writer, zigzag_encode(*int))?;
+            },
+            ExprKind::Real(real) => {
+                writer.write_all(&[TAG_REAL | flag])?;
+                writer.write_all(&real.into_inner().to_le_bytes())?;
+            },
+            ExprKind::String(string) => {
+                writer.write_all(&[TAG_STRING | flag])?;
+                write_str(&mut writer, string)?;
+            },
+            ExprKind::Symbol(symbol) => {
+                writer.write_all(&[TAG_SYMBOL | flag])?;
+                write_str(&mut writer, symbol.as_str())?;
+            },
+            ExprKind::Normal(Normal { head, contents }) => {
+                writer.write_all(&[TAG_NORMAL | flag])?;
+                write_varint(&mut writer, contents.len() as u64)?;
+
+                // The normal expression is defined only after its head and elements
+                // have been written.
+                if is_shared {
+                    stack.push(Task::Define(expr));
+                }
+
+                for elem in contents.iter().rev() {
+                    stack.push(Task::Visit(elem));
+                }
+                stack.push(Task::Visit(head));
+
+                continue;
+            },
+        }
+
+        if is_shared {
+            stack.push(Task::Define(expr));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_str<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    write_varint(writer, string.len() as u64)?;
+    writer.write_all(string.as_bytes())
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+//======================================
+// Decoding
+//======================================
+
+/// Decode an expression from a slice of bytes produced by [`to_bytes()`].
+///
+/// The default [`Limits`] are applied while decoding. Use
+/// [`from_bytes_with_limits()`] to specify different limits.
+///
+/// Returns an error if `bytes` contains any data after the encoded expression.
+pub fn from_bytes(bytes: &[u8]) -> Result<Expr, DecodeError> {
+    from_bytes_with_limits(bytes, &Limits::default())
+}
+
+/// Decode an expression from a slice of bytes produced by [`to_bytes()`], returning an
+/// error if the expression exceeds any of the specified `limits`.
+///
+/// Returns an error if `bytes` contains any data after the encoded expression.
+pub fn from_bytes_with_limits(
+    mut bytes: &[u8],
+    limits: &Limits,
+) -> Result<Expr, DecodeError> {
+    let expr = read_with_limits(&mut bytes, limits)?;
+
+    if !bytes.is_empty() {
+        return Err(DecodeError::TrailingData);
+    }
+
+    Ok(expr)
+}
+
+/// Read and decode an expression written by [`write()`].
+///
+/// The default [`Limits`] are applied while decoding. Use [`read_with_limits()`] to
+/// specify different limits.
+///
+/// Shared subexpressions in the encoded data will be shared in the returned
+/// expression.
+///
+/// This function does not read any data past the end of the encoded expression. It
+/// performs many small reads; if `reader` is not already buffered, consider wrapping
+/// it in a [`BufReader`][std::io::BufReader].
+pub fn read<R: Read>(reader: R) -> Result<Expr, DecodeError> {
+    read_with_limits(reader, &Limits::default())
+}
+
+/// Read and decode an expression written by [`write()`], returning an error if the
+/// expression exceeds any of the specified `limits`.
+///
+/// Limits are checked before the memory needed to represent the offending data is
+/// allocated. Shared subexpressions count towards [`Limits::max_nesting`] and
+/// [`Limits::max_node_count`] at every place they are referenced, so a small input
+/// cannot describe an expression that would be exponentially large if traversed.
+///
+/// See [`read()`] for more details.
+pub fn read_with_limits<R: Read>(
+    mut reader: R,
+    limits: &Limits,
+) -> Result<Expr, DecodeError> {
+    /// A normal expression whose head and elements are still being read.
+    struct Partial {
+        shared: bool,
+        len: usize,
+        head: Option<Expr>,
+        contents: Vec<Expr>,
+        /// Maximum nesting of the head and elements read so far.
+        child_nesting: usize,
+        /// Number of nodes read before this expression was started.
+        nodes_before: usize,
+    }
+
+    /// A shared expression that may be referred to by a back-reference.
+    struct Shared {
+        expr: Expr,
+        nesting: usize,
+        node_count: usize,
+    }
+
+    let mut header = [0; 4];
+    reader.read_exact(&mut header)?;
+    if header != *HEADER {
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    let mut budget = Budget::new(limits);
+
+    let mut shared: Vec<Shared> = Vec::new();
+    let mut stack: Vec<Partial> = Vec::new();
+
+    loop {
+        let tag = read_u8(&mut reader)?;
+
+        let is_shared = tag & SHARED_FLAG != 0;
+
+        let nodes_before = budget.nodes();
+
+        // The nesting of the subexpression being read, counting heads. Atoms have a
+        // nesting of 1.
+        let mut nesting = 1;
+
+        let mut expr = match tag & !SHARED_FLAG {
+            TAG_INTEGER => {
+                let value = zigzag_decode(read_varint(&mut reader)?);
+                Expr::number(Number::Integer(value))
+            },
+            TAG_REAL => {
+                let mut bytes = [0; 8];
+                reader.read_exact(&mut bytes)?;
+                let real = crate::F64::new(f64::from_le_bytes(bytes))
+                    .map_err(|_| DecodeError::NaN)?;
+                Expr::number(Number::Real(real))
+            },
+            TAG_STRING => Expr::string(read_string(&mut reader, &mut budget)?),
+            TAG_SYMBOL => {
+                let string = read_string(&mut reader, &mut budget)?;
+                match Symbol::try_new(&string) {
+                    Some(symbol) => Expr::symbol(symbol),
+                    None => return Err(DecodeError::InvalidSymbol(string)),
+                }
+            },
+            TAG_NORMAL => {
+                let len = read_len(&mut reader)?;
+
+                // The head and every element are at least one node each, so check the
+                // node count and size limits before allocating space for the
+                // elements.
+                budget.check_nesting(stack.len() + 1)?;
+                budget.check_nodes(len.saturating_add(2))?;
+                budget.add_nodes(1)?;
+                budget.add_bytes(
+                    ALLOCATION_OVERHEAD
+                        .saturating_add(len.saturating_mul(mem::size_of::<Expr>())),
+                )?;
+
+                stack.push(Partial {
+                    shared: is_shared,
+                    len,
+                    head: None,
+                    contents: Vec::with_capacity(len),
+                    child_nesting: 0,
+                    nodes_before,
+                });
+                continue;
+            },
+            TAG_BACK_REFERENCE if !is_shared => {
+                let index = read_varint(&mut reader)?;
+                let Shared {
+                    expr,
+                    nesting: shared_nesting,
+                    node_count,
+                } = match usize::try_from(index).ok().and_then(|i| shared.get(i)) {
+                    Some(shared) => shared,
+                    None => return Err(DecodeError::InvalidBackReference(index)),
+                };
+
+                // The shared expression has already been allocated, so it only
+                // counts towards the nesting and node count limits.
+                nesting = *shared_nesting;
+                budget.add_nodes(*node_count)?;
+
+                expr.clone()
+            },
+            _ => return Err(DecodeError::UnknownTag(tag)),
+        };
+
+        if tag & !SHARED_FLAG != TAG_BACK_REFERENCE {
+            budget.add_nodes(1)?;
+            budget.add_bytes(ALLOCATION_OVERHEAD)?;
+        }
+
+        budget.check_nesting(stack.len().saturating_add(nesting))?;
+
+        if is_shared {
+            shared.push(Shared {
+                expr: expr.clone(),
+                nesting,
+                node_count: budget.nodes() - nodes_before,
+            });
+        }
+
+        // Add the completed expression to its parent. If that completes the parent,
+        // continue with the parent's parent, and so on.
+        loop {
+            let parent = match stack.last_mut() {
+                Some(parent) => parent,
+                None => return Ok(expr),
+            };
+
+            parent.child_nesting = parent.child_nesting.max(nesting);
+
+            match parent.head {
+                None => parent.head = Some(expr),
+                Some(_) => parent.contents.push(expr),
+            }
+
+            if parent.head.is_none() || parent.contents.len() < parent.len {
+                break;
+            }
+
+            let Partial {
+                shared: is_shared,
+                len: _,
+                head,
+                contents,
+                child_nesting,
+                nodes_before,
+            } = stack.pop().unwrap();
+
+            expr = Expr::normal(head.unwrap(), contents);
+            nesting = child_nesting + 1;
+
+            if is_shared {
+                shared.push(Shared {
+                    expr: expr.clone(),
+                    nesting,
+                    node_count: budget.nodes() - nodes_before,
+                });
+            }
+        }
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64, DecodeError> {
+    let mut value: u64 = 0;
+
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+
+        let bits = u64::from(byte & 0x7F);
+        if shift == 63 && bits > 1 {
+            return Err(DecodeError::VarintOverflow);
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(DecodeError::VarintOverflow)
+}
+
+fn read_len<R: Read>(reader: &mut R) -> Result<usize, DecodeError> {
+    let len = read_varint(reader)?;
+    usize::try_from(len).map_err(|_| DecodeError::VarintOverflow)
+}
+
+fn read_string<R: Read>(
+    reader: &mut R,
+    budget: &mut Budget,
+) -> Result<String, DecodeError> {
+    let len = read_varint(reader)?;
+
+    budget.check_string_length(len)?;
+    budget.add_bytes(usize::try_from(len).unwrap_or(usize::MAX))?;
+
+    // Read through `take()` instead of allocating a buffer of `len` bytes up front, so
+    // that a corrupt length doesn't cause a huge allocation if the input is short.
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(DecodeError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+//======================================
+// Formatting impls
+//======================================
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "I/O error: {}", err),
+            DecodeError::InvalidHeader => write!(f, "invalid header"),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown tag: {:#04x}", tag),
+            DecodeError::VarintOverflow => write!(f, "varint overflows 64 bits"),
+            DecodeError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            DecodeError::InvalidSymbol(string) => {
+                write!(f, "string is not parseable as a symbol: {}", string)
+            },
+            DecodeError::NaN => write!(f, "real number is NaN"),
+            DecodeError::InvalidBackReference(index) => {
+                write!(
+                    f,
+                    "back-reference to undefined shared expression: {}",
+                    index
+                )
+            },
+            DecodeError::TrailingData => write!(f, "trailing data after expression"),
+            DecodeError::LimitExceeded(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            DecodeError::LimitExceeded(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
+
+impl From<LimitExceeded> for DecodeError {
+    fn from(err: LimitExceeded) -> Self {
+        DecodeError::LimitExceeded(err)
+    }
+}
@@ -0,0 +1,525 @@
+//! Structural differences between expressions.
+//!
+//! [`diff()`] compares two expressions and returns the list of [`Edit`]s that
+//! transform the first into the second. Each edit is keyed by the
+//! [`Part`](https://reference.wolfram.com/language/ref/Part.html) <sub>WL</sub>
+//! position of the subexpression it affects, so that a change deep inside a large
+//! expression can be located without comparing two long [`Display`][std::fmt::Display]
+//! strings by eye.
+//!
+//! [`render()`] formats a list of edits as human-readable text, and [`apply_patch()`]
+//! applies a list of edits to an expression.
+//!
+//! # Positions
+//!
+//! Positions use the same convention as `Part`: elements are numbered starting from
+//! 1, and index 0 refers to the head of a normal expression. The empty position
+//! `{}` refers to the whole expression.
+//!
+//! Edits are applied in order, and the position of each edit refers to the
+//! expression as it is after all of the preceding edits have been applied.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{diff, Expr};
+//!
+//! let list = Expr::list;
+//!
+//! // {1, 2, {"x"}}
+//! let old = list(vec![Expr::from(1), Expr::from(2), list(vec![Expr::from("x")])]);
+//! // {2, {"y"}, 3}
+//! let new = list(vec![Expr::from(2), list(vec![Expr::from("y")]), Expr::from(3)]);
+//!
+//! let edits = diff::diff(&old, &new);
+//!
+//! assert_eq!(diff::render(&edits), "\
+//! {1}: deleted 1
+//! {3}: inserted 3
+//! {2, 1}: replaced \"x\" with \"y\"
+//! ");
+//!
+//! assert_eq!(diff::apply_patch(&old, &edits).unwrap(), new);
+//! ```
+
+use std::fmt;
+
+use crate::{Expr, ExprKind, Normal};
+
+
+/// Maximum number of cells in the table used to align the elements of two normal
+/// expressions.
+///
+/// Element lists that would require a larger table are aligned by position instead,
+/// after removing any common prefix and suffix.
+const MAX_ALIGNMENT_CELLS: usize = 1 << 22;
+
+/// A single change to an expression, produced by [`diff()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Edit {
+    /// The subexpression at `position` was replaced by a different expression.
+    Replaced {
+        /// Position of the replaced subexpression.
+        position: Vec<usize>,
+        /// The subexpression before the edit.
+        old: Expr,
+        /// The subexpression after the edit.
+        new: Expr,
+    },
+    /// A new element was inserted so that it is at `position`.
+    ///
+    /// The element previously at `position`, and any elements after it, are shifted
+    /// one place to the right.
+    Inserted {
+        /// Position of the inserted element.
+        position: Vec<usize>,
+        /// The inserted element.
+        new: Expr,
+    },
+    /// The element at `position` was deleted.
+    ///
+    /// Any elements after it are shifted one place to the left.
+    Deleted {
+        /// Position of the deleted element.
+        position: Vec<usize>,
+        /// The deleted element.
+        old: Expr,
+    },
+    /// The head of the normal expression at `position` was replaced.
+    HeadChanged {
+        /// Position of the normal expression whose head changed.
+        position: Vec<usize>,
+        /// The head before the edit.
+        old: Expr,
+        /// The head after the edit.
+        new: Expr,
+    },
+}
+
+/// Error returned by [`apply_patch()`] when an edit cannot be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The position of an edit does not refer to a valid part of the expression.
+    InvalidPosition(Vec<usize>),
+    /// The subexpression at the position of an edit is not equal to the old
+    /// subexpression recorded in the edit.
+    Mismatch(Vec<usize>),
+}
+
+//======================================
+// Diffing
+//======================================
+
+/// Compute the edits that transform `old` into `new`.
+///
+/// Applying the returned edits to `old` using [`apply_patch()`] produces an
+/// expression equal to `new`. If `old` and `new` are equal, no edits are returned.
+///
+/// The elements of normal expressions are aligned so that inserting or deleting an
+/// element is reported as a single [`Edit::Inserted`] or [`Edit::Deleted`], instead of
+/// as a change to every element that follows it. Normal expressions whose heads or
+/// lengths are the same are compared element by element; other differing
+/// subexpressions are reported as a single [`Edit::Replaced`].
+pub fn diff(old: &Expr, new: &Expr) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    if old == new {
+        return edits;
+    }
+
+    if !is_comparable(old, new) {
+        edits.push(Edit::Replaced {
+            position: Vec::new(),
+            old: old.clone(),
+            new: new.clone(),
+        });
+        return edits;
+    }
+
+    // Pairs of differing normal expressions whose parts remain to be compared.
+    let mut stack: Vec<(&Normal, &Normal, Vec<usize>)> = Vec::new();
+    push_normals(&mut stack, old, new, Vec::new());
+
+    while let Some((old, new, position)) = stack.pop() {
+        let mut children = Vec::new();
+
+        if old.head != new.head {
+            if is_comparable(&old.head, &new.head) {
+                children.push((&old.head, &new.head, child(&position, 0)));
+            } else {
+                edits.push(Edit::HeadChanged {
+                    position: position.clone(),
+                    old: old.head.clone(),
+                    new: new.head.clone(),
+                });
+            }
+        }
+
+        // Index of the next element in the expression being edited.
+        let mut index = 0;
+
+        for op in align(&old.contents, &new.contents) {
+            match op {
+                Op::Keep => index += 1,
+                Op::Modify(old, new) => {
+                    index += 1;
+
+                    if is_comparable(old, new) {
+                        children.push((old, new, child(&position, index)));
+                    } else {
+                        edits.push(Edit::Replaced {
+                            position: child(&position, index),
+                            old: old.clone(),
+                            new: new.clone(),
+                        });
+                    }
+                },
+                Op::Delete(old) => edits.push(Edit::Deleted {
+                    position: child(&position, index + 1),
+                    old: old.clone(),
+                }),
+                Op::Insert(new) => {
+                    index += 1;
+
+                    edits.push(Edit::Inserted {
+                        position: child(&position, index),
+                        new: new.clone(),
+                    });
+                },
+            }
+        }
+
+        // The elements of this expression are now in their final positions, so the
+        // edits within each modified element can be emitted after the edits to this
+        // expression. Push in reverse so that they are processed in order.
+        for (old, new, position) in children.into_iter().rev() {
+            push_normals(&mut stack, old, new, position);
+        }
+    }
+
+    edits
+}
+
+/// Returns `true` if `old` and `new` are normal expressions that should be compared
+/// part by part, instead of being replaced as a whole.
+fn is_comparable(old: &Expr, new: &Expr) -> bool {
+    match (old.kind(), new.kind()) {
+        (ExprKind::Normal(old), ExprKind::Normal(new)) => {
+            old.head == new.head || old.contents.len() == new.contents.len()
+        },
+        _ => false,
+    }
+}
+
+fn push_normals<'e>(
+    stack: &mut Vec<(&'e Normal, &'e Normal, Vec<usize>)>,
+    old: &'e Expr,
+    new: &'e Expr,
+    position: Vec<usize>,
+) {
+    match (old.kind(), new.kind()) {
+        (ExprKind::Normal(old), ExprKind::Normal(new)) => {
+            stack.push((old, new, position))
+        },
+        _ => unreachable!("diff: expected normal expressions"),
+    }
+}
+
+fn child(position: &[usize], index: usize) -> Vec<usize> {
+    let mut child = position.to_vec();
+    child.push(index);
+    child
+}
+
+//======================================
+// Alignment
+//======================================
+
+/// Step in the alignment of two lists of elements.
+enum Op<'e> {
+    /// The next old and new elements are equal.
+    Keep,
+    /// The next old element was changed into the next new element.
+    Modify(&'e Expr, &'e Expr),
+    /// The next old element was deleted.
+    Delete(&'e Expr),
+    /// The next new element was inserted.
+    Insert(&'e Expr),
+}
+
+/// Align `old` and `new` using a longest common subsequence of equal elements.
+///
+/// Runs of deleted and inserted elements between two kept elements are paired up
+/// into modifications.
+fn align<'e>(old: &'e [Expr], new: &'e [Expr]) -> Vec<Op<'e>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // Pairs of indices into `old_middle` and `new_middle` of the elements that are
+    // kept.
+    let kept = longest_common_subsequence(old_middle, new_middle);
+
+    let mut ops: Vec<Op> = Vec::with_capacity(old.len() + new.len());
+    ops.extend((0..prefix).map(|_| Op::Keep));
+
+    let (mut i, mut j) = (0, 0);
+    let end = (old_middle.len(), new_middle.len());
+
+    for (next_i, next_j) in kept.into_iter().chain(std::iter::once(end)) {
+        let deleted = &old_middle[i..next_i];
+        let inserted = &new_middle[j..next_j];
+        let paired = deleted.len().min(inserted.len());
+
+        for (old, new) in deleted.iter().zip(inserted) {
+            ops.push(Op::Modify(old, new));
+        }
+        ops.extend(deleted[paired..].iter().map(Op::Delete));
+        ops.extend(inserted[paired..].iter().map(Op::Insert));
+
+        if (next_i, next_j) != end {
+            ops.push(Op::Keep);
+        }
+
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+
+    ops.extend((0..suffix).map(|_| Op::Keep));
+
+    ops
+}
+
+/// Indices of the elements of a longest common subsequence of `old` and `new`.
+///
+/// If the lists are too long to align, returns no common elements.
+fn longest_common_subsequence(old: &[Expr], new: &[Expr]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+
+    if n == 0 || m == 0 || n.saturating_mul(m) > MAX_ALIGNMENT_CELLS {
+        return Vec::new();
+    }
+
+    // `lengths[i * (m + 1) + j]` is the length of the longest common subsequence of
+    // `old[i..]` and `new[j..]`.
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[at(i, j)] = if old[i] == new[j] {
+                lengths[at(i + 1, j + 1)] + 1
+            } else {
+                lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut kept = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < n && j < m {
+        if old[i] == new[j] {
+            kept.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    kept
+}
+
+//======================================
+// Patching
+//======================================
+
+/// Apply `edits`, in order, to `expr`.
+///
+/// Returns an error if the position of an edit does not exist, or if the
+/// subexpression being replaced or deleted by an edit is not equal to the old value
+/// recorded in the edit.
+///
+/// `expr` is not modified; subexpressions that are not affected by any edit are
+/// shared between `expr` and the returned expression.
+pub fn apply_patch(expr: &Expr, edits: &[Edit]) -> Result<Expr, PatchError> {
+    let mut expr = expr.clone();
+
+    for edit in edits {
+        match edit {
+            Edit::Replaced { position, old, new } => {
+                let part = part_mut(&mut expr, position)?;
+                if part != old {
+                    return Err(PatchError::Mismatch(position.clone()));
+                }
+                *part = new.clone();
+            },
+            Edit::HeadChanged { position, old, new } => {
+                let normal = normal_mut(&mut expr, position)?;
+                if normal.head != *old {
+                    return Err(PatchError::Mismatch(position.clone()));
+                }
+                normal.head = new.clone();
+            },
+            Edit::Inserted { position, new } => {
+                let (parent, index) = element_position(position)?;
+                let normal = normal_mut(&mut expr, parent)?;
+                if index > normal.contents.len() {
+                    return Err(PatchError::InvalidPosition(position.clone()));
+                }
+                normal.contents.insert(index, new.clone());
+            },
+            Edit::Deleted { position, old } => {
+                let (parent, index) = element_position(position)?;
+                let normal = normal_mut(&mut expr, parent)?;
+                match normal.contents.get(index) {
+                    Some(element) if element == old => {
+                        normal.contents.remove(index);
+                    },
+                    Some(_) => return Err(PatchError::Mismatch(position.clone())),
+                    None => return Err(PatchError::InvalidPosition(position.clone())),
+                }
+            },
+        }
+    }
+
+    Ok(expr)
+}
+
+/// Split the position of an element into the position of its parent and its 0-based
+/// index.
+fn element_position(position: &[usize]) -> Result<(&[usize], usize), PatchError> {
+    match position.split_last() {
+        Some((&index, parent)) if index > 0 => Ok((parent, index - 1)),
+        _ => Err(PatchError::InvalidPosition(position.to_vec())),
+    }
+}
+
+/// Get mutable access to the subexpression of `expr` at `position`.
+///
+/// Any shared allocations along the path to the subexpression are cloned.
+fn part_mut<'e>(
+    mut expr: &'e mut Expr,
+    position: &[usize],
+) -> Result<&'e mut Expr, PatchError> {
+    for (depth, &index) in position.iter().enumerate() {
+        let normal = match expr.kind_mut() {
+            ExprKind::Normal(normal) => normal,
+            _ => return Err(PatchError::InvalidPosition(position[..=depth].to_vec())),
+        };
+
+        expr = match index {
+            0 => &mut normal.head,
+            _ => match normal.contents.get_mut(index - 1) {
+                Some(element) => element,
+                None => {
+                    return Err(PatchError::InvalidPosition(position[..=depth].to_vec()))
+                },
+            },
+        };
+    }
+
+    Ok(expr)
+}
+
+/// Get mutable access to the normal expression at `position` in `expr`.
+fn normal_mut<'e>(
+    expr: &'e mut Expr,
+    position: &[usize],
+) -> Result<&'e mut Normal, PatchError> {
+    match part_mut(expr, position)?.kind_mut() {
+        ExprKind::Normal(normal) => Ok(normal),
+        _ => Err(PatchError::InvalidPosition(position.to_vec())),
+    }
+}
+
+//======================================
+// Rendering
+//======================================
+
+/// Format `edits` as human-readable text, with one edit per line.
+///
+/// See [`Edit`]'s [`Display`][fmt::Display] impl for the format of each line.
+pub fn render(edits: &[Edit]) -> String {
+    let mut text = String::new();
+
+    for edit in edits {
+        text.push_str(&edit.to_string());
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Formats a single edit as `{position}: description`, e.g.
+/// `` {2, 1}: replaced Global`x with Global`y ``.
+///
+/// The position of a [`Edit::HeadChanged`] is formatted as the position of the head,
+/// ending in `0`.
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Replaced { position, old, new } => {
+                write!(f, "{}: replaced {} with {}", Position(position), old, new)
+            },
+            Edit::Inserted { position, new } => {
+                write!(f, "{}: inserted {}", Position(position), new)
+            },
+            Edit::Deleted { position, old } => {
+                write!(f, "{}: deleted {}", Position(position), old)
+            },
+            Edit::HeadChanged { position, old, new } => write!(
+                f,
+                "{}: head changed from {} to {}",
+                Position(&child(position, 0)),
+                old,
+                new
+            ),
+        }
+    }
+}
+
+/// Formats a position as a Wolfram Language list, e.g. `{2, 1}`.
+struct Position<'p>(&'p [usize]);
+
+impl fmt::Display for Position<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Position(indices) = self;
+
+        write!(f, "{{")?;
+        for (idx, index) in indices.iter().enumerate() {
+            if idx != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", index)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::InvalidPosition(position) => {
+                write!(f, "position {} does not exist", Position(position))
+            },
+            PatchError::Mismatch(position) => write!(
+                f,
+                "expression at position {} does not match the patch",
+                Position(position)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
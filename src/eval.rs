@@ -0,0 +1,560 @@
+//! Evaluation of expressions using user-defined rewrite rules.
+//!
+//! [`Evaluator`] implements a small subset of the Wolfram Language evaluation
+//! procedure, without a Wolfram kernel. Definitions are stored in the same three
+//! tables the kernel uses, keyed by the [`Symbol`] that the definition is associated
+//! with:
+//!
+//! Table       | Definition           | Example
+//! ------------|----------------------|--------------------
+//! OwnValues   | `x = value`          | `x = 5`
+//! DownValues  | `f[args] := value`   | `f[x_] := g[x, x]`
+//! SubValues   | `f[a][b] := value`   | `f[x_][y_] := {x, y}`
+//!
+//! An expression is evaluated using the standard evaluation order: the head is
+//! evaluated first, then each element in turn, and then the first definition whose
+//! left-hand side matches the resulting expression is applied. This is repeated until
+//! the expression no longer changes.
+//!
+//! # Attributes
+//!
+//! Each evaluator has an [`AttributeRegistry`], which initially contains the
+//! attributes of common `` System` `` symbols. The attributes of the head of an
+//! expression control how it is evaluated:
+//!
+//! Attribute                 | Effect
+//! --------------------------|-----------------------------------------------
+//! `HoldFirst`, `HoldRest`   | The first, or all but the first, are held
+//! `HoldAll`                 | No elements are evaluated
+//! `HoldAllComplete`         | Like `HoldAll`, and `Sequence[...]` is kept
+//! `SequenceHold`            | `Sequence[...]` elements are not spliced
+//! `Flat`                    | Nested applications are [flattened]
+//! `Listable`                | The head is [threaded] over lists
+//! `Orderless`               | The elements are [sorted]
+//! `Protected`               | Definitions return [`EvalError::Protected`]
+//!
+//! [flattened]: crate::attributes::flatten
+//! [threaded]: crate::attributes::thread_listable
+//! [sorted]: crate::attributes::sort_orderless
+//!
+//! `Flat` and `Orderless` do not affect pattern matching.
+//!
+//! Evaluation is limited by an iteration limit, equivalent to
+//! [`$IterationLimit`](https://reference.wolfram.com/language/ref/$IterationLimit.html)
+//! <sub>WL</sub>, and a recursion limit, equivalent to
+//! [`$RecursionLimit`](https://reference.wolfram.com/language/ref/$RecursionLimit.html)
+//! <sub>WL</sub>. Exceeding either limit returns an [`EvalError`], so evaluating a
+//! non-terminating rule program fails instead of looping forever.
+//!
+//! # Patterns
+//!
+//! The left-hand side of a definition may contain the patterns `Blank`,
+//! `BlankSequence`, `BlankNullSequence`, `Pattern`, `HoldPattern` and `Alternatives`.
+//! Definitions are tried in the order they were made, except that definitions whose
+//! left-hand side contains no patterns are tried before any definition that does.
+//! Making a definition with the same left-hand side as an existing definition replaces
+//! it.
+//!
+//! A definition is associated with a symbol after removing any `HoldPattern` wrappers
+//! from its left-hand side, so `HoldPattern[f[x_]] := ...` is a DownValue of `f`.
+//! Conditions and pattern tests are not evaluated, so making a definition whose
+//! left-hand side contains `Condition` or `PatternTest` returns
+//! [`EvalError::UnsupportedPattern`].
+//!
+//! The values bound to named patterns are substituted into the right-hand side using
+//! [`scope::substitute()`], so local symbols of scoping constructs like `Function` and
+//! `Module` in the right-hand side are not replaced.
+//!
+//! # Built-in functions
+//!
+//! The evaluator has no built-in mathematical functions. It does implement
+//! `Set` (`=`), `SetDelayed` (`:=`) and `CompoundExpression` (`;`), so that rule
+//! programs can make definitions as they are evaluated. Which of their elements are
+//! evaluated is determined by their `HoldFirst` and `HoldAll` attributes.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{eval::Evaluator, wl};
+//!
+//! let mut evaluator = Evaluator::new();
+//!
+//! let m_ = wl!(Pattern[Global::m, Blank[]]);
+//! let n_ = wl!(Pattern[Global::n, Blank[]]);
+//!
+//! // plus[zero, n_] := n
+//! evaluator.set_delayed(
+//!     wl!(context = "Global`"; plus[zero, #(n_.clone())]),
+//!     wl!(context = "Global`"; n),
+//! )?;
+//!
+//! // plus[s[m_], n_] := s[plus[m, n]]
+//! evaluator.set_delayed(
+//!     wl!(context = "Global`"; plus[s[#m_], #n_]),
+//!     wl!(context = "Global`"; s[plus[m, n]]),
+//! )?;
+//!
+//! assert_eq!(
+//!     evaluator.evaluate(&wl!(context = "Global`"; plus[s[s[zero]], s[zero]]))?,
+//!     wl!(context = "Global`"; s[s[s[zero]]])
+//! );
+//! # Ok::<(), wolfram_expr::eval::EvalError>(())
+//! ```
+
+mod dependency;
+pub(crate) mod pattern;
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    attributes::{self, AttributeRegistry, Attributes},
+    scope, Expr, ExprKind, Symbol,
+};
+
+use self::pattern::{has_condition, head_name, is_pattern, match_pattern};
+
+pub use self::dependency::DependencyGraph;
+
+
+/// Evaluates expressions using a set of user-defined rewrite rules.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    own_values: HashMap<Symbol, Expr>,
+    down_values: HashMap<Symbol, Vec<Definition>>,
+    sub_values: HashMap<Symbol, Vec<Definition>>,
+    attributes: AttributeRegistry,
+    iteration_limit: usize,
+    recursion_limit: usize,
+}
+
+/// Definition of a rewrite rule `lhs :> rhs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    /// Pattern matched by this definition.
+    pub lhs: Expr,
+    /// Replacement for expressions that match [`Definition::lhs`], with the values
+    /// bound to named patterns substituted.
+    pub rhs: Expr,
+}
+
+/// Error returned when an expression cannot be evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// An expression was rewritten more times than the iteration limit allows.
+    IterationLimitExceeded(usize),
+    /// Evaluation was nested more deeply than the recursion limit allows.
+    RecursionLimitExceeded(usize),
+    /// A definition was made for an expression that has no symbol to associate the
+    /// definition with, like `1 = 2`.
+    InvalidDefinition(Expr),
+    /// A definition was made for a symbol with the `Protected` attribute, like
+    /// `Plus[x_, y_] := 0`.
+    Protected(Symbol),
+    /// A definition was made whose left-hand side contains a `Condition` or
+    /// `PatternTest`, like `f[x_] /; x > 0 := x`. Conditions and pattern tests are not
+    /// evaluated, so the definition could never match.
+    UnsupportedPattern(Expr),
+}
+
+impl Evaluator {
+    /// Default iteration limit, equal to the default value of `$IterationLimit`.
+    pub const DEFAULT_ITERATION_LIMIT: usize = 4096;
+
+    /// Default recursion limit, equal to the default value of `$RecursionLimit`.
+    pub const DEFAULT_RECURSION_LIMIT: usize = 1024;
+
+    /// Construct a new evaluator with no definitions, and the attributes of common
+    /// `` System` `` symbols.
+    pub fn new() -> Self {
+        Evaluator::with_attributes(AttributeRegistry::new())
+    }
+
+    /// Construct a new evaluator with no definitions, using `attributes` as the
+    /// attributes of symbols.
+    pub fn with_attributes(attributes: AttributeRegistry) -> Self {
+        Evaluator {
+            own_values: HashMap::new(),
+            down_values: HashMap::new(),
+            sub_values: HashMap::new(),
+            attributes,
+            iteration_limit: Evaluator::DEFAULT_ITERATION_LIMIT,
+            recursion_limit: Evaluator::DEFAULT_RECURSION_LIMIT,
+        }
+    }
+
+    //==================================
+    // Limits
+    //==================================
+
+    /// Maximum number of times a single expression may be rewritten before it reaches
+    /// a fixed point.
+    pub fn iteration_limit(&self) -> usize {
+        self.iteration_limit
+    }
+
+    /// Set the maximum number of times a single expression may be rewritten.
+    pub fn set_iteration_limit(&mut self, limit: usize) {
+        self.iteration_limit = limit;
+    }
+
+    /// Maximum nesting depth of evaluation.
+    pub fn recursion_limit(&self) -> usize {
+        self.recursion_limit
+    }
+
+    /// Set the maximum nesting depth of evaluation.
+    ///
+    /// Each level of nesting uses a fixed amount of space on the native stack, so very
+    /// large limits may cause a stack overflow.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit;
+    }
+
+    //==================================
+    // Attributes
+    //==================================
+
+    /// Attributes of the symbols used by this evaluator.
+    pub fn attributes(&self) -> &AttributeRegistry {
+        &self.attributes
+    }
+
+    /// Mutable access to the attributes of the symbols used by this evaluator.
+    pub fn attributes_mut(&mut self) -> &mut AttributeRegistry {
+        &mut self.attributes
+    }
+
+    //==================================
+    // Definitions
+    //==================================
+
+    /// Evaluate `rhs` and define it as the value of `lhs`, like `lhs = rhs`.
+    ///
+    /// Returns the evaluated value of `rhs`.
+    pub fn set(&mut self, lhs: Expr, rhs: Expr) -> Result<Expr, EvalError> {
+        let rhs = self.evaluate(&rhs)?;
+        self.define(lhs, rhs.clone())?;
+        Ok(rhs)
+    }
+
+    /// Define `rhs` as the value of `lhs` without evaluating it, like `lhs := rhs`.
+    pub fn set_delayed(&mut self, lhs: Expr, rhs: Expr) -> Result<(), EvalError> {
+        self.define(lhs, rhs)
+    }
+
+    /// Remove all definitions associated with `symbol`, like `Clear[symbol]`.
+    pub fn clear(&mut self, symbol: &Symbol) {
+        self.own_values.remove(symbol);
+        self.down_values.remove(symbol);
+        self.sub_values.remove(symbol);
+    }
+
+    /// Get the value of `symbol`, if it has one.
+    pub fn own_value(&self, symbol: &Symbol) -> Option<&Expr> {
+        self.own_values.get(symbol)
+    }
+
+    /// Get the definitions of the form `symbol[...] := ...`, in the order they are
+    /// tried.
+    pub fn down_values(&self, symbol: &Symbol) -> &[Definition] {
+        self.down_values.get(symbol).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the definitions of the form `symbol[...][...] := ...`, in the order they are
+    /// tried.
+    pub fn sub_values(&self, symbol: &Symbol) -> &[Definition] {
+        self.sub_values.get(symbol).map_or(&[], Vec::as_slice)
+    }
+
+    /// Construct the [`DependencyGraph`] of the DownValues of this evaluator.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::from_down_values(
+            self.down_values
+                .iter()
+                .map(|(symbol, definitions)| (symbol, definitions.as_slice())),
+        )
+    }
+
+    fn define(&mut self, lhs: Expr, rhs: Expr) -> Result<(), EvalError> {
+        if has_condition(&lhs) {
+            return Err(EvalError::UnsupportedPattern(lhs));
+        }
+
+        let target = definition_target(&lhs);
+
+        if let Some(tag) = target.tag() {
+            if self.attributes.get(&tag).contains(Attributes::PROTECTED) {
+                return Err(EvalError::Protected(tag));
+            }
+        }
+
+        let table = match target.kind() {
+            ExprKind::Symbol(symbol) => {
+                self.own_values.insert(symbol.clone(), rhs);
+                return Ok(());
+            },
+            ExprKind::Normal(normal) => match normal.head().kind() {
+                ExprKind::Symbol(_) => &mut self.down_values,
+                _ => &mut self.sub_values,
+            },
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => {
+                return Err(EvalError::InvalidDefinition(lhs))
+            },
+        };
+
+        let tag = match target.tag() {
+            Some(tag) => tag,
+            None => return Err(EvalError::InvalidDefinition(lhs)),
+        };
+
+        let definitions = table.entry(tag).or_default();
+
+        if let Some(existing) = definitions.iter_mut().find(|def| def.lhs == lhs) {
+            existing.rhs = rhs;
+            return Ok(());
+        }
+
+        let index = if is_pattern(&lhs) {
+            definitions.len()
+        } else {
+            definitions
+                .iter()
+                .position(|def| is_pattern(&def.lhs))
+                .unwrap_or(definitions.len())
+        };
+
+        definitions.insert(index, Definition { lhs, rhs });
+
+        Ok(())
+    }
+
+    //==================================
+    // Evaluation
+    //==================================
+
+    /// Evaluate `expr` until it no longer changes.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Expr, EvalError> {
+        self.evaluate_at(expr, 0)
+    }
+
+    fn evaluate_at(&mut self, expr: &Expr, depth: usize) -> Result<Expr, EvalError> {
+        if depth > self.recursion_limit {
+            return Err(EvalError::RecursionLimitExceeded(self.recursion_limit));
+        }
+
+        let mut expr = expr.clone();
+        let mut iterations = 0;
+
+        while let Some(next) = self.step(&expr, depth)? {
+            if next == expr {
+                break;
+            }
+
+            iterations += 1;
+            if iterations > self.iteration_limit {
+                return Err(EvalError::IterationLimitExceeded(self.iteration_limit));
+            }
+
+            expr = next;
+        }
+
+        Ok(expr)
+    }
+
+    /// Perform one step of evaluation, returning `None` if `expr` evaluates to itself.
+    fn step(&mut self, expr: &Expr, depth: usize) -> Result<Option<Expr>, EvalError> {
+        let normal = match expr.kind() {
+            ExprKind::Symbol(symbol) => return Ok(self.own_values.get(symbol).cloned()),
+            ExprKind::Normal(normal) => normal,
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => {
+                return Ok(None)
+            },
+        };
+
+        let head = self.evaluate_at(normal.head(), depth + 1)?;
+        let mut changed = head != *normal.head();
+
+        let attributes = match head.kind() {
+            ExprKind::Symbol(symbol) => self.attributes.get(symbol),
+            _ => Attributes::empty(),
+        };
+
+        let splice_sequences = !attributes
+            .intersects(Attributes::SEQUENCE_HOLD | Attributes::HOLD_ALL_COMPLETE);
+
+        let mut elements = Vec::with_capacity(normal.elements().len());
+
+        for (index, element) in normal.elements().iter().enumerate() {
+            let value = if attributes.holds(index) {
+                element.clone()
+            } else {
+                self.evaluate_at(element, depth + 1)?
+            };
+            changed |= value != *element;
+
+            // Splice the elements of Sequence[...] into the enclosing expression.
+            match value.try_as_normal() {
+                Some(sequence)
+                    if splice_sequences
+                        && head_name(sequence) == Some("System`Sequence") =>
+                {
+                    changed = true;
+                    elements.extend_from_slice(sequence.elements());
+                },
+                _ => elements.push(value),
+            }
+        }
+
+        let mut expr = if changed {
+            Expr::normal(head, elements)
+        } else {
+            expr.clone()
+        };
+
+        if attributes.contains(Attributes::FLAT) {
+            let flat = attributes::flatten(&expr);
+            changed |= flat != expr;
+            expr = flat;
+        }
+
+        if attributes.contains(Attributes::LISTABLE) {
+            if let Some(threaded) = attributes::thread_listable(&expr) {
+                return Ok(Some(threaded));
+            }
+        }
+
+        if attributes.contains(Attributes::ORDERLESS) {
+            let sorted = attributes::sort_orderless(&expr);
+            changed |= sorted != expr;
+            expr = sorted;
+        }
+
+        let normal = expr.try_as_normal().unwrap();
+
+        if let ExprKind::Symbol(symbol) = normal.head().kind() {
+            if let Some(result) = self.builtin(symbol, normal.elements(), depth)? {
+                return Ok(Some(result));
+            }
+        }
+
+        if let Some(result) = self.apply_definitions(&expr, depth)? {
+            return Ok(Some(result));
+        }
+
+        Ok(if changed { Some(expr) } else { None })
+    }
+
+    /// Apply the first DownValue or SubValue definition that matches `expr`.
+    fn apply_definitions(
+        &self,
+        expr: &Expr,
+        depth: usize,
+    ) -> Result<Option<Expr>, EvalError> {
+        let normal = match expr.try_as_normal() {
+            Some(normal) => normal,
+            None => return Ok(None),
+        };
+
+        let definitions = match normal.head().kind() {
+            ExprKind::Symbol(symbol) => self.down_values.get(symbol),
+            ExprKind::Normal(_) => expr.tag().and_then(|tag| self.sub_values.get(&tag)),
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => None,
+        };
+
+        for def in definitions.map_or(&[][..], Vec::as_slice) {
+            let matched = match_pattern(&def.lhs, expr, depth + 1, self.recursion_limit)?;
+
+            if let Some(bindings) = matched {
+                return Ok(Some(scope::substitute(&def.rhs, &bindings)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Evaluate the built-in functions that make definitions or control the order of
+    /// evaluation. Which elements have already been evaluated is determined by the
+    /// attributes of `head`.
+    fn builtin(
+        &mut self,
+        head: &Symbol,
+        elements: &[Expr],
+        depth: usize,
+    ) -> Result<Option<Expr>, EvalError> {
+        let result = match (head.as_str(), elements) {
+            ("System`Set", [lhs, rhs]) => {
+                self.define(lhs.clone(), rhs.clone())?;
+                rhs.clone()
+            },
+            ("System`SetDelayed", [lhs, rhs]) => {
+                self.define(lhs.clone(), rhs.clone())?;
+                Expr::symbol(crate::symbol!("System`Null"))
+            },
+            ("System`CompoundExpression", elements) => {
+                let mut result = Expr::symbol(crate::symbol!("System`Null"));
+
+                for element in elements {
+                    result = self.evaluate_at(element, depth + 1)?;
+                }
+
+                result
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(result))
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+/// Returns the expression that the definition `lhs = rhs` is made for, with any
+/// `HoldPattern` wrappers removed, like `f[x_]` for `HoldPattern[f[x_]]`.
+fn definition_target(lhs: &Expr) -> &Expr {
+    let mut target = lhs;
+
+    while let Some(normal) = target.try_as_normal() {
+        target = match (head_name(normal), normal.elements()) {
+            (Some("System`HoldPattern"), [inner]) => inner,
+            _ => break,
+        };
+    }
+
+    target
+}
+
+//======================================
+// Formatting
+//======================================
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::IterationLimitExceeded(limit) => {
+                write!(f, "iteration limit of {} exceeded", limit)
+            },
+            EvalError::RecursionLimitExceeded(limit) => {
+                write!(f, "recursion depth of {} exceeded", limit)
+            },
+            EvalError::InvalidDefinition(lhs) => {
+                write!(f, "cannot make a definition for {}", lhs)
+            },
+            EvalError::Protected(symbol) => {
+                write!(f, "symbol {} is protected", symbol)
+            },
+            EvalError::UnsupportedPattern(lhs) => write!(
+                f,
+                "cannot make a definition for {} containing a condition or pattern test",
+                lhs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
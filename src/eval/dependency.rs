@@ -0,0 +1,213 @@
+//! Dependency graph of DownValues definitions.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{scope, Symbol};
+
+use super::Definition;
+
+
+/// Graph of the dependencies between symbols that have DownValues.
+///
+/// A symbol `f` depends on a symbol `g` if `g` occurs free in the right-hand side of
+/// one of the DownValues of `f`, as determined by
+/// [`scope::free_symbols()`][crate::scope::free_symbols]. The pattern names in the
+/// left-hand side of a definition are bound in its right-hand side, so `x` is not a
+/// dependency of `f[x_] := g[x]`. Only symbols that have DownValues in the graph are
+/// included as dependencies.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{eval::Evaluator, wl, Symbol};
+///
+/// let mut evaluator = Evaluator::new();
+///
+/// // f[x_] := g[x] + h[x]
+/// evaluator.set_delayed(
+///     wl!(Global::f[Pattern[Global::x, Blank[]]]),
+///     wl!(Plus[Global::g[Global::x], Global::h[Global::x]]),
+/// )?;
+/// // g[x_] := 2 x
+/// evaluator.set_delayed(
+///     wl!(Global::g[Pattern[Global::x, Blank[]]]),
+///     wl!(Times[2, Global::x]),
+/// )?;
+///
+/// let graph = evaluator.dependency_graph();
+///
+/// let f = Symbol::new("Global`f");
+/// let g = Symbol::new("Global`g");
+///
+/// // h has no definitions, so it is not included.
+/// assert_eq!(graph.dependencies(&f), &[g.clone()]);
+/// assert_eq!(graph.topological_order(), Some(vec![g, f]));
+/// # Ok::<(), wolfram_expr::eval::EvalError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DependencyGraph {
+    dependencies: BTreeMap<Symbol, Vec<Symbol>>,
+}
+
+impl DependencyGraph {
+    /// Construct the dependency graph of the given DownValues definitions, grouped by
+    /// the symbol they are associated with.
+    pub fn from_down_values<'a, I>(down_values: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a Symbol, &'a [Definition])>,
+    {
+        let down_values: BTreeMap<&Symbol, &[Definition]> =
+            down_values.into_iter().collect();
+
+        let dependencies = down_values
+            .iter()
+            .map(|(symbol, definitions)| {
+                let mut dependencies: Vec<Symbol> = Vec::new();
+
+                for definition in definitions.iter() {
+                    let names = scope::pattern_names(&definition.lhs);
+
+                    for dependency in scope::free_symbols(&definition.rhs) {
+                        if down_values.contains_key(&dependency)
+                            && !names.contains(&dependency)
+                            && !dependencies.contains(&dependency)
+                        {
+                            dependencies.push(dependency);
+                        }
+                    }
+                }
+
+                (Symbol::clone(symbol), dependencies)
+            })
+            .collect();
+
+        DependencyGraph { dependencies }
+    }
+
+    /// Symbols in the graph, in sorted order.
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.dependencies.keys()
+    }
+
+    /// Symbols that `symbol` depends on directly, in the order they first appear in its
+    /// definitions.
+    pub fn dependencies(&self, symbol: &Symbol) -> &[Symbol] {
+        self.dependencies.get(symbol).map_or(&[], Vec::as_slice)
+    }
+
+    /// Symbols that depend directly on `symbol`, in sorted order.
+    pub fn dependents(&self, symbol: &Symbol) -> Vec<Symbol> {
+        self.dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.contains(symbol))
+            .map(|(dependent, _)| dependent.clone())
+            .collect()
+    }
+
+    /// Symbols that `symbol` depends on directly or indirectly.
+    ///
+    /// `symbol` itself is included if it is recursive.
+    pub fn transitive_dependencies(&self, symbol: &Symbol) -> Vec<Symbol> {
+        let mut dependencies = Vec::new();
+
+        self.walk_dependencies(symbol, |dependency| {
+            dependencies.push(dependency.clone());
+            true
+        });
+
+        dependencies
+    }
+
+    /// Returns `true` if `symbol` depends on itself, directly or indirectly.
+    pub fn is_recursive(&self, symbol: &Symbol) -> bool {
+        let mut recursive = false;
+
+        self.walk_dependencies(symbol, |dependency| {
+            recursive = dependency == symbol;
+            !recursive
+        });
+
+        recursive
+    }
+
+    /// Call `found` once for each symbol that `symbol` depends on directly or
+    /// indirectly, in depth-first order. The walk stops early if `found` returns
+    /// `false`.
+    fn walk_dependencies<F>(&self, symbol: &Symbol, mut found: F)
+    where
+        F: FnMut(&Symbol) -> bool,
+    {
+        let mut visited: HashSet<&Symbol> = HashSet::new();
+        let mut stack: Vec<&Symbol> = self.dependencies(symbol).iter().rev().collect();
+
+        while let Some(dependency) = stack.pop() {
+            if !visited.insert(dependency) {
+                continue;
+            }
+
+            if !found(dependency) {
+                return;
+            }
+
+            stack.extend(self.dependencies(dependency).iter().rev());
+        }
+    }
+
+    /// Order the symbols so that each symbol comes after the symbols it depends on.
+    ///
+    /// Symbols that depend directly on themselves are allowed. Returns `None` if two or
+    /// more symbols depend on each other.
+    pub fn topological_order(&self) -> Option<Vec<Symbol>> {
+        #[derive(Copy, Clone, PartialEq)]
+        enum State {
+            Visiting,
+            Done,
+        }
+
+        let mut states: HashMap<&Symbol, State> = HashMap::new();
+        let mut order = Vec::with_capacity(self.dependencies.len());
+
+        // Symbols being visited, with the index of the next dependency to visit.
+        let mut stack: Vec<(&Symbol, usize)> = Vec::new();
+
+        for root in self.symbols() {
+            if states.contains_key(root) {
+                continue;
+            }
+
+            states.insert(root, State::Visiting);
+            stack.push((root, 0));
+
+            while let Some((symbol, index)) = stack.last_mut() {
+                let symbol = *symbol;
+
+                let dependency = match self.dependencies(symbol).get(*index) {
+                    Some(dependency) => dependency,
+                    None => {
+                        states.insert(symbol, State::Done);
+                        order.push(symbol.clone());
+                        stack.pop();
+                        continue;
+                    },
+                };
+
+                *index += 1;
+
+                if dependency == symbol {
+                    continue;
+                }
+
+                match states.get(dependency) {
+                    Some(State::Done) => (),
+                    Some(State::Visiting) => return None,
+                    None => {
+                        states.insert(dependency, State::Visiting);
+                        stack.push((dependency, 0));
+                    },
+                }
+            }
+        }
+
+        Some(order)
+    }
+}
@@ -0,0 +1,306 @@
+//! Matching of expressions against patterns.
+
+use crate::{Expr, ExprKind, Normal, Symbol};
+
+use super::EvalError;
+
+
+/// Values bound to named patterns, in the order they were bound.
+pub(crate) type Bindings = Vec<(Symbol, Expr)>;
+
+/// Number of elements matched by a blank pattern.
+#[derive(Copy, Clone)]
+enum Arity {
+    /// `Blank[]`: exactly one element.
+    One,
+    /// `BlankSequence[]`: one or more elements.
+    Sequence,
+    /// `BlankNullSequence[]`: zero or more elements.
+    NullSequence,
+}
+
+/// Returns `true` if `expr` contains a `Blank`, `BlankSequence`, `BlankNullSequence` or
+/// `Pattern` subexpression.
+pub(crate) fn is_pattern(expr: &Expr) -> bool {
+    let mut stack = vec![expr];
+
+    while let Some(expr) = stack.pop() {
+        if let ExprKind::Normal(normal) = expr.kind() {
+            if let Some(
+                "System`Blank"
+                | "System`BlankSequence"
+                | "System`BlankNullSequence"
+                | "System`Pattern",
+            ) = head_name(normal)
+            {
+                return true;
+            }
+
+            stack.push(normal.head());
+            stack.extend(normal.elements());
+        }
+    }
+
+    false
+}
+
+/// Attempt to match `expr` against `pattern`.
+///
+/// Returns the values bound to the named patterns in `pattern` if the match succeeds.
+/// Values bound to sequence patterns are wrapped in `Sequence[...]`.
+///
+/// `depth` is the nesting depth of evaluation at which the match is performed. Each
+/// level of the pattern that is matched counts as one more level of nesting, and
+/// nesting more deeply than `recursion_limit` returns an error.
+pub(crate) fn match_pattern(
+    pattern: &Expr,
+    expr: &Expr,
+    depth: usize,
+    recursion_limit: usize,
+) -> Result<Option<Bindings>, EvalError> {
+    let mut matcher = Matcher {
+        bindings: Vec::new(),
+        recursion_limit,
+    };
+
+    if matcher.matches(pattern, expr, depth)? {
+        Ok(Some(matcher.bindings))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns `true` if `expr` contains a `Condition` or `PatternTest` subexpression.
+///
+/// Conditions and pattern tests are not evaluated, so patterns that contain them can
+/// never match.
+pub(crate) fn has_condition(expr: &Expr) -> bool {
+    let mut stack = vec![expr];
+
+    while let Some(expr) = stack.pop() {
+        if let ExprKind::Normal(normal) = expr.kind() {
+            if let Some("System`Condition" | "System`PatternTest") = head_name(normal) {
+                return true;
+            }
+
+            stack.push(normal.head());
+            stack.extend(normal.elements());
+        }
+    }
+
+    false
+}
+
+//======================================
+// Matching
+//======================================
+
+/// State of an in-progress match.
+struct Matcher {
+    bindings: Bindings,
+    recursion_limit: usize,
+}
+
+impl Matcher {
+    /// Match a single expression. On failure, `bindings` may contain partial bindings
+    /// that the caller must discard.
+    fn matches(
+        &mut self,
+        pattern: &Expr,
+        expr: &Expr,
+        depth: usize,
+    ) -> Result<bool, EvalError> {
+        if depth > self.recursion_limit {
+            return Err(EvalError::RecursionLimitExceeded(self.recursion_limit));
+        }
+
+        let normal = match pattern.kind() {
+            ExprKind::Normal(normal) => normal,
+            _ => return Ok(pattern == expr),
+        };
+
+        let matched = match (head_name(normal), normal.elements()) {
+            (Some("System`Pattern"), [name, inner]) => match name.try_as_symbol() {
+                Some(name) => {
+                    self.matches(inner, expr, depth + 1)? && self.bind(name, expr)
+                },
+                None => false,
+            },
+            (Some("System`HoldPattern"), [inner]) => {
+                self.matches(inner, expr, depth + 1)?
+            },
+            (Some("System`Alternatives"), alternatives) => {
+                let mark = self.bindings.len();
+
+                for alternative in alternatives {
+                    self.bindings.truncate(mark);
+
+                    if self.matches(alternative, expr, depth + 1)? {
+                        return Ok(true);
+                    }
+                }
+
+                false
+            },
+            _ => match blank(normal) {
+                // A sequence pattern outside of a sequence of elements matches a single
+                // expression.
+                Some((_, Some(head))) => has_head(expr, head),
+                Some((_, None)) => true,
+                None => match expr.kind() {
+                    ExprKind::Normal(expr) => {
+                        self.matches(normal.head(), expr.head(), depth + 1)?
+                            && self.match_sequence(
+                                normal.elements(),
+                                expr.elements(),
+                                depth + 1,
+                            )?
+                    },
+                    _ => false,
+                },
+            },
+        };
+
+        Ok(matched)
+    }
+
+    /// Match a sequence of elements against a sequence of element patterns, trying
+    /// every way of dividing the elements between sequence patterns.
+    ///
+    /// Patterns that match a single element are matched in a loop, so only sequence
+    /// patterns, which may need to backtrack, increase the nesting depth.
+    fn match_sequence(
+        &mut self,
+        mut patterns: &[Expr],
+        mut exprs: &[Expr],
+        depth: usize,
+    ) -> Result<bool, EvalError> {
+        let mark = self.bindings.len();
+
+        while let Some((pattern, rest)) = patterns.split_first() {
+            if let Some((name, min_len, head)) = sequence_pattern(pattern) {
+                let sequence_mark = self.bindings.len();
+
+                for len in min_len..=exprs.len() {
+                    let (taken, remaining) = exprs.split_at(len);
+
+                    // If the last element taken does not have the required head, no
+                    // longer sequence can match either.
+                    if let (Some(head), Some(last)) = (head, taken.last()) {
+                        if !has_head(last, head) {
+                            break;
+                        }
+                    }
+
+                    let bound = match name {
+                        Some(name) => {
+                            let sequence = Expr::normal(
+                                crate::symbol!("System`Sequence"),
+                                taken.to_vec(),
+                            );
+                            self.bind(name, &sequence)
+                        },
+                        None => true,
+                    };
+
+                    if bound && self.match_sequence(rest, remaining, depth + 1)? {
+                        return Ok(true);
+                    }
+
+                    self.bindings.truncate(sequence_mark);
+                }
+
+                self.bindings.truncate(mark);
+                return Ok(false);
+            }
+
+            let matched = match exprs.split_first() {
+                Some((expr, remaining)) => {
+                    exprs = remaining;
+                    self.matches(pattern, expr, depth)?
+                },
+                None => false,
+            };
+
+            if !matched {
+                self.bindings.truncate(mark);
+                return Ok(false);
+            }
+
+            patterns = rest;
+        }
+
+        Ok(exprs.is_empty())
+    }
+
+    /// Bind `name` to `value`, or check that `value` is equal to the value `name` is
+    /// already bound to.
+    fn bind(&mut self, name: &Symbol, value: &Expr) -> bool {
+        match self.bindings.iter().find(|(bound, _)| bound == name) {
+            Some((_, bound)) => bound == value,
+            None => {
+                self.bindings.push((name.clone(), value.clone()));
+                true
+            },
+        }
+    }
+}
+
+//======================================
+// Helpers
+//======================================
+
+/// If `normal` is `Blank[]`, `BlankSequence[]` or `BlankNullSequence[]`, return its
+/// arity and optional head constraint.
+fn blank(normal: &Normal) -> Option<(Arity, Option<&Expr>)> {
+    let arity = match head_name(normal)? {
+        "System`Blank" => Arity::One,
+        "System`BlankSequence" => Arity::Sequence,
+        "System`BlankNullSequence" => Arity::NullSequence,
+        _ => return None,
+    };
+
+    match normal.elements() {
+        [] => Some((arity, None)),
+        [head] => Some((arity, Some(head))),
+        _ => None,
+    }
+}
+
+/// If `pattern` matches a sequence of elements, return the name it binds, the minimum
+/// number of elements it matches, and its optional head constraint.
+fn sequence_pattern(pattern: &Expr) -> Option<(Option<&Symbol>, usize, Option<&Expr>)> {
+    let normal = pattern.try_as_normal()?;
+
+    let (name, normal) = match (head_name(normal), normal.elements()) {
+        (Some("System`Pattern"), [name, inner]) => {
+            (Some(name.try_as_symbol()?), inner.try_as_normal()?)
+        },
+        _ => (None, normal),
+    };
+
+    match blank(normal)? {
+        (Arity::One, _) => None,
+        (Arity::Sequence, head) => Some((name, 1, head)),
+        (Arity::NullSequence, head) => Some((name, 0, head)),
+    }
+}
+
+/// Returns `true` if the head of `expr` is `head`. The heads of atomic expressions are
+/// `Integer`, `Real`, `String` and `Symbol`.
+fn has_head(expr: &Expr, head: &Expr) -> bool {
+    let atom_head = match expr.kind() {
+        ExprKind::Normal(normal) => return normal.head() == head,
+        ExprKind::Integer(_) => "System`Integer",
+        ExprKind::Real(_) => "System`Real",
+        ExprKind::String(_) => "System`String",
+        ExprKind::Symbol(_) => "System`Symbol",
+    };
+
+    head.try_as_symbol().map(Symbol::as_str) == Some(atom_head)
+}
+
+/// If the head of `normal` is a symbol, return its name.
+pub(crate) fn head_name(normal: &Normal) -> Option<&str> {
+    normal.head().try_as_symbol().map(Symbol::as_str)
+}
@@ -0,0 +1,350 @@
+//! Application of pure functions.
+//!
+//! [`apply_function()`] applies a
+//! [`Function`](https://reference.wolfram.com/language/ref/Function.html) <sub>WL</sub>
+//! expression to a list of arguments, by substituting the arguments into the body of
+//! the function. The result is not evaluated.
+//!
+//! Both forms of `Function` are supported:
+//!
+//! Form                         | Parameters
+//! -----------------------------|----------------------------------------------------
+//! `Function[body]`             | `#`, `#n`, `##`, `##n` and `#name` slots in `body`
+//! `Function[{x, y, ...}, body]`| The symbols `x`, `y`, ...
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{function::apply_function, wl};
+//!
+//! // f[#2, #1]&
+//! let function = wl!(Function[Global::f[Slot[2], Slot[1]]]);
+//!
+//! assert_eq!(
+//!     apply_function(&function, &[wl!(Global::a), wl!(Global::b)])?,
+//!     wl!(Global::f[Global::b, Global::a])
+//! );
+//! # Ok::<(), wolfram_expr::function::FunctionError>(())
+//! ```
+
+use std::{convert::TryFrom, fmt};
+
+use crate::{scope::substitute, Expr, ExprKind, Normal, Symbol};
+
+
+/// Error returned when a function cannot be applied to its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionError {
+    /// The expression is not a `Function` with a valid list of parameters.
+    InvalidFunction(Expr),
+    /// A function with named parameters was applied to fewer arguments than it has
+    /// parameters.
+    TooFewArguments {
+        /// Number of parameters of the function.
+        parameters: usize,
+        /// Number of arguments the function was applied to.
+        arguments: usize,
+    },
+    /// A `Slot` or `SlotSequence` expression refers to an argument that was not given,
+    /// like `#3` in a function applied to two arguments.
+    SlotOutOfRange(Expr),
+    /// A `Slot` or `SlotSequence` expression has an invalid argument, like `Slot[-1]`.
+    InvalidSlot(Expr),
+    /// A named slot, like `#name`, was used, but the first argument is not an
+    /// `Association`.
+    NotAnAssociation(Expr),
+}
+
+/// Apply `function` to `args`, like `function[args]`.
+///
+/// In a `Function[body]`:
+///
+/// * `#n` (`Slot[n]`) is replaced by the `n`-th argument, and `#0` by the function
+///   itself.
+/// * `##n` (`SlotSequence[n]`) is replaced by the arguments from the `n`-th onward. If
+///   it is an element of a normal expression, the arguments are spliced into that
+///   expression. Otherwise it is replaced by `Sequence[...]`.
+/// * `#name` (`Slot["name"]`) is replaced by the value of the key `"name"` in the first
+///   argument, which must be an `Association`. If the key is not present, it is
+///   replaced by `Missing["KeyAbsent", "name"]`.
+///
+/// Slots inside a nested `Function[body]` belong to that function, and are not
+/// replaced. Slots inside a nested function with named parameters are replaced.
+///
+/// In a `Function[{x, y, ...}, body]`, each free occurrence of a parameter in `body`
+/// is replaced by the corresponding argument, using
+/// [`scope::substitute()`][crate::scope::substitute]. Any extra arguments are ignored.
+/// A nested scoping construct, like a function, that binds a symbol with the same name
+/// shadows the outer parameter. A symbol bound by a nested construct that would
+/// capture a symbol in one of the arguments is renamed, like `x` to `x$`, so that
+/// `Function[{x}, Function[{y}, x + y]][y]` becomes `Function[{y$}, y + y$]`.
+pub fn apply_function(function: &Expr, args: &[Expr]) -> Result<Expr, FunctionError> {
+    let invalid = || FunctionError::InvalidFunction(function.clone());
+
+    let normal = match function.try_as_normal() {
+        Some(normal) if normal.has_head(&crate::symbol!("System`Function")) => normal,
+        _ => return Err(invalid()),
+    };
+
+    if let Some(body) = slot_function_body(normal) {
+        return replace_slots(body, |slot, expr| slot_value(slot, expr, function, args));
+    }
+
+    let (parameters, body) = match normal.elements() {
+        [parameters, body] | [parameters, body, _] => {
+            (parameter_list(parameters).ok_or_else(invalid)?, body)
+        },
+        _ => return Err(invalid()),
+    };
+
+    if args.len() < parameters.len() {
+        return Err(FunctionError::TooFewArguments {
+            parameters: parameters.len(),
+            arguments: args.len(),
+        });
+    }
+
+    let bindings: Vec<(Symbol, Expr)> =
+        parameters.into_iter().zip(args.iter().cloned()).collect();
+
+    Ok(substitute(body, &bindings))
+}
+
+//======================================
+// Slots
+//======================================
+
+/// Value of a `Slot` or `SlotSequence` expression.
+pub(crate) enum SlotValue {
+    Single(Expr),
+    /// Values that are spliced into the enclosing normal expression, or wrapped in
+    /// `Sequence[...]` if the slot is not an element.
+    Sequence(Vec<Expr>),
+}
+
+impl SlotValue {
+    /// Returns this value as an expression that is not spliced into its parent.
+    fn into_expr(self) -> Expr {
+        match self {
+            SlotValue::Single(value) => value,
+            SlotValue::Sequence(values) => {
+                Expr::normal(crate::symbol!("System`Sequence"), values)
+            },
+        }
+    }
+}
+
+/// Replace each `Slot` and `SlotSequence` subexpression of `body`, the body of a
+/// `Function[body]`, with the value returned by `slot_value`.
+///
+/// Slots inside a nested `Function[body]` belong to that function, and are not
+/// replaced.
+///
+/// Subexpressions are visited without recursion, so this can be used on arbitrarily
+/// deep expressions without overflowing the stack.
+pub(crate) fn replace_slots<E>(
+    body: &Expr,
+    mut slot_value: impl FnMut(&Normal, &Expr) -> Result<SlotValue, E>,
+) -> Result<Expr, E> {
+    // Values of completed subexpressions that have not been consumed by their parent.
+    let mut values: Vec<SlotValue> = Vec::new();
+
+    let mut stack: Vec<(&Expr, bool)> = vec![(body, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        let normal = match expr.try_as_normal() {
+            Some(normal) => normal,
+            None => {
+                values.push(SlotValue::Single(expr.clone()));
+                continue;
+            },
+        };
+
+        if children_visited {
+            let elements = values.split_off(values.len() - normal.elements().len());
+            let head = values.pop().expect("replace_slots: head was not visited");
+
+            let mut spliced = Vec::with_capacity(elements.len());
+            for element in elements {
+                match element {
+                    SlotValue::Single(value) => spliced.push(value),
+                    SlotValue::Sequence(values) => spliced.extend(values),
+                }
+            }
+
+            values.push(SlotValue::Single(Expr::normal(head.into_expr(), spliced)));
+            continue;
+        }
+
+        if normal.has_head(&crate::symbol!("System`Slot"))
+            || normal.has_head(&crate::symbol!("System`SlotSequence"))
+        {
+            values.push(slot_value(normal, expr)?);
+            continue;
+        }
+
+        // Slots in a nested `Function[body]` belong to that function.
+        if normal.has_head(&crate::symbol!("System`Function"))
+            && slot_function_body(normal).is_some()
+        {
+            values.push(SlotValue::Single(expr.clone()));
+            continue;
+        }
+
+        stack.push((expr, true));
+
+        for element in normal.elements().iter().rev() {
+            stack.push((element, false));
+        }
+        stack.push((normal.head(), false));
+    }
+
+    debug_assert!(values.len() == 1);
+
+    let value = values
+        .pop()
+        .expect("replace_slots: no value computed for body");
+
+    Ok(value.into_expr())
+}
+
+/// Returns the value of `expr`, a `Slot` or `SlotSequence` expression, in
+/// `function` applied to `args`.
+fn slot_value(
+    normal: &Normal,
+    expr: &Expr,
+    function: &Expr,
+    args: &[Expr],
+) -> Result<SlotValue, FunctionError> {
+    let is_sequence = normal.has_head(&crate::symbol!("System`SlotSequence"));
+
+    let out_of_range = || FunctionError::SlotOutOfRange(expr.clone());
+
+    let value = match (normal.elements(), is_sequence) {
+        // #0 is the function itself.
+        ([index], false) if *index == Expr::from(0) => {
+            SlotValue::Single(function.clone())
+        },
+        ([index], _) => match index.kind() {
+            ExprKind::Integer(n) if *n >= 1 => {
+                let n = usize::try_from(*n).map_err(|_| out_of_range())?;
+
+                if is_sequence {
+                    match args.get(n - 1..) {
+                        Some(values) => SlotValue::Sequence(values.to_vec()),
+                        None => return Err(out_of_range()),
+                    }
+                } else {
+                    match args.get(n - 1) {
+                        Some(value) => SlotValue::Single(value.clone()),
+                        None => return Err(out_of_range()),
+                    }
+                }
+            },
+            ExprKind::String(name) if !is_sequence => {
+                let association = args.first().ok_or_else(out_of_range)?;
+                SlotValue::Single(association_lookup(association, name)?)
+            },
+            _ => return Err(FunctionError::InvalidSlot(expr.clone())),
+        },
+        _ => return Err(FunctionError::InvalidSlot(expr.clone())),
+    };
+
+    Ok(value)
+}
+
+/// Look up the value of the string key `name` in `association`.
+fn association_lookup(association: &Expr, name: &str) -> Result<Expr, FunctionError> {
+    let normal = match association.try_as_normal() {
+        Some(normal) if normal.has_head(&crate::symbol!("System`Association")) => normal,
+        _ => return Err(FunctionError::NotAnAssociation(association.clone())),
+    };
+
+    let key = Expr::string(name);
+
+    for rule in normal.elements() {
+        let rule = match rule.try_as_normal() {
+            Some(rule)
+                if rule.has_head(&crate::symbol!("System`Rule"))
+                    || rule.has_head(&crate::symbol!("System`RuleDelayed")) =>
+            {
+                rule
+            },
+            _ => return Err(FunctionError::NotAnAssociation(association.clone())),
+        };
+
+        match rule.elements() {
+            [lhs, rhs] if *lhs == key => return Ok(rhs.clone()),
+            [_, _] => (),
+            _ => return Err(FunctionError::NotAnAssociation(association.clone())),
+        }
+    }
+
+    Ok(Expr::normal(crate::symbol!("System`Missing"), vec![
+        Expr::string("KeyAbsent"),
+        key,
+    ]))
+}
+
+/// If `function` is a `Function[body]` or `Function[Null, body, attributes]`, whose
+/// parameters are slots, return its body.
+pub(crate) fn slot_function_body(function: &Normal) -> Option<&Expr> {
+    match function.elements() {
+        [body] => Some(body),
+        [parameters, body] | [parameters, body, _] if *parameters == Expr::null() => {
+            Some(body)
+        },
+        _ => None,
+    }
+}
+
+//======================================
+// Named parameters
+//======================================
+
+/// Returns the parameters of `Function[parameters, body]`, which are either a single
+/// symbol or a list of symbols.
+pub(crate) fn parameter_list(parameters: &Expr) -> Option<Vec<Symbol>> {
+    match parameters.kind() {
+        ExprKind::Symbol(symbol) => Some(vec![symbol.clone()]),
+        ExprKind::Normal(list) if list.has_head(&crate::symbol!("System`List")) => list
+            .elements()
+            .iter()
+            .map(|parameter| parameter.try_as_symbol().cloned())
+            .collect(),
+        _ => None,
+    }
+}
+
+//======================================
+// Formatting
+//======================================
+
+impl fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FunctionError::InvalidFunction(function) => {
+                write!(f, "invalid function: {}", function)
+            },
+            FunctionError::TooFewArguments {
+                parameters,
+                arguments,
+            } => write!(
+                f,
+                "function with {} parameters applied to {} arguments",
+                parameters, arguments
+            ),
+            FunctionError::SlotOutOfRange(slot) => {
+                write!(f, "{} refers to an argument that was not given", slot)
+            },
+            FunctionError::InvalidSlot(slot) => write!(f, "invalid slot: {}", slot),
+            FunctionError::NotAnAssociation(expr) => write!(
+                f,
+                "named slot used with {}, which is not an association",
+                expr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FunctionError {}
@@ -0,0 +1,314 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    metrics::allocation_size, ptr_cmp::ExprRefCmp, Expr, ExprKind, Normal, Number,
+    SmallString, Symbol,
+};
+
+
+/// Table of interned expressions, used to make structurally equal subexpressions share a
+/// single allocation.
+///
+/// Expressions that are built up independently (for example, by decoding an expression
+/// sent by the Wolfram Kernel) will often contain many subexpressions that are equal,
+/// but that are stored in separate reference-counted allocations. Passing such an
+/// expression through [`HashCons::intern()`] will return an equal expression in which
+/// every set of structurally equal subexpressions is represented by a single
+/// allocation.
+///
+/// Expressions can also be constructed directly through the table using
+/// [`HashCons::normal()`], [`HashCons::symbol()`], [`HashCons::string()`] and
+/// [`HashCons::number()`], in which case nodes are interned as they are built.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{Expr, HashCons, Symbol};
+///
+/// let g_x = || Expr::normal(Symbol::new("Global`g"), vec![Expr::from("x")]);
+///
+/// // f[g["x"], g["x"]], where each g["x"] is a separate allocation.
+/// let expr = Expr::normal(Symbol::new("Global`f"), vec![g_x(), g_x()]);
+///
+/// let mut table = HashCons::new();
+/// let shared = table.intern(&expr);
+///
+/// assert_eq!(shared, expr);
+/// assert_eq!(table.stats().shared_nodes, 3);
+///
+/// // Nodes constructed via the table are interned as they are built.
+/// let g = table.symbol(Symbol::new("Global`g"));
+/// let x = table.string("x");
+/// let another = table.normal(g, vec![x]);
+///
+/// assert_eq!(another, g_x());
+/// assert!(std::ptr::eq(another.kind(), shared.normal_part(0).unwrap().kind()));
+/// ```
+#[derive(Debug, Default)]
+pub struct HashCons {
+    nodes: HashMap<NodeKey, Expr>,
+    stats: HashConsStats,
+}
+
+/// Statistics describing the sharing performed by a [`HashCons`] table.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct HashConsStats {
+    /// Number of distinct nodes stored in the table.
+    pub unique_nodes: usize,
+    /// Number of nodes that were replaced by a structurally equal node already stored
+    /// in the table.
+    pub shared_nodes: usize,
+    /// Approximate number of bytes of memory that are no longer needed because nodes
+    /// were replaced by shared nodes.
+    ///
+    /// This assumes the original, unshared expressions are dropped after being
+    /// interned.
+    pub bytes_saved: usize,
+}
+
+/// Shallow identity of an interned node.
+///
+/// The children of a [`Normal`] node are always interned before the node itself, so
+/// two normal nodes are structurally equal iff their head and elements are the *same*
+/// interned allocations. This means they can be compared by reference, without
+/// walking the entire subexpression.
+///
+/// Reals are keyed by their bit pattern instead of by value, because `0.0` and `-0.0`
+/// compare equal but are distinct expressions.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum NodeKey {
+    Atom(Expr),
+    Real(u64),
+    Normal(ExprRefCmp, Vec<ExprRefCmp>),
+}
+
+impl HashCons {
+    /// Construct a new, empty table.
+    pub fn new() -> Self {
+        HashCons::default()
+    }
+
+    /// Return an expression equal to `expr` in which structurally equal
+    /// subexpressions share a single allocation.
+    ///
+    /// Subexpressions equal to nodes interned by previous calls to this table will
+    /// share those nodes as well.
+    pub fn intern(&mut self, expr: &Expr) -> Expr {
+        // Map from allocations in `expr` to their interned equivalents. Subexpressions
+        // that are already shared within `expr` are only visited once.
+        let mut interned: HashMap<ExprRefCmp, Expr> = HashMap::new();
+
+        // Traverse `expr` in post-order, so that the children of every normal node are
+        // interned before the node itself.
+        let mut stack: Vec<(Expr, bool)> = vec![(expr.clone(), false)];
+
+        while let Some((node, children_visited)) = stack.pop() {
+            let node = ExprRefCmp(node);
+
+            if interned.contains_key(&node) {
+                continue;
+            }
+
+            let ExprRefCmp(node) = node;
+
+            let result = match node.kind() {
+                ExprKind::Normal(normal) if !children_visited => {
+                    stack.push((node.clone(), true));
+
+                    for child in normal.contents.iter().rev() {
+                        stack.push((child.clone(), false));
+                    }
+                    stack.push((normal.head.clone(), false));
+
+                    continue;
+                },
+                ExprKind::Normal(Normal { head, contents }) => {
+                    let head = interned[&ExprRefCmp(head.clone())].clone();
+                    let contents = contents
+                        .iter()
+                        .map(|elem| interned[&ExprRefCmp(elem.clone())].clone())
+                        .collect();
+
+                    self.intern_normal(head, contents, Some(&node))
+                },
+                ExprKind::Integer(_)
+                | ExprKind::Real(_)
+                | ExprKind::String(_)
+                | ExprKind::Symbol(_) => self.intern_atom(&node),
+            };
+
+            interned.insert(ExprRefCmp(node), result);
+        }
+
+        interned
+            .remove(&ExprRefCmp(expr.clone()))
+            .expect("HashCons::intern(): root expression was not interned")
+    }
+
+    //==================================
+    // Constructors
+    //==================================
+
+    /// Construct an interned normal expression from the head and elements.
+    ///
+    /// `head` and `contents` are expected to have been produced by this table. If they
+    /// were not, they will be interned first.
+    pub fn normal<H: Into<Expr>>(&mut self, head: H, contents: Vec<Expr>) -> Expr {
+        let head = self.intern_child(head.into());
+        let contents = contents
+            .into_iter()
+            .map(|elem| self.intern_child(elem))
+            .collect();
+
+        self.intern_normal(head, contents, None)
+    }
+
+    /// Construct an interned expression from a [`Symbol`].
+    pub fn symbol<S: Into<Symbol>>(&mut self, s: S) -> Expr {
+        self.intern_atom(&Expr::symbol(s))
+    }
+
+    /// Construct an interned expression from a [`Number`].
+    pub fn number(&mut self, num: Number) -> Expr {
+        self.intern_atom(&Expr::number(num))
+    }
+
+    /// Construct an interned expression from a string.
+    pub fn string<S: Into<SmallString>>(&mut self, s: S) -> Expr {
+        self.intern_atom(&Expr::string(s))
+    }
+
+    //==================================
+    // Accessors
+    //==================================
+
+    /// Statistics describing the sharing performed by this table so far.
+    pub fn stats(&self) -> HashConsStats {
+        self.stats
+    }
+
+    /// Number of distinct nodes stored in this table.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if this table contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Remove all nodes from this table, and reset its statistics.
+    ///
+    /// Expressions previously returned by this table are not affected.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.stats = HashConsStats::default();
+    }
+
+    //==================================
+    // Helpers
+    //==================================
+
+    fn intern_atom(&mut self, atom: &Expr) -> Expr {
+        let key = match atom.kind() {
+            ExprKind::Real(real) => NodeKey::Real(real.into_inner().to_bits()),
+            _ => NodeKey::Atom(atom.clone()),
+        };
+
+        self.lookup_or_insert(key, atom, || atom.clone())
+    }
+
+    /// Intern a normal expression whose head and elements have already been interned.
+    ///
+    /// If `original` is provided and its head and elements are already the interned
+    /// nodes, it will be reused instead of allocating a new node.
+    fn intern_normal(
+        &mut self,
+        head: Expr,
+        contents: Vec<Expr>,
+        original: Option<&Expr>,
+    ) -> Expr {
+        let key = NodeKey::Normal(
+            ExprRefCmp(head.clone()),
+            contents.iter().cloned().map(ExprRefCmp).collect(),
+        );
+
+        let reusable = original.filter(|original| {
+            let normal = original
+                .try_as_normal()
+                .expect("HashCons: expected normal expression");
+
+            ptr_eq(&normal.head, &head)
+                && normal.contents.len() == contents.len()
+                && normal
+                    .contents
+                    .iter()
+                    .zip(&contents)
+                    .all(|(a, b)| ptr_eq(a, b))
+        });
+
+        match (reusable, original) {
+            (Some(original), _) => {
+                self.lookup_or_insert(key, original, || original.clone())
+            },
+            (None, Some(original)) => {
+                self.lookup_or_insert(key, original, || Expr::normal(head, contents))
+            },
+            (None, None) => {
+                let new = Expr::normal(head, contents);
+                self.lookup_or_insert(key, &new, || new.clone())
+            },
+        }
+    }
+
+    /// Intern an expression passed to one of the constructor methods.
+    ///
+    /// Children that were produced by this table are recognized without walking their
+    /// subexpressions.
+    fn intern_child(&mut self, child: Expr) -> Expr {
+        let key = match child.kind() {
+            ExprKind::Normal(Normal { head, contents }) => NodeKey::Normal(
+                ExprRefCmp(head.clone()),
+                contents.iter().cloned().map(ExprRefCmp).collect(),
+            ),
+            ExprKind::Integer(_)
+            | ExprKind::Real(_)
+            | ExprKind::String(_)
+            | ExprKind::Symbol(_) => return self.intern_atom(&child),
+        };
+
+        match self.nodes.get(&key) {
+            Some(existing) if ptr_eq(existing, &child) => child,
+            _ => self.intern(&child),
+        }
+    }
+
+    /// Return the interned node equal to `key`, or insert the node returned by `new`.
+    ///
+    /// `original` is the node being replaced, and is used to keep track of sharing
+    /// statistics.
+    fn lookup_or_insert<F>(&mut self, key: NodeKey, original: &Expr, new: F) -> Expr
+    where
+        F: FnOnce() -> Expr,
+    {
+        if let Some(existing) = self.nodes.get(&key) {
+            if !ptr_eq(existing, original) {
+                self.stats.shared_nodes += 1;
+                self.stats.bytes_saved += allocation_size(original);
+            }
+
+            return existing.clone();
+        }
+
+        let new = new();
+
+        self.nodes.insert(key, new.clone());
+        self.stats.unique_nodes += 1;
+
+        new
+    }
+}
+
+pub(crate) fn ptr_eq(a: &Expr, b: &Expr) -> bool {
+    Arc::ptr_eq(&a.inner, &b.inner)
+}
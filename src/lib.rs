@@ -3,32 +3,68 @@
 #![allow(clippy::let_and_return)]
 #![warn(missing_docs)]
 
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+mod arbitrary;
+mod arena;
 mod conversion;
+mod hash_cons;
+mod limits;
+mod local;
+mod macros;
+mod metrics;
+mod numeric;
 mod ptr_cmp;
-
+mod small_string;
+
+pub mod algebra;
+pub mod attributes;
+pub mod compile;
+pub mod dag;
+pub mod diff;
+pub mod eval;
+pub mod function;
+pub mod scope;
 pub mod symbol;
 
 #[cfg(test)]
 mod tests;
 
+// Ensure that doc tests in the README.md file get run.
 #[doc(hidden)]
-mod test_readme {
-    // Ensure that doc tests in the README.md file get run.
-    #![doc = include_str ! ("../README.md")]
-}
+#[doc = include_str!("../README.md")]
+mod test_readme {}
 
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 
 #[doc(inline)]
 pub use self::symbol::Symbol;
 
+pub use self::{
+    arena::{ArenaExpr, ExprArena},
+    hash_cons::{HashCons, HashConsStats},
+    limits::{LimitExceeded, Limits},
+    local::LocalExpr,
+    numeric::{Numeric, Rational},
+    small_string::SmallString,
+};
+
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+pub use self::arbitrary::ArbitraryConfig;
+
 #[cfg(feature = "unstable_parse")]
 pub use self::ptr_cmp::ExprRefCmp;
 
+#[doc(hidden)]
+pub use self::macros::__private;
+
 /// Wolfram Language expression.
 ///
 /// # Example
@@ -49,9 +85,33 @@ pub use self::ptr_cmp::ExprRefCmp;
 ///
 /// Internally, `Expr` is an atomically reference-counted [`ExprKind`]. This makes cloning
 /// an expression computationally inexpensive.
-#[derive(Clone, PartialEq, Eq, Hash)]
+///
+/// Single-threaded code can use [`LocalExpr`] to avoid the cost of atomic reference
+/// counting.
+///
+/// # Deeply nested expressions
+///
+/// Dropping, cloning, comparing, hashing, and formatting an `Expr` are all implemented
+/// without recursion, so they can be used on arbitrarily deep expressions (e.g. a
+/// 1,000,000-level `f[f[f[...]]]` chain) without overflowing the stack.
+///
+/// # Hashing
+///
+/// The structural hash of an expression is computed the first time it is needed, and
+/// then cached in the reference-counted allocation. Subsequent calls to
+/// [`Hash::hash()`] on the same expression (or on a clone of it) are O(1), as is
+/// comparing two unequal expressions whose hashes have both already been computed.
+#[derive(Clone)]
 pub struct Expr {
-    inner: Arc<ExprKind>,
+    inner: Arc<ExprNode>,
+}
+
+/// The reference-counted allocation that backs an [`Expr`].
+pub(crate) struct ExprNode {
+    kind: ExprKind,
+    /// Lazily computed structural hash of `kind`, or `0` if it hasn't been computed
+    /// yet.
+    hash: AtomicU64,
 }
 
 // Assert that Expr has the same size and alignment as a usize / pointer.
@@ -64,7 +124,10 @@ impl Expr {
     /// Construct a new expression from an [`ExprKind`].
     pub fn new(kind: ExprKind) -> Expr {
         Expr {
-            inner: Arc::new(kind),
+            inner: Arc::new(ExprNode {
+                kind,
+                hash: AtomicU64::new(0),
+            }),
         }
     }
 
@@ -83,14 +146,14 @@ impl Expr {
     #[allow(clippy::wrong_self_convention)]
     pub fn to_kind(self) -> ExprKind {
         match Arc::try_unwrap(self.inner) {
-            Ok(kind) => kind,
-            Err(self_) => (*self_).clone(),
+            Ok(mut node) => node.take_kind(),
+            Err(self_) => self_.kind.clone(),
         }
     }
 
     /// Get the [`ExprKind`] representing this expression.
     pub fn kind(&self) -> &ExprKind {
-        &*self.inner
+        &self.inner.kind
     }
 
     /// Get mutable access to the [`ExprKind`] that represents this expression.
@@ -98,7 +161,12 @@ impl Expr {
     /// If the reference count of the underlying shared pointer is not equal to 1, this
     /// will clone the [`ExprKind`] to make it unique.
     pub fn kind_mut(&mut self) -> &mut ExprKind {
-        Arc::make_mut(&mut self.inner)
+        let node = Arc::make_mut(&mut self.inner);
+
+        // The caller may modify the expression, so any cached hash is no longer valid.
+        *node.hash.get_mut() = 0;
+
+        &mut node.kind
     }
 
     /// Retrieve the reference count of this expression.
@@ -106,13 +174,67 @@ impl Expr {
         Arc::strong_count(&self.inner)
     }
 
+    /// Get the structural hash of this expression, computing and caching it if
+    /// necessary.
+    pub(crate) fn structural_hash(&self) -> u64 {
+        if let Some(hash) = self.cached_hash() {
+            return hash;
+        }
+
+        // Compute the hashes of any subexpressions that don't already have a cached
+        // hash in post-order, so that the hash of every normal expression is computed
+        // after the hashes of its head and elements.
+        let mut stack: Vec<(&Expr, bool)> = vec![(self, false)];
+
+        while let Some((expr, children_visited)) = stack.pop() {
+            if expr.cached_hash().is_some() {
+                continue;
+            }
+
+            match expr.kind() {
+                ExprKind::Normal(normal) if !children_visited => {
+                    stack.push((expr, true));
+
+                    for child in std::iter::once(&normal.head).chain(&normal.contents) {
+                        if child.cached_hash().is_none() {
+                            stack.push((child, false));
+                        }
+                    }
+                },
+                _ => {
+                    // The hashes of the head and elements of `expr` (if any) have
+                    // already been cached, so this does not recurse.
+                    let mut hasher = DefaultHasher::new();
+                    expr.kind().hash(&mut hasher);
+
+                    // `0` is reserved to mean "not yet computed".
+                    let hash = match hasher.finish() {
+                        0 => 1,
+                        hash => hash,
+                    };
+
+                    expr.inner.hash.store(hash, Ordering::Relaxed);
+                },
+            }
+        }
+
+        self.cached_hash()
+            .expect("Expr::structural_hash(): hash was not computed")
+    }
+
+    /// Get the structural hash of this expression, if it has already been computed.
+    fn cached_hash(&self) -> Option<u64> {
+        match self.inner.hash.load(Ordering::Relaxed) {
+            0 => None,
+            hash => Some(hash),
+        }
+    }
+
     /// Construct a new normal expression from the head and elements.
     pub fn normal<H: Into<Expr>>(head: H, contents: Vec<Expr>) -> Expr {
         let head = head.into();
         // let contents = contents.into();
-        Expr {
-            inner: Arc::new(ExprKind::Normal(Normal { head, contents })),
-        }
+        Expr::new(ExprKind::Normal(Normal { head, contents }))
     }
 
     // TODO: Should Expr's be cached? Especially Symbol exprs? Would certainly save
@@ -120,23 +242,20 @@ impl Expr {
     /// Construct a new expression from a [`Symbol`].
     pub fn symbol<S: Into<Symbol>>(s: S) -> Expr {
         let s = s.into();
-        Expr {
-            inner: Arc::new(ExprKind::Symbol(s)),
-        }
+        Expr::new(ExprKind::Symbol(s))
     }
 
     /// Construct a new expression from a [`Number`].
     pub fn number(num: Number) -> Expr {
-        Expr {
-            inner: Arc::new(ExprKind::from(num)),
-        }
+        Expr::new(ExprKind::from(num))
     }
 
-    /// Construct a new expression from a [`String`].
-    pub fn string<S: Into<String>>(s: S) -> Expr {
-        Expr {
-            inner: Arc::new(ExprKind::String(s.into())),
-        }
+    /// Construct a new expression from a string.
+    ///
+    /// Short strings are stored inline, without a separate allocation. See
+    /// [`SmallString`].
+    pub fn string<S: Into<SmallString>>(s: S) -> Expr {
+        Expr::new(ExprKind::String(s.into()))
     }
 
     /// Construct an expression from a floating-point number.
@@ -148,11 +267,24 @@ impl Expr {
     ///
     /// # Panics
     ///
-    /// This function will panic if `real` is NaN.
+    /// This function will panic if `real` is NaN. Use [`Expr::try_real()`] to handle NaN
+    /// values, or `Expr::from(real)` to convert NaN to `Indeterminate`.
     pub fn real(real: f64) -> Expr {
         Expr::number(Number::real(real))
     }
 
+    /// Construct an expression from a floating-point number, returning `None` if `real`
+    /// is NaN.
+    ///
+    /// ```
+    /// # use wolfram_expr::Expr;
+    /// assert_eq!(Expr::try_real(2.5), Some(Expr::real(2.5)));
+    /// assert_eq!(Expr::try_real(f64::NAN), None);
+    /// ```
+    pub fn try_real(real: f64) -> Option<Expr> {
+        Number::try_real(real).map(Expr::number)
+    }
+
     /// Returns the outer-most symbol "tag" used in this expression.
     ///
     /// To illustrate:
@@ -170,17 +302,23 @@ impl Expr {
     // TODO: The above TODO is probably wrong -- tag() shouldn't have any language
     //       semantics built in to it.
     pub fn tag(&self) -> Option<Symbol> {
-        match *self.inner {
-            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => None,
-            ExprKind::Normal(ref normal) => normal.head.tag(),
-            ExprKind::Symbol(ref sym) => Some(sym.clone()),
+        let mut expr = self;
+
+        loop {
+            match *expr.kind() {
+                ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => {
+                    return None
+                },
+                ExprKind::Normal(ref normal) => expr = &normal.head,
+                ExprKind::Symbol(ref sym) => return Some(sym.clone()),
+            }
         }
     }
 
     /// If this represents a [`Normal`] expression, return its head. Otherwise, return
     /// `None`.
     pub fn normal_head(&self) -> Option<Expr> {
-        match *self.inner {
+        match *self.kind() {
             ExprKind::Normal(ref normal) => Some(normal.head.clone()),
             ExprKind::Symbol(_)
             | ExprKind::Integer(_)
@@ -243,7 +381,7 @@ impl Expr {
     pub fn rule<LHS: Into<Expr>>(lhs: LHS, rhs: Expr) -> Expr {
         let lhs = lhs.into();
 
-        Expr::normal(Symbol::new("System`Rule"), vec![lhs, rhs])
+        Expr::normal(crate::symbol!("System`Rule"), vec![lhs, rhs])
     }
     /// Construct a new `RuleDelayed[_, _]` expression from the left-hand side and right-hand
     /// side.
@@ -263,7 +401,7 @@ impl Expr {
     pub fn rule_delayed<LHS: Into<Expr>>(lhs: LHS, rhs: Expr) -> Expr {
         let lhs = lhs.into();
 
-        Expr::normal(Symbol::new("System`RuleDelayed"), vec![lhs, rhs])
+        Expr::normal(crate::symbol!("System`RuleDelayed"), vec![lhs, rhs])
     }
 
     /// Construct a new `List[...]`(`{...}`) expression from it's elements.
@@ -278,7 +416,7 @@ impl Expr {
     /// let list = Expr::list(vec![Expr::from(1), Expr::from(2), Expr::from(3)]);
     /// ```
     pub fn list(elements: Vec<Expr>) -> Expr {
-        Expr::normal(Symbol::new("System`List"), elements)
+        Expr::normal(crate::symbol!("System`List"), elements)
     }
 }
 
@@ -288,7 +426,7 @@ impl Expr {
 pub enum ExprKind<E = Expr> {
     Integer(i64),
     Real(F64),
-    String(String),
+    String(SmallString),
     Symbol(Symbol),
     Normal(Normal<E>),
 }
@@ -330,6 +468,238 @@ pub type F32 = ordered_float::NotNan<f32>;
 // Type Impl's
 //=======================================
 
+impl ExprNode {
+    /// Take the `ExprKind` stored in this node, leaving behind a placeholder atom.
+    fn take_kind(&mut self) -> ExprKind {
+        mem::replace(&mut self.kind, ExprKind::Integer(0))
+    }
+}
+
+impl Clone for ExprNode {
+    /// Cloning an `ExprNode` is shallow: the head and elements of a normal expression
+    /// are cloned by incrementing their reference counts.
+    fn clone(&self) -> Self {
+        ExprNode {
+            kind: self.kind.clone(),
+            hash: AtomicU64::new(self.hash.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Drop the subexpressions of a normal expression without recursion.
+///
+/// The default drop glue for a normal expression would recursively drop its head and
+/// elements, which can overflow the stack when dropping a very deep expression.
+/// Instead, uniquely referenced subexpressions are moved onto an explicit work list,
+/// and the `ExprKind` of each is replaced by an atom before the allocation is freed,
+/// so that dropping it does not recurse.
+impl Drop for ExprNode {
+    fn drop(&mut self) {
+        if !matches!(self.kind, ExprKind::Normal(_)) {
+            return;
+        }
+
+        let mut stack: Vec<Expr> = match self.take_kind() {
+            ExprKind::Normal(Normal { head, contents }) => {
+                let mut stack = contents;
+                stack.push(head);
+                stack
+            },
+            _ => unreachable!(),
+        };
+
+        while let Some(expr) = stack.pop() {
+            // If this was not the last reference to `expr`, dropping it only decrements
+            // the reference count.
+            let mut node = match Arc::into_inner(expr.inner) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            if let ExprKind::Normal(Normal { head, mut contents }) = node.take_kind() {
+                stack.push(head);
+                stack.append(&mut contents);
+            }
+
+            // `node` now contains an atom, so it is dropped without recursion.
+        }
+    }
+}
+
+/// Pointer to an [`ExprKind`], used to share traversal code between [`Expr`],
+/// [`LocalExpr`] and [`ArenaExpr`].
+pub(crate) trait ExprPtr: Sized {
+    fn kind_ref(&self) -> &ExprKind<Self>;
+
+    /// Address of the node this pointer refers to.
+    fn addr(&self) -> *const ();
+
+    /// Returns `true` if the node this pointer refers to may be referenced more than
+    /// once.
+    fn is_shared(&self) -> bool;
+
+    /// Structural hash of the node, if one has already been computed.
+    ///
+    /// Nodes with different structural hashes cannot be equal.
+    fn known_hash(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl ExprPtr for Expr {
+    fn kind_ref(&self) -> &ExprKind {
+        self.kind()
+    }
+
+    fn addr(&self) -> *const () {
+        Arc::as_ptr(&self.inner) as *const ()
+    }
+
+    fn is_shared(&self) -> bool {
+        self.ref_count() > 1
+    }
+
+    fn known_hash(&self) -> Option<u64> {
+        self.cached_hash()
+    }
+}
+
+/// Convert between expression representations without recursion, preserving shared
+/// subexpressions.
+///
+/// `new` constructs a node of the target representation.
+pub(crate) fn convert<A, B, F>(root: &A, mut new: F) -> B
+where
+    A: ExprPtr,
+    B: Clone,
+    F: FnMut(ExprKind<B>) -> B,
+{
+    // Converted values of shared allocations.
+    let mut memo: HashMap<*const (), B> = HashMap::new();
+
+    // Converted subexpressions that have not been consumed by their parent.
+    let mut values: Vec<B> = Vec::new();
+
+    let mut stack: Vec<(&A, bool)> = vec![(root, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        let is_shared = expr.is_shared();
+
+        if is_shared && !children_visited {
+            if let Some(value) = memo.get(&expr.addr()) {
+                values.push(value.clone());
+                continue;
+            }
+        }
+
+        let kind = match expr.kind_ref() {
+            ExprKind::Normal(Normal { head, contents }) if !children_visited => {
+                stack.push((expr, true));
+
+                for elem in contents.iter().rev() {
+                    stack.push((elem, false));
+                }
+                stack.push((head, false));
+
+                continue;
+            },
+            ExprKind::Normal(Normal { contents, .. }) => {
+                let start = values.len() - contents.len() - 1;
+
+                let mut converted = values.split_off(start).into_iter();
+                let head = converted.next().expect("convert: missing head");
+
+                ExprKind::Normal(Normal {
+                    head,
+                    contents: converted.collect(),
+                })
+            },
+            ExprKind::Integer(int) => ExprKind::Integer(*int),
+            ExprKind::Real(real) => ExprKind::Real(*real),
+            ExprKind::String(string) => ExprKind::String(string.clone()),
+            ExprKind::Symbol(symbol) => ExprKind::Symbol(symbol.clone()),
+        };
+
+        let value = new(kind);
+
+        if is_shared {
+            memo.insert(expr.addr(), value.clone());
+        }
+
+        values.push(value);
+    }
+
+    values
+        .pop()
+        .expect("convert: no value computed for root expression")
+}
+
+/// Compare two expressions for structural equality without recursion.
+pub(crate) fn eq_iteratively<E: ExprPtr + PartialEq>(a: &E, b: &E) -> bool {
+    // Pairs of subexpressions that remain to be compared.
+    let mut stack: Vec<(&E, &E)> = vec![(a, b)];
+
+    while let Some((a, b)) = stack.pop() {
+        if a.addr() == b.addr() {
+            continue;
+        }
+
+        if let (Some(a_hash), Some(b_hash)) = (a.known_hash(), b.known_hash()) {
+            if a_hash != b_hash {
+                return false;
+            }
+        }
+
+        match (a.kind_ref(), b.kind_ref()) {
+            (ExprKind::Normal(a), ExprKind::Normal(b)) => {
+                if a.contents.len() != b.contents.len() {
+                    return false;
+                }
+
+                for pair in a.contents.iter().zip(&b.contents).rev() {
+                    stack.push(pair);
+                }
+                stack.push((&a.head, &b.head));
+            },
+            // At least one of `a` and `b` is an atom, so this does not recurse.
+            (a, b) => {
+                if a != b {
+                    return false;
+                }
+            },
+        }
+    }
+
+    true
+}
+
+/// Hash an expression without recursion.
+pub(crate) fn hash_iteratively<E, H>(expr: &E, state: &mut H)
+where
+    E: ExprPtr + Hash,
+    H: Hasher,
+{
+    // Hash the expression in pre-order. Including the number of elements of each
+    // normal expression makes the sequence of hashed values unambiguous.
+    let mut stack: Vec<&E> = vec![expr];
+
+    while let Some(expr) = stack.pop() {
+        match expr.kind_ref() {
+            ExprKind::Normal(Normal { head, contents }) => {
+                mem::discriminant(expr.kind_ref()).hash(state);
+                contents.len().hash(state);
+
+                for elem in contents.iter().rev() {
+                    stack.push(elem);
+                }
+                stack.push(head);
+            },
+            // Atoms are hashed without recursion.
+            kind => kind.hash(state),
+        }
+    }
+}
+
 impl Normal {
     /// Construct a new normal expression from the head and elements.
     pub fn new<E: Into<Expr>>(head: E, contents: Vec<Expr>) -> Self {
@@ -338,9 +708,11 @@ impl Normal {
             contents,
         }
     }
+}
 
+impl<E> Normal<E> {
     /// The head of this normal expression.
-    pub fn head(&self) -> &Expr {
+    pub fn head(&self) -> &E {
         &self.head
     }
 
@@ -348,19 +720,22 @@ impl Normal {
     ///
     /// If `head` conceptually represents a function, these are the arguments that are
     /// being applied to `head`.
-    pub fn elements(&self) -> &[Expr] {
+    pub fn elements(&self) -> &[E] {
         &self.contents
     }
 
     /// The elements of this normal expression.
     ///
     /// Use [`Normal::elements()`] to get a reference to this value.
-    pub fn into_elements(self) -> Vec<Expr> {
+    pub fn into_elements(self) -> Vec<E> {
         self.contents
     }
 
     /// Returns `true` if the head of this expression is `sym`.
-    pub fn has_head(&self, sym: &Symbol) -> bool {
+    pub fn has_head(&self, sym: &Symbol) -> bool
+    where
+        E: PartialEq<Symbol>,
+    {
         self.head == *sym
     }
 }
@@ -379,6 +754,11 @@ impl Number {
         };
         Number::Real(r)
     }
+
+    /// Construct a real number, returning `None` if `r` is NaN.
+    pub fn try_real(r: f64) -> Option<Self> {
+        ordered_float::NotNan::new(r).ok().map(Number::Real)
+    }
 }
 
 //=======================================
@@ -387,8 +767,7 @@ impl Number {
 
 impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let Expr { inner } = self;
-        write!(f, "{:?}", inner)
+        write!(f, "{}", self)
     }
 }
 
@@ -398,26 +777,13 @@ impl fmt::Debug for Expr {
 /// literals needing precision and accuracy marks will have them.
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.inner)
+        fmt_iteratively(f, vec![FmtTask::Expr(self)])
     }
 }
 
 impl fmt::Display for ExprKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            ExprKind::Normal(ref normal) => fmt::Display::fmt(normal, f),
-            ExprKind::Integer(ref int) => fmt::Display::fmt(int, f),
-            ExprKind::Real(ref real) => fmt::Display::fmt(real, f),
-            ExprKind::String(ref string) => {
-                // Escape any '"' which appear in the string.
-                // Using the Debug implementation will cause \n, \t, etc. to appear in
-                // place of the literal character they are escapes for. This is necessary
-                // when printing expressions in a way that they can be read back in as a
-                // string, such as with ToExpression.
-                write!(f, "{:?}", string)
-            },
-            ExprKind::Symbol(ref symbol) => fmt::Display::fmt(symbol, f),
-        }
+        fmt_kind(self, f)
     }
 }
 
@@ -429,15 +795,81 @@ impl fmt::Debug for ExprKind {
 
 impl fmt::Display for Normal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}[", self.head)?;
-        for (idx, elem) in self.contents.iter().enumerate() {
-            write!(f, "{}", elem)?;
-            if idx != self.contents.len() - 1 {
-                write!(f, ", ")?;
-            }
+        fmt_normal(self, f)
+    }
+}
+
+/// Format an [`ExprKind`] of any expression representation.
+pub(crate) fn fmt_kind<E: ExprPtr>(
+    kind: &ExprKind<E>,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    match *kind {
+        ExprKind::Normal(ref normal) => fmt_normal(normal, f),
+        ExprKind::Integer(ref int) => fmt::Display::fmt(int, f),
+        ExprKind::Real(ref real) => fmt::Display::fmt(real, f),
+        ExprKind::String(ref string) => {
+            // Escape any '"' which appear in the string.
+            // Using the Debug implementation will cause \n, \t, etc. to appear in
+            // place of the literal character they are escapes for. This is necessary
+            // when printing expressions in a way that they can be read back in as a
+            // string, such as with ToExpression.
+            write!(f, "{:?}", string)
+        },
+        ExprKind::Symbol(ref symbol) => fmt::Display::fmt(symbol, f),
+    }
+}
+
+/// Format a [`Normal`] of any expression representation.
+pub(crate) fn fmt_normal<E: ExprPtr>(
+    normal: &Normal<E>,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    let mut stack = Vec::new();
+    push_normal_tasks(&mut stack, normal);
+
+    fmt_iteratively(f, stack)
+}
+
+/// Pending piece of output used by [`fmt_iteratively()`].
+pub(crate) enum FmtTask<'e, E> {
+    Expr(&'e E),
+    Str(&'static str),
+}
+
+/// Format expressions without recursion, so that arbitrarily deep expressions can be
+/// formatted without overflowing the stack.
+///
+/// `stack` contains the pieces of output still to be written, in reverse order.
+pub(crate) fn fmt_iteratively<E: ExprPtr>(
+    f: &mut fmt::Formatter,
+    mut stack: Vec<FmtTask<E>>,
+) -> fmt::Result {
+    while let Some(task) = stack.pop() {
+        match task {
+            FmtTask::Str(str) => f.write_str(str)?,
+            FmtTask::Expr(expr) => match expr.kind_ref() {
+                ExprKind::Normal(normal) => push_normal_tasks(&mut stack, normal),
+                // Atoms are formatted without recursion.
+                kind => fmt_kind(kind, f)?,
+            },
         }
-        write!(f, "]")
     }
+
+    Ok(())
+}
+
+/// Push the tasks that format `normal` as `head[elem1, elem2, ...]` onto `stack`.
+fn push_normal_tasks<'e, E>(stack: &mut Vec<FmtTask<'e, E>>, normal: &'e Normal<E>) {
+    stack.push(FmtTask::Str("]"));
+    for (idx, elem) in normal.contents.iter().enumerate().rev() {
+        stack.push(FmtTask::Expr(elem));
+        if idx != 0 {
+            stack.push(FmtTask::Str(", "));
+        }
+    }
+    stack.push(FmtTask::Str("["));
+    stack.push(FmtTask::Expr(&normal.head));
 }
 
 impl fmt::Display for Number {
@@ -458,6 +890,20 @@ impl fmt::Display for Number {
 // Comparision trait impls
 //======================================
 
+impl PartialEq for Expr {
+    fn eq(&self, other: &Expr) -> bool {
+        eq_iteratively(self, other)
+    }
+}
+
+impl Eq for Expr {}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.structural_hash())
+    }
+}
+
 impl PartialEq<Symbol> for Expr {
     fn eq(&self, other: &Symbol) -> bool {
         match self.kind() {
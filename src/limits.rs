@@ -0,0 +1,193 @@
+use std::fmt;
+
+
+/// Resource limits applied when decoding expressions from untrusted sources.
+///
+/// A malicious or corrupt payload can describe an expression that is arbitrarily
+/// deep or arbitrarily large. Decoders that accept a `Limits` value check each limit
+/// as the expression is read, and return a [`LimitExceeded`] error as soon as one is
+/// exceeded, before allocating the memory that would be required to represent the
+/// offending data.
+///
+/// The following functions honor `Limits`:
+///
+/// * [`dag::read_with_limits()`][crate::dag::read_with_limits]
+/// * [`dag::from_bytes_with_limits()`][crate::dag::from_bytes_with_limits]
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{dag, Expr, Limits};
+///
+/// let expr = Expr::list(vec![Expr::list(vec![Expr::from(1)])]);
+/// let bytes = dag::to_bytes(&expr);
+///
+/// let limits = Limits {
+///     max_nesting: 2,
+///     ..Limits::default()
+/// };
+///
+/// assert!(dag::from_bytes_with_limits(&bytes, &limits).is_err());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Maximum nesting of the expression, counting heads, as measured by
+    /// [`Expr::nesting()`][crate::Expr::nesting].
+    ///
+    /// Unlike [`Expr::depth()`][crate::Expr::depth], this limits expressions with
+    /// deeply nested heads, like `f[a][b][c]`.
+    pub max_nesting: usize,
+    /// Maximum number of subexpressions in the expression, including heads.
+    ///
+    /// Shared subexpressions are counted once for every place they appear, consistent
+    /// with [`Expr::node_count()`][crate::Expr::node_count].
+    pub max_node_count: usize,
+    /// Maximum length in bytes of any string or symbol in the expression.
+    pub max_string_length: usize,
+    /// Maximum approximate number of bytes of memory used by the decoded expression.
+    ///
+    /// Shared subexpressions are counted only once, consistent with
+    /// [`Expr::byte_count()`][crate::Expr::byte_count].
+    pub max_total_bytes: usize,
+}
+
+/// Error returned when an expression being decoded exceeds one of the configured
+/// [`Limits`].
+///
+/// Each variant contains the value of the limit that was exceeded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LimitExceeded {
+    /// [`Limits::max_nesting`] was exceeded.
+    Nesting(usize),
+    /// [`Limits::max_node_count`] was exceeded.
+    NodeCount(usize),
+    /// [`Limits::max_string_length`] was exceeded.
+    StringLength(usize),
+    /// [`Limits::max_total_bytes`] was exceeded.
+    TotalBytes(usize),
+}
+
+impl Limits {
+    /// Limits that allow any expression to be decoded.
+    pub const fn unlimited() -> Self {
+        Limits {
+            max_nesting: usize::MAX,
+            max_node_count: usize::MAX,
+            max_string_length: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+/// The default limits are generous enough for most legitimate expressions, while
+/// still protecting against payloads designed to exhaust memory:
+///
+/// Limit               | Default
+/// --------------------|------------------------------
+/// `max_nesting`       | 4,096
+/// `max_node_count`    | 2<sup>24</sup>
+/// `max_string_length` | 2<sup>24</sup> bytes (16 MiB)
+/// `max_total_bytes`   | 2<sup>30</sup> bytes (1 GiB)
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_nesting: 4096,
+            max_node_count: 1 << 24,
+            max_string_length: 1 << 24,
+            max_total_bytes: 1 << 30,
+        }
+    }
+}
+
+/// Running totals of the resources used by an expression being decoded.
+pub(crate) struct Budget<'l> {
+    limits: &'l Limits,
+    nodes: usize,
+    bytes: usize,
+}
+
+impl<'l> Budget<'l> {
+    pub(crate) fn new(limits: &'l Limits) -> Self {
+        Budget {
+            limits,
+            nodes: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Total number of nodes added so far.
+    pub(crate) fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    pub(crate) fn check_nesting(&self, nesting: usize) -> Result<(), LimitExceeded> {
+        if nesting > self.limits.max_nesting {
+            return Err(LimitExceeded::Nesting(self.limits.max_nesting));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_string_length(&self, len: u64) -> Result<(), LimitExceeded> {
+        if len > self.limits.max_string_length as u64 {
+            return Err(LimitExceeded::StringLength(self.limits.max_string_length));
+        }
+
+        Ok(())
+    }
+
+    /// Check that `count` more nodes can be added without exceeding the limit, without
+    /// adding them.
+    pub(crate) fn check_nodes(&self, count: usize) -> Result<(), LimitExceeded> {
+        if self.nodes.saturating_add(count) > self.limits.max_node_count {
+            return Err(LimitExceeded::NodeCount(self.limits.max_node_count));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn add_nodes(&mut self, count: usize) -> Result<(), LimitExceeded> {
+        self.nodes = self.nodes.saturating_add(count);
+
+        if self.nodes > self.limits.max_node_count {
+            return Err(LimitExceeded::NodeCount(self.limits.max_node_count));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn add_bytes(&mut self, count: usize) -> Result<(), LimitExceeded> {
+        self.bytes = self.bytes.saturating_add(count);
+
+        if self.bytes > self.limits.max_total_bytes {
+            return Err(LimitExceeded::TotalBytes(self.limits.max_total_bytes));
+        }
+
+        Ok(())
+    }
+}
+
+//======================================
+// Formatting impls
+//======================================
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Nesting(limit) => {
+                write!(f, "expression nesting exceeds limit of {}", limit)
+            },
+            LimitExceeded::NodeCount(limit) => {
+                write!(f, "expression node count exceeds limit of {}", limit)
+            },
+            LimitExceeded::StringLength(limit) => {
+                write!(f, "string length exceeds limit of {} bytes", limit)
+            },
+            LimitExceeded::TotalBytes(limit) => {
+                write!(f, "expression size exceeds limit of {} bytes", limit)
+            },
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
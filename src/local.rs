@@ -0,0 +1,327 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
+};
+
+use crate::{
+    convert, eq_iteratively, fmt_iteratively, fmt_kind, fmt_normal, hash_iteratively,
+    Expr, ExprKind, ExprPtr, FmtTask, Normal, Number, SmallString, Symbol,
+};
+
+
+/// Single-threaded Wolfram Language expression.
+///
+/// `LocalExpr` is equivalent to [`Expr`], except that it is reference-counted using
+/// [`Rc`] instead of [`Arc`][std::sync::Arc]. Cloning and dropping a `LocalExpr` does
+/// not require atomic operations, which makes it cheaper to use in single-threaded
+/// code that clones expressions frequently. A `LocalExpr` cannot be sent to another
+/// thread.
+///
+/// `LocalExpr` uses the same [`ExprKind`] and [`Normal`] types as [`Expr`], as
+/// `ExprKind<LocalExpr>` and `Normal<LocalExpr>`, so accessors like
+/// [`ExprKind::try_as_str()`] and [`Normal::elements()`] work with both
+/// representations.
+///
+/// Use [`Expr::to_local()`] and [`LocalExpr::to_expr()`] to convert between the two.
+/// Subexpressions that are shared in the original expression are also shared in the
+/// converted expression.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{Expr, LocalExpr};
+///
+/// let expr = Expr::list(vec![Expr::from(1), Expr::from("two")]);
+///
+/// let local: LocalExpr = expr.to_local();
+///
+/// let elements = local.kind().try_as_normal().unwrap().elements();
+/// assert_eq!(elements[1].kind().try_as_str(), Some("two"));
+///
+/// assert_eq!(local.to_expr(), expr);
+/// ```
+#[derive(Clone)]
+pub struct LocalExpr {
+    inner: Rc<ExprKind<LocalExpr>>,
+}
+
+// Assert that LocalExpr has the same size as a pointer.
+const _: () = assert!(mem::size_of::<LocalExpr>() == mem::size_of::<*const ()>());
+
+impl LocalExpr {
+    /// Construct a new expression from an [`ExprKind`].
+    pub fn new(kind: ExprKind<LocalExpr>) -> LocalExpr {
+        LocalExpr {
+            inner: Rc::new(kind),
+        }
+    }
+
+    /// Consume `self` and return an owned [`ExprKind`].
+    ///
+    /// If the reference count of `self` is equal to 1 this function will *not* perform
+    /// a clone of the stored `ExprKind`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_kind(mut self) -> ExprKind<LocalExpr> {
+        match Rc::get_mut(&mut self.inner) {
+            Some(kind) => take_kind(kind),
+            None => ExprKind::clone(&self.inner),
+        }
+    }
+
+    /// Get the [`ExprKind`] representing this expression.
+    pub fn kind(&self) -> &ExprKind<LocalExpr> {
+        &self.inner
+    }
+
+    /// Get mutable access to the [`ExprKind`] that represents this expression.
+    ///
+    /// If the reference count of the underlying shared pointer is not equal to 1, this
+    /// will clone the [`ExprKind`] to make it unique.
+    pub fn kind_mut(&mut self) -> &mut ExprKind<LocalExpr> {
+        Rc::make_mut(&mut self.inner)
+    }
+
+    /// Retrieve the reference count of this expression.
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.inner)
+    }
+
+    /// Convert this expression into an [`Expr`].
+    ///
+    /// Subexpressions that are shared within `self` are converted only once, and are
+    /// shared in the returned expression.
+    pub fn to_expr(&self) -> Expr {
+        convert(self, Expr::new)
+    }
+
+    /// Construct a new normal expression from the head and elements.
+    pub fn normal<H: Into<LocalExpr>>(head: H, contents: Vec<LocalExpr>) -> LocalExpr {
+        LocalExpr::new(ExprKind::Normal(Normal {
+            head: head.into(),
+            contents,
+        }))
+    }
+
+    /// Construct a new expression from a [`Symbol`].
+    pub fn symbol<S: Into<Symbol>>(s: S) -> LocalExpr {
+        LocalExpr::new(ExprKind::Symbol(s.into()))
+    }
+
+    /// Construct a new expression from a [`Number`].
+    pub fn number(num: Number) -> LocalExpr {
+        LocalExpr::new(ExprKind::from(num))
+    }
+
+    /// Construct a new expression from a string.
+    pub fn string<S: Into<SmallString>>(s: S) -> LocalExpr {
+        LocalExpr::new(ExprKind::String(s.into()))
+    }
+
+    /// Construct a new `List[...]`(`{...}`) expression from it's elements.
+    pub fn list(elements: Vec<LocalExpr>) -> LocalExpr {
+        LocalExpr::normal(crate::symbol!("System`List"), elements)
+    }
+
+    /// If this expression is uniquely referenced and is a normal expression, move its
+    /// head and elements onto `stack`, leaving behind a placeholder atom.
+    fn take_children(&mut self, stack: &mut Vec<LocalExpr>) {
+        if let Some(kind) = Rc::get_mut(&mut self.inner) {
+            if let ExprKind::Normal(_) = kind {
+                if let ExprKind::Normal(Normal { head, mut contents }) = take_kind(kind) {
+                    stack.push(head);
+                    stack.append(&mut contents);
+                }
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Convert this expression into a [`LocalExpr`].
+    ///
+    /// Subexpressions that are shared within `self` are converted only once, and are
+    /// shared in the returned expression.
+    pub fn to_local(&self) -> LocalExpr {
+        convert(self, LocalExpr::new)
+    }
+}
+
+impl ExprPtr for LocalExpr {
+    fn kind_ref(&self) -> &ExprKind<LocalExpr> {
+        self.kind()
+    }
+
+    fn addr(&self) -> *const () {
+        Rc::as_ptr(&self.inner) as *const ()
+    }
+
+    fn is_shared(&self) -> bool {
+        self.ref_count() > 1
+    }
+}
+
+/// Take the `ExprKind` stored in `kind`, leaving behind a placeholder atom.
+fn take_kind(kind: &mut ExprKind<LocalExpr>) -> ExprKind<LocalExpr> {
+    mem::replace(kind, ExprKind::Integer(0))
+}
+
+/// Drop the subexpressions of a normal expression without recursion.
+///
+/// See the `Drop` impl for `ExprNode`.
+impl Drop for LocalExpr {
+    fn drop(&mut self) {
+        if !matches!(*self.inner, ExprKind::Normal(_)) {
+            return;
+        }
+
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+
+        while let Some(mut expr) = stack.pop() {
+            // If this was the last reference to `expr`, its kind is replaced by an atom,
+            // so dropping it does not recurse.
+            expr.take_children(&mut stack);
+        }
+    }
+}
+
+//=======================================
+// Conversion trait impl's
+//=======================================
+
+impl From<&Expr> for LocalExpr {
+    fn from(expr: &Expr) -> LocalExpr {
+        expr.to_local()
+    }
+}
+
+impl From<&LocalExpr> for Expr {
+    fn from(expr: &LocalExpr) -> Expr {
+        expr.to_expr()
+    }
+}
+
+impl From<Symbol> for LocalExpr {
+    fn from(sym: Symbol) -> LocalExpr {
+        LocalExpr::symbol(sym)
+    }
+}
+
+impl From<&Symbol> for LocalExpr {
+    fn from(sym: &Symbol) -> LocalExpr {
+        LocalExpr::symbol(sym)
+    }
+}
+
+impl From<Normal<LocalExpr>> for LocalExpr {
+    fn from(normal: Normal<LocalExpr>) -> LocalExpr {
+        LocalExpr::new(ExprKind::Normal(normal))
+    }
+}
+
+impl From<Number> for LocalExpr {
+    fn from(num: Number) -> LocalExpr {
+        LocalExpr::number(num)
+    }
+}
+
+impl From<bool> for LocalExpr {
+    fn from(value: bool) -> LocalExpr {
+        match value {
+            true => LocalExpr::symbol(crate::symbol!("System`True")),
+            false => LocalExpr::symbol(crate::symbol!("System`False")),
+        }
+    }
+}
+
+macro_rules! string_like {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for LocalExpr {
+                fn from(s: $t) -> LocalExpr {
+                    LocalExpr::string(s)
+                }
+            }
+        )*
+    }
+}
+
+string_like!(&str, &String, String);
+
+macro_rules! integer_like {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for LocalExpr {
+                fn from(int: $t) -> LocalExpr {
+                    LocalExpr::number(Number::Integer(i64::from(int)))
+                }
+            }
+        )*
+    }
+}
+
+integer_like!(u8, i8, u16, i16, u32, i32, i64);
+
+//=======================================
+// Display & Debug impl/s
+//=======================================
+
+impl fmt::Debug for LocalExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Formats the same way as the [`Display`][fmt::Display] impl for [`Expr`].
+impl fmt::Display for LocalExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_iteratively(f, vec![FmtTask::Expr(self)])
+    }
+}
+
+impl fmt::Display for ExprKind<LocalExpr> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_kind(self, f)
+    }
+}
+
+impl fmt::Debug for ExprKind<LocalExpr> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Normal<LocalExpr> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_normal(self, f)
+    }
+}
+
+//======================================
+// Comparision trait impls
+//======================================
+
+impl PartialEq for LocalExpr {
+    fn eq(&self, other: &LocalExpr) -> bool {
+        eq_iteratively(self, other)
+    }
+}
+
+impl Eq for LocalExpr {}
+
+impl Hash for LocalExpr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_iteratively(self, state)
+    }
+}
+
+impl PartialEq<Symbol> for LocalExpr {
+    fn eq(&self, other: &Symbol) -> bool {
+        match self.kind() {
+            ExprKind::Symbol(self_sym) => self_sym == other,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Macros for constructing expressions.
+
+/// Construct a [`Symbol`][crate::Symbol] from a string that is validated at compile
+/// time.
+///
+/// The string must be a constant absolute symbol, like `` "System`List" ``. Unlike
+/// [`Symbol::new()`][crate::Symbol::new], which parses its argument every time it is
+/// called and panics on invalid input, `symbol!` checks the string once during
+/// compilation. At runtime the symbol is constructed without any validation, and
+/// without a heap allocation for names of up to
+/// [`SmallString::INLINE_CAPACITY`][crate::SmallString::INLINE_CAPACITY] bytes.
+///
+/// Only ASCII symbols are supported. Use [`Symbol::new()`][crate::Symbol::new] for
+/// symbols that contain non-ASCII letters.
+///
+/// # Examples
+///
+/// ```
+/// use wolfram_expr::{symbol, Symbol};
+///
+/// let list: Symbol = symbol!("System`List");
+///
+/// assert_eq!(list, Symbol::new("System`List"));
+/// ```
+///
+/// Invalid symbols are rejected at compile time:
+///
+/// ```compile_fail
+/// use wolfram_expr::symbol;
+///
+/// let list = symbol!("List");
+/// ```
+#[macro_export]
+macro_rules! symbol {
+    ($symbol:expr) => {{
+        const SYMBOL: $crate::symbol::SymbolRef<'static> =
+            $crate::__private::symbol_ref($symbol);
+        SYMBOL.to_symbol()
+    }};
+}
+
+/// Construct a [`Context`][crate::symbol::Context] from a string that is validated at
+/// compile time.
+///
+/// The string must be a constant absolute context, like `` "Global`" ``. See
+/// [`symbol!`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use wolfram_expr::{context, symbol::Context};
+///
+/// assert_eq!(context!("Global`"), Context::global());
+/// assert_eq!(context!("MyPackage`Private`").as_str(), "MyPackage`Private`");
+/// ```
+///
+/// Invalid contexts are rejected at compile time:
+///
+/// ```compile_fail
+/// use wolfram_expr::context;
+///
+/// let global = context!("Global");
+/// ```
+#[macro_export]
+macro_rules! context {
+    ($context:expr) => {{
+        const CONTEXT: $crate::symbol::ContextRef<'static> =
+            $crate::__private::context_ref($context);
+        CONTEXT.to_context()
+    }};
+}
+
+/// Construct an [`Expr`][crate::Expr] using Wolfram Language-like syntax.
+///
+/// # Syntax
+///
+/// Syntax                  | Expression
+/// ------------------------|-----------------------------------------------------------
+/// `Plus[x, 1]`            | normal expression
+/// `Derivative[1][f]`      | normal expression with a normal head
+/// `{a, b, c}`             | `List[a, b, c]`
+/// `5`, `-5`, `2.5`, `"s"` | integer, real, and string literals
+/// `x`                     | symbol in the default context, e.g. `` System`x ``
+/// `Global::x`             | symbol with an explicit context, e.g. `` Global`x ``
+/// `#value`                | the value of the Rust variable `value`
+/// `#(value)`              | the value of the Rust expression `value`
+///
+/// Names without an explicit context are resolved in the `` System` `` context. A
+/// different default context can be specified by starting the macro input with
+/// `` context = "MyContext`"; ``. Symbols are resolved and validated at compile time,
+/// using [`symbol!`].
+///
+/// Interpolated values can be of any type that implements `Into<Expr>`. Interpolated
+/// values are moved into the expression; interpolate `#(value.clone())` to keep using
+/// `value` afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use wolfram_expr::wl;
+///
+/// let expr = wl!(Plus[x, 1, {a, "s", 2.5}]);
+///
+/// assert_eq!(
+///     expr.to_string(),
+///     r#"System`Plus[System`x, 1, System`List[System`a, "s", 2.5]]"#
+/// );
+/// ```
+///
+/// Interpolate Rust values, and choose a different default context:
+///
+/// ```
+/// use wolfram_expr::{wl, Expr};
+///
+/// let n = 5;
+/// let elements = Expr::list(vec![Expr::from(1), Expr::from(2)]);
+///
+/// let expr = wl!(context = "Global`"; f[#n, #elements, System::Automatic]);
+///
+/// assert_eq!(
+///     expr.to_string(),
+///     "Global`f[5, System`List[1, 2], System`Automatic]"
+/// );
+/// ```
+///
+/// # Limitations
+///
+/// Symbol names must be valid Rust identifiers, so names containing `$` cannot be
+/// written directly; use `#(symbol!("System`$Context"))` instead. Very large
+/// literal expressions may require increasing the crate's `#![recursion_limit]`.
+#[macro_export]
+macro_rules! wl {
+    (context = $context:literal; $($expr:tt)+) => {
+        $crate::__wl!(@elems one $context; [] $($expr)+)
+    };
+    ($($expr:tt)+) => {
+        $crate::__wl!(@elems one "System`"; [] $($expr)+)
+    };
+}
+
+/// Implementation of [`wl!`].
+///
+/// `@elems` parses a comma-separated sequence of expressions, accumulating each parsed
+/// expression in the `[...]` group. In `list` mode this produces a `Vec<Expr>`, and in
+/// `one` mode it produces the single expression.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __wl {
+    //==================================
+    // Sequences of expressions
+    //==================================
+
+    (@elems list $ctx:literal; [$($done:tt)*]) => {
+        ::std::vec![$($done)*]
+    };
+    (@elems one $ctx:literal; [($($done:tt)*),]) => {
+        $($done)*
+    };
+
+    // #var[...]
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        # $value:ident $([$($args:tt)*])* $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)* ($crate::__wl!(@apply $ctx;
+            (::std::convert::Into::<$crate::Expr>::into($value)) $([$($args)*])*
+        )),] $($($rest)*)?)
+    };
+    // #(expr)[...]
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        # ($value:expr) $([$($args:tt)*])* $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)* ($crate::__wl!(@apply $ctx;
+            (::std::convert::Into::<$crate::Expr>::into($value)) $([$($args)*])*
+        )),] $($($rest)*)?)
+    };
+    // -5, -2.5
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        - $value:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)*
+            ($crate::__private::literal(-$value)),
+        ] $($($rest)*)?)
+    };
+    // 5, 2.5, "s"
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        $value:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)*
+            ($crate::__private::literal($value)),
+        ] $($($rest)*)?)
+    };
+    // {...}
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        {$($elements:tt)*} $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)*
+            ($crate::Expr::list($crate::__wl!(@elems list $ctx; [] $($elements)*))),
+        ] $($($rest)*)?)
+    };
+    // name[...], Context::name[...]
+    (@elems $mode:ident $ctx:literal; [$($done:tt)*]
+        $name:ident $(:: $names:ident)* $([$($args:tt)*])* $(, $($rest:tt)*)?
+    ) => {
+        $crate::__wl!(@elems $mode $ctx; [$($done)* ($crate::__wl!(@apply $ctx;
+            ($crate::Expr::symbol($crate::__wl!(@symbol $ctx; $name $(:: $names)*)))
+            $([$($args)*])*
+        )),] $($($rest)*)?)
+    };
+
+    //==================================
+    // Helpers
+    //==================================
+
+    // Apply `head` to each sequence of bracketed arguments in turn.
+    (@apply $ctx:literal; ($($head:tt)*)) => {
+        $($head)*
+    };
+    (@apply $ctx:literal; ($($head:tt)*) [$($args:tt)*] $($more:tt)*) => {
+        $crate::__wl!(@apply $ctx;
+            ($crate::Expr::normal($($head)*, $crate::__wl!(@elems list $ctx; [] $($args)*)))
+            $($more)*
+        )
+    };
+
+    (@symbol $ctx:literal; $name:ident) => {
+        $crate::symbol!(::std::concat!($ctx, ::std::stringify!($name)))
+    };
+    (@symbol $ctx:literal; $context:ident $(:: $names:ident)+) => {
+        $crate::symbol!(::std::concat!(
+            ::std::stringify!($context) $(, "`", ::std::stringify!($names))+
+        ))
+    };
+}
+
+/// Items used by the expansions of macros exported by this crate. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{
+        symbol::{parse, ContextRef, SymbolRef},
+        Expr,
+    };
+
+    /// Validate a symbol for [`symbol!`]. Panics, at compile time, if `string` is not a
+    /// valid absolute symbol.
+    pub const fn symbol_ref(string: &'static str) -> SymbolRef<'static> {
+        if !parse::is_absolute_symbol(string) {
+            panic!("symbol!: string is not a valid absolute symbol");
+        }
+
+        // SAFETY: `string` was validated above.
+        unsafe { SymbolRef::unchecked_new(string) }
+    }
+
+    /// Validate a context for [`context!`]. Panics, at compile time, if `string` is not
+    /// a valid absolute context.
+    pub const fn context_ref(string: &'static str) -> ContextRef<'static> {
+        if !parse::is_absolute_context(string) {
+            panic!("context!: string is not a valid absolute context");
+        }
+
+        // SAFETY: `string` was validated above.
+        unsafe { ContextRef::unchecked_new(string) }
+    }
+
+    /// Rust literal that can appear in [`wl!`].
+    pub trait Literal {
+        fn into_expr(self) -> Expr;
+    }
+
+    pub fn literal<T: Literal>(value: T) -> Expr {
+        value.into_expr()
+    }
+
+    macro_rules! from_literal {
+        ($($t:ty),*) => {
+            $(
+                impl Literal for $t {
+                    fn into_expr(self) -> Expr {
+                        Expr::from(self)
+                    }
+                }
+            )*
+        }
+    }
+
+    from_literal!(i8, u8, i16, u16, i32, u32, i64, bool, &str);
+
+    impl Literal for f64 {
+        fn into_expr(self) -> Expr {
+            Expr::real(self)
+        }
+    }
+
+    impl Literal for f32 {
+        fn into_expr(self) -> Expr {
+            Expr::real(f64::from(self))
+        }
+    }
+}
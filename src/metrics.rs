@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+
+/// Structural metrics.
+///
+/// All of these methods are computed iteratively, so they can be used on arbitrarily
+/// deep expressions without overflowing the stack. Subexpressions that are shared
+/// (i.e. the same allocation appears more than once) are only traversed once.
+impl Expr {
+    /// Total number of atomic subexpressions in this expression, including heads.
+    ///
+    /// This is equivalent to
+    /// [`LeafCount`](https://reference.wolfram.com/language/ref/LeafCount.html)
+    /// <sub>WL</sub>.
+    ///
+    /// ```
+    /// # use wolfram_expr::{Expr, Symbol};
+    /// let sym = |name| Expr::symbol(Symbol::new(name));
+    ///
+    /// // f[x, g[y]]
+    /// let expr = Expr::normal(sym("Global`f"), vec![
+    ///     sym("Global`x"),
+    ///     Expr::normal(sym("Global`g"), vec![sym("Global`y")]),
+    /// ]);
+    ///
+    /// assert_eq!(expr.leaf_count(), 4);
+    /// ```
+    pub fn leaf_count(&self) -> usize {
+        fold(
+            self,
+            |_| 1,
+            |head: usize, elements| {
+                elements
+                    .iter()
+                    .fold(head, |count, elem| count.saturating_add(*elem))
+            },
+        )
+    }
+
+    /// Maximum number of indices needed to specify any part of this expression, plus
+    /// one. Heads are not counted.
+    ///
+    /// This is equivalent to
+    /// [`Depth`](https://reference.wolfram.com/language/ref/Depth.html) <sub>WL</sub>.
+    ///
+    /// ```
+    /// # use wolfram_expr::Expr;
+    /// assert_eq!(Expr::from(5).depth(), 1);
+    ///
+    /// // {1, {2}}
+    /// let expr = Expr::list(vec![Expr::from(1), Expr::list(vec![Expr::from(2)])]);
+    ///
+    /// assert_eq!(expr.depth(), 3);
+    /// ```
+    pub fn depth(&self) -> usize {
+        fold(
+            self,
+            |_| 1,
+            |_, elements: &[usize]| {
+                let max = elements.iter().copied().max().unwrap_or(0);
+                max.saturating_add(1)
+            },
+        )
+    }
+
+    /// Maximum number of subexpressions on any path from this expression to an atom,
+    /// including this expression and the atom. Heads are counted.
+    ///
+    /// Unlike [`depth()`](Expr::depth), this includes the nesting of heads. This is
+    /// the measure limited by [`Limits::max_nesting`][crate::Limits::max_nesting].
+    ///
+    /// ```
+    /// # use wolfram_expr::{Expr, Symbol};
+    /// let sym = |name| Expr::symbol(Symbol::new(name));
+    ///
+    /// assert_eq!(Expr::from(5).nesting(), 1);
+    ///
+    /// // f[a][b]
+    /// let expr = Expr::normal(Expr::normal(sym("Global`f"), vec![sym("Global`a")]), vec![
+    ///     sym("Global`b"),
+    /// ]);
+    ///
+    /// assert_eq!(expr.nesting(), 3);
+    /// assert_eq!(expr.depth(), 2);
+    /// ```
+    pub fn nesting(&self) -> usize {
+        fold(
+            self,
+            |_| 1,
+            |head, elements: &[usize]| {
+                let max = elements.iter().copied().fold(head, usize::max);
+                max.saturating_add(1)
+            },
+        )
+    }
+
+    /// Total number of subexpressions in this expression, including heads and this
+    /// expression itself.
+    ///
+    /// Shared subexpressions are counted once for every place they appear.
+    ///
+    /// ```
+    /// # use wolfram_expr::{Expr, Symbol};
+    /// let sym = |name| Expr::symbol(Symbol::new(name));
+    ///
+    /// // f[x, g[y]]
+    /// let expr = Expr::normal(sym("Global`f"), vec![
+    ///     sym("Global`x"),
+    ///     Expr::normal(sym("Global`g"), vec![sym("Global`y")]),
+    /// ]);
+    ///
+    /// assert_eq!(expr.node_count(), 6);
+    /// ```
+    pub fn node_count(&self) -> usize {
+        fold(
+            self,
+            |_| 1,
+            |head: usize, elements| {
+                elements.iter().fold(head.saturating_add(1), |count, elem| {
+                    count.saturating_add(*elem)
+                })
+            },
+        )
+    }
+
+    /// Approximate number of bytes of memory used by this expression.
+    ///
+    /// Every distinct allocation is counted once, no matter how many times it is
+    /// shared within this expression.
+    pub fn byte_count(&self) -> usize {
+        let mut bytes: usize = 0;
+
+        visit_allocations(self, |expr, first_visit| {
+            if first_visit {
+                bytes = bytes.saturating_add(allocation_size(expr));
+            }
+        });
+
+        bytes
+    }
+
+    /// Number of distinct allocations that appear more than once within this
+    /// expression.
+    ///
+    /// ```
+    /// # use wolfram_expr::Expr;
+    /// let shared = Expr::list(vec![Expr::from(1)]);
+    ///
+    /// let expr = Expr::list(vec![shared.clone(), shared.clone(), Expr::list(vec![])]);
+    ///
+    /// assert_eq!(expr.shared_node_count(), 1);
+    /// ```
+    pub fn shared_node_count(&self) -> usize {
+        let mut repeated: HashSet<*const ExprNode> = HashSet::new();
+
+        visit_allocations(self, |expr, first_visit| {
+            if !first_visit {
+                repeated.insert(Arc::as_ptr(&expr.inner));
+            }
+        });
+
+        repeated.len()
+    }
+}
+
+/// Size of the reference-counted allocation backing an [`Expr`].
+///
+/// The allocation stores the strong and weak counts alongside the `ExprNode` value.
+pub(crate) const ALLOCATION_OVERHEAD: usize =
+    2 * mem::size_of::<usize>() + mem::size_of::<ExprNode>();
+
+/// Approximate number of bytes owned directly by the allocation backing `expr`,
+/// excluding any child expressions.
+pub(crate) fn allocation_size(expr: &Expr) -> usize {
+    let owned = match expr.kind() {
+        ExprKind::Integer(_) | ExprKind::Real(_) => 0,
+        ExprKind::String(string) => string.heap_size(),
+        ExprKind::Symbol(symbol) => symbol.heap_size(),
+        ExprKind::Normal(normal) => normal.contents.capacity() * mem::size_of::<Expr>(),
+    };
+
+    ALLOCATION_OVERHEAD + owned
+}
+
+/// Compute a value for `root` bottom-up, without recursion.
+///
+/// `atom` computes the value of an atomic expression. `normal` computes the value of a
+/// normal expression from the values of its head and elements.
+///
+/// The values of shared allocations are memoized, so each allocation is only
+/// processed once.
+fn fold<T, A, N>(root: &Expr, atom: A, normal: N) -> T
+where
+    T: Copy,
+    A: Fn(&Expr) -> T,
+    N: Fn(T, &[T]) -> T,
+{
+    let mut memo: HashMap<*const ExprNode, T> = HashMap::new();
+
+    // Values of completed subexpressions that have not been consumed by their parent.
+    let mut values: Vec<T> = Vec::new();
+
+    let mut stack: Vec<(&Expr, bool)> = vec![(root, false)];
+
+    while let Some((expr, children_visited)) = stack.pop() {
+        // An allocation with only one reference cannot appear more than once.
+        let is_shared = expr.ref_count() > 1;
+        let ptr = Arc::as_ptr(&expr.inner);
+
+        if is_shared && !children_visited {
+            if let Some(value) = memo.get(&ptr) {
+                values.push(*value);
+                continue;
+            }
+        }
+
+        let value = match expr.kind() {
+            ExprKind::Normal(Normal { head, contents }) if !children_visited => {
+                stack.push((expr, true));
+
+                for elem in contents.iter().rev() {
+                    stack.push((elem, false));
+                }
+                stack.push((head, false));
+
+                continue;
+            },
+            ExprKind::Normal(Normal { contents, .. }) => {
+                let start = values.len() - contents.len() - 1;
+
+                let value = normal(values[start], &values[start + 1..]);
+                values.truncate(start);
+                value
+            },
+            ExprKind::Integer(_)
+            | ExprKind::Real(_)
+            | ExprKind::String(_)
+            | ExprKind::Symbol(_) => atom(expr),
+        };
+
+        if is_shared {
+            memo.insert(ptr, value);
+        }
+
+        values.push(value);
+    }
+
+    debug_assert!(values.len() == 1);
+
+    values
+        .pop()
+        .expect("fold: no value computed for root expression")
+}
+
+/// Call `visit` for every occurrence of every allocation in `root`, without
+/// recursion.
+///
+/// The second argument of `visit` is `true` the first time an allocation is visited.
+/// The subexpressions of an allocation are only traversed on its first visit.
+fn visit_allocations<F>(root: &Expr, mut visit: F)
+where
+    F: FnMut(&Expr, bool),
+{
+    let mut visited: HashSet<*const ExprNode> = HashSet::new();
+
+    let mut stack: Vec<&Expr> = vec![root];
+
+    while let Some(expr) = stack.pop() {
+        // An allocation with only one reference cannot appear more than once.
+        if expr.ref_count() > 1 && !visited.insert(Arc::as_ptr(&expr.inner)) {
+            visit(expr, false);
+            continue;
+        }
+
+        visit(expr, true);
+
+        if let ExprKind::Normal(Normal { head, contents }) = expr.kind() {
+            stack.push(head);
+            stack.extend(contents.iter());
+        }
+    }
+}
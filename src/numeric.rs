@@ -0,0 +1,495 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use crate::{Expr, Number, F64};
+
+
+/// Result of exact arithmetic on [`Number`]s.
+///
+/// Arithmetic follows the rules used by the Wolfram Language for machine-sized
+/// numbers:
+///
+/// * Integer arithmetic is exact. A result that does not fit in an `i64` is promoted
+///   to a machine real, instead of wrapping.
+/// * Dividing integers produces a [`Numeric::Rational`] when the result is not an
+///   integer.
+/// * If either operand is a machine real, the result is a machine real, except that
+///   multiplying by an exact zero produces an exact zero.
+/// * Dividing by zero produces [`Numeric::ComplexInfinity`], and operations with no
+///   well-defined result, like `0/0` or `0^0`, produce [`Numeric::Indeterminate`],
+///   instead of panicking.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{Number, Numeric};
+///
+/// let one = Number::Integer(1);
+/// let two = Number::Integer(2);
+///
+/// assert_eq!(one / two, Numeric::rational(1, 2));
+/// assert_eq!(one / two + Numeric::rational(1, 2), Numeric::Integer(1));
+/// assert_eq!(one / Number::Integer(0), Numeric::ComplexInfinity);
+///
+/// // Overflow promotes to a machine real.
+/// assert_eq!(Number::Integer(i64::MAX) + one, Numeric::real(9.223372036854775808e18));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Numeric {
+    /// Machine-sized integer.
+    Integer(i64),
+    /// Exact rational number that is not an integer.
+    Rational(Rational),
+    /// Machine real number.
+    Real(F64),
+    /// [`Indeterminate`](https://reference.wolfram.com/language/ref/Indeterminate.html)
+    /// <sub>WL</sub>, the result of an operation with no well-defined value.
+    Indeterminate,
+    /// [`ComplexInfinity`](https://reference.wolfram.com/language/ref/ComplexInfinity.html)
+    /// <sub>WL</sub>, the result of dividing a nonzero number by zero.
+    ComplexInfinity,
+}
+
+/// Exact rational number `numerator/denominator` that is not an integer.
+///
+/// A `Rational` is always in lowest terms, with a denominator greater than 1, so that
+/// equal values compare and hash equal. Use [`Rational::new()`] or
+/// [`Numeric::rational()`] to construct one.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{Numeric, Rational};
+///
+/// let half = Rational::new(2, -4).unwrap();
+///
+/// assert_eq!((half.numerator(), half.denominator()), (-1, 2));
+/// assert_eq!(Numeric::Rational(half), Numeric::rational(-1, 2));
+///
+/// // Integers are not rationals.
+/// assert_eq!(Rational::new(4, 2), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+/// Operand of an arithmetic operation, with exact values widened so that
+/// intermediate results cannot overflow.
+#[derive(Copy, Clone)]
+enum Operand {
+    /// `numerator/denominator`, with a positive denominator.
+    Exact(i128, i128),
+    Real(f64),
+    Indeterminate,
+    ComplexInfinity,
+}
+
+impl Numeric {
+    /// Construct a machine real, or [`Numeric::Indeterminate`] if `real` is NaN.
+    pub fn real(real: f64) -> Self {
+        match F64::new(real) {
+            Ok(real) => Numeric::Real(real),
+            Err(_) => Numeric::Indeterminate,
+        }
+    }
+
+    /// Construct the exact rational `numerator/denominator`, in lowest terms.
+    ///
+    /// Returns a [`Numeric::Integer`] if the result is an integer,
+    /// [`Numeric::ComplexInfinity`] or [`Numeric::Indeterminate`] if `denominator` is
+    /// zero, and a machine real if the result cannot be represented exactly.
+    pub fn rational(numerator: i64, denominator: i64) -> Self {
+        Numeric::Integer(numerator) / Numeric::Integer(denominator)
+    }
+
+    /// Returns `true` if this is an exact or machine real zero.
+    pub fn is_zero(self) -> bool {
+        match self {
+            Numeric::Integer(int) => int == 0,
+            Numeric::Real(real) => *real == 0.0,
+            Numeric::Rational(_) | Numeric::Indeterminate | Numeric::ComplexInfinity => {
+                false
+            },
+        }
+    }
+
+    /// Returns `true` if this is an [`Integer`][Numeric::Integer] or a
+    /// [`Rational`][Numeric::Rational].
+    pub fn is_exact(self) -> bool {
+        matches!(self, Numeric::Integer(_) | Numeric::Rational(_))
+    }
+
+    /// Convert this value to a machine real.
+    ///
+    /// Returns `None` for [`Numeric::Indeterminate`] and [`Numeric::ComplexInfinity`].
+    /// Machine reals are returned unchanged, including infinite values.
+    pub fn to_f64(self) -> Option<f64> {
+        match self {
+            Numeric::Integer(int) => Some(int as f64),
+            Numeric::Rational(rational) => Some(rational.to_f64()),
+            Numeric::Real(real) => Some(*real),
+            Numeric::Indeterminate | Numeric::ComplexInfinity => None,
+        }
+    }
+
+    /// Raise this value to an integer power.
+    ///
+    /// Exact values raised to a negative power produce exact reciprocals, and `0^0`
+    /// produces [`Numeric::Indeterminate`].
+    pub fn pow(self, exponent: i64) -> Numeric {
+        if exponent == 0 {
+            return match self {
+                Numeric::Indeterminate | Numeric::ComplexInfinity => {
+                    Numeric::Indeterminate
+                },
+                _ if self.is_zero() => Numeric::Indeterminate,
+                _ => Numeric::Integer(1),
+            };
+        }
+
+        match self {
+            Numeric::Indeterminate => Numeric::Indeterminate,
+            Numeric::ComplexInfinity if exponent > 0 => Numeric::ComplexInfinity,
+            Numeric::ComplexInfinity => Numeric::Integer(0),
+            Numeric::Real(real) => {
+                if *real == 0.0 && exponent < 0 {
+                    Numeric::ComplexInfinity
+                } else {
+                    Numeric::real(real.powf(exponent as f64))
+                }
+            },
+            Numeric::Integer(_) | Numeric::Rational(_) => {
+                let base = if exponent < 0 {
+                    Numeric::Integer(1) / self
+                } else {
+                    self
+                };
+
+                pow_exact(base, exponent.unsigned_abs())
+            },
+        }
+    }
+
+    fn to_operand(self) -> Operand {
+        match self {
+            Numeric::Integer(int) => Operand::Exact(i128::from(int), 1),
+            Numeric::Rational(rational) => Operand::Exact(
+                i128::from(rational.numerator),
+                i128::from(rational.denominator),
+            ),
+            Numeric::Real(real) => Operand::Real(*real),
+            Numeric::Indeterminate => Operand::Indeterminate,
+            Numeric::ComplexInfinity => Operand::ComplexInfinity,
+        }
+    }
+
+    fn from_operand(operand: Operand) -> Numeric {
+        match operand {
+            Operand::Exact(numerator, denominator) => {
+                let (numerator, denominator) = match normalize(numerator, denominator) {
+                    Operand::Exact(numerator, denominator) => (numerator, denominator),
+                    other => return Numeric::from_operand(other),
+                };
+
+                match (i64::try_from(numerator), i64::try_from(denominator)) {
+                    (Ok(numerator), Ok(1)) => Numeric::Integer(numerator),
+                    (Ok(numerator), Ok(denominator)) => Numeric::Rational(Rational {
+                        numerator,
+                        denominator,
+                    }),
+                    // Promote results that do not fit in machine integers.
+                    _ => Numeric::real(numerator as f64 / denominator as f64),
+                }
+            },
+            Operand::Real(real) => Numeric::real(real),
+            Operand::Indeterminate => Numeric::Indeterminate,
+            Operand::ComplexInfinity => Numeric::ComplexInfinity,
+        }
+    }
+}
+
+impl Rational {
+    /// Construct the rational number `numerator/denominator`, in lowest terms.
+    ///
+    /// Returns `None` if `denominator` is zero, if the result is an integer, or if the
+    /// result in lowest terms does not fit in `i64`s. Use [`Numeric::rational()`] to
+    /// handle those cases.
+    pub fn new(numerator: i64, denominator: i64) -> Option<Rational> {
+        match Numeric::rational(numerator, denominator) {
+            Numeric::Rational(rational) => Some(rational),
+            _ => None,
+        }
+    }
+
+    /// The numerator of this rational number.
+    pub fn numerator(self) -> i64 {
+        self.numerator
+    }
+
+    /// The denominator of this rational number, which is always greater than 1.
+    pub fn denominator(self) -> i64 {
+        self.denominator
+    }
+
+    /// Convert this value to the nearest machine real.
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+impl Number {
+    /// Raise this number to an integer power.
+    ///
+    /// See [`Numeric::pow()`].
+    pub fn pow(self, exponent: i64) -> Numeric {
+        Numeric::from(self).pow(exponent)
+    }
+}
+
+//======================================
+// Exact arithmetic
+//======================================
+
+/// Reduce `numerator/denominator` to lowest terms with a positive denominator.
+fn normalize(numerator: i128, denominator: i128) -> Operand {
+    if denominator == 0 {
+        return if numerator == 0 {
+            Operand::Indeterminate
+        } else {
+            Operand::ComplexInfinity
+        };
+    }
+
+    // Neither value can be i128::MIN, because both are computed from i64 values.
+    let divisor = gcd(numerator.abs(), denominator.abs()) * denominator.signum();
+
+    Operand::Exact(numerator / divisor, denominator / divisor)
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+
+    a
+}
+
+fn is_zero(operand: Operand) -> bool {
+    match operand {
+        Operand::Exact(numerator, _) => numerator == 0,
+        Operand::Real(real) => real == 0.0,
+        Operand::Indeterminate | Operand::ComplexInfinity => false,
+    }
+}
+
+fn to_real(numerator: i128, denominator: i128) -> f64 {
+    numerator as f64 / denominator as f64
+}
+
+fn add(a: Operand, b: Operand) -> Operand {
+    use self::Operand::*;
+
+    match (a, b) {
+        (Indeterminate, _) | (_, Indeterminate) => Indeterminate,
+        (ComplexInfinity, ComplexInfinity) => Indeterminate,
+        (ComplexInfinity, _) | (_, ComplexInfinity) => ComplexInfinity,
+        (Exact(n1, d1), Exact(n2, d2)) => normalize(n1 * d2 + n2 * d1, d1 * d2),
+        (Exact(n, d), Real(real)) | (Real(real), Exact(n, d)) => {
+            Real(to_real(n, d) + real)
+        },
+        (Real(a), Real(b)) => Real(a + b),
+    }
+}
+
+fn mul(a: Operand, b: Operand) -> Operand {
+    use self::Operand::*;
+
+    match (a, b) {
+        (Indeterminate, _) | (_, Indeterminate) => Indeterminate,
+        (ComplexInfinity, other) | (other, ComplexInfinity) => {
+            if is_zero(other) {
+                Indeterminate
+            } else {
+                ComplexInfinity
+            }
+        },
+        (Exact(n1, d1), Exact(n2, d2)) => normalize(n1 * n2, d1 * d2),
+        // An exact zero absorbs machine reals.
+        (Exact(0, _), Real(_)) | (Real(_), Exact(0, _)) => Exact(0, 1),
+        (Exact(n, d), Real(real)) | (Real(real), Exact(n, d)) => {
+            Real(to_real(n, d) * real)
+        },
+        (Real(a), Real(b)) => Real(a * b),
+    }
+}
+
+fn neg(a: Operand) -> Operand {
+    match a {
+        Operand::Exact(numerator, denominator) => Operand::Exact(-numerator, denominator),
+        Operand::Real(real) => Operand::Real(-real),
+        Operand::Indeterminate | Operand::ComplexInfinity => a,
+    }
+}
+
+fn reciprocal(a: Operand) -> Operand {
+    match a {
+        Operand::Exact(numerator, denominator) => normalize(denominator, numerator),
+        Operand::Real(0.0) => Operand::ComplexInfinity,
+        Operand::Real(real) => Operand::Real(1.0 / real),
+        Operand::Indeterminate => Operand::Indeterminate,
+        Operand::ComplexInfinity => Operand::Exact(0, 1),
+    }
+}
+
+/// Raise an exact value to a non-negative power, promoting to a machine real on
+/// overflow.
+fn pow_exact(base: Numeric, exponent: u64) -> Numeric {
+    let (numerator, denominator) = match base {
+        Numeric::Integer(int) => (int, 1),
+        Numeric::Rational(rational) => (rational.numerator, rational.denominator),
+        // Inverting an exact zero.
+        other => return other,
+    };
+
+    let parity = if exponent % 2 == 1 { -1 } else { 1 };
+
+    match (numerator, denominator) {
+        (0, _) | (1, 1) => return base,
+        (-1, 1) => return Numeric::Integer(parity),
+        _ => (),
+    }
+
+    let exact = u32::try_from(exponent).ok().and_then(|exponent| {
+        Some((
+            numerator.checked_pow(exponent)?,
+            denominator.checked_pow(exponent)?,
+        ))
+    });
+
+    match exact {
+        Some((numerator, 1)) => Numeric::Integer(numerator),
+        // Powers of coprime values are coprime, so the result is in lowest terms.
+        Some((numerator, denominator)) => Numeric::Rational(Rational {
+            numerator,
+            denominator,
+        }),
+        None => {
+            let real = to_real(i128::from(numerator), i128::from(denominator));
+            Numeric::real(real.powf(exponent as f64))
+        },
+    }
+}
+
+//======================================
+// Operator impls
+//======================================
+
+macro_rules! binary_op {
+    ($trait:ident, $method:ident, |$a:ident, $b:ident| $body:expr) => {
+        impl $trait for Numeric {
+            type Output = Numeric;
+
+            fn $method(self, other: Numeric) -> Numeric {
+                let ($a, $b) = (self.to_operand(), other.to_operand());
+                Numeric::from_operand($body)
+            }
+        }
+
+        impl $trait for Number {
+            type Output = Numeric;
+
+            fn $method(self, other: Number) -> Numeric {
+                $trait::$method(Numeric::from(self), Numeric::from(other))
+            }
+        }
+    };
+}
+
+binary_op!(Add, add, |a, b| add(a, b));
+binary_op!(Sub, sub, |a, b| add(a, neg(b)));
+binary_op!(Mul, mul, |a, b| mul(a, b));
+binary_op!(Div, div, |a, b| mul(a, reciprocal(b)));
+
+impl Neg for Numeric {
+    type Output = Numeric;
+
+    fn neg(self) -> Numeric {
+        Numeric::from_operand(neg(self.to_operand()))
+    }
+}
+
+impl Neg for Number {
+    type Output = Numeric;
+
+    fn neg(self) -> Numeric {
+        -Numeric::from(self)
+    }
+}
+
+//======================================
+// Conversions
+//======================================
+
+impl From<Number> for Numeric {
+    fn from(number: Number) -> Numeric {
+        match number {
+            Number::Integer(int) => Numeric::Integer(int),
+            Number::Real(real) => Numeric::Real(real),
+        }
+    }
+}
+
+impl From<i64> for Numeric {
+    fn from(int: i64) -> Numeric {
+        Numeric::Integer(int)
+    }
+}
+
+/// `Rational`, `Indeterminate` and `ComplexInfinity` are converted to the equivalent
+/// Wolfram Language expressions. Infinite machine reals are converted to
+/// `DirectedInfinity[1]` or `DirectedInfinity[-1]`.
+impl From<Numeric> for Expr {
+    fn from(numeric: Numeric) -> Expr {
+        match numeric {
+            Numeric::Integer(int) => Expr::from(int),
+            Numeric::Rational(rational) => {
+                Expr::normal(crate::symbol!("System`Rational"), vec![
+                    Expr::from(rational.numerator),
+                    Expr::from(rational.denominator),
+                ])
+            },
+            Numeric::Real(real) => Expr::from(*real),
+            Numeric::Indeterminate => {
+                Expr::symbol(crate::symbol!("System`Indeterminate"))
+            },
+            Numeric::ComplexInfinity => {
+                Expr::symbol(crate::symbol!("System`ComplexInfinity"))
+            },
+        }
+    }
+}
+
+impl fmt::Display for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Numeric::Integer(int) => write!(f, "{}", int),
+            Numeric::Rational(rational) => write!(f, "{}", rational),
+            Numeric::Real(real) => write!(f, "{}", Number::Real(real)),
+            Numeric::Indeterminate => write!(f, "Indeterminate"),
+            Numeric::ComplexInfinity => write!(f, "ComplexInfinity"),
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     sync::Arc,
 };
 
-use crate::{Expr, ExprKind};
+use crate::{Expr, ExprNode};
 
 
 /// [`Expr`] wrapper that compares by reference instead of by value.
@@ -39,7 +39,7 @@ pub struct ExprRefCmp(pub Expr);
 impl Hash for ExprRefCmp {
     fn hash<H: Hasher>(&self, state: &mut H) {
         let ExprRefCmp(Expr { inner }) = self;
-        let ptr: *const ExprKind = Arc::as_ptr(inner);
+        let ptr: *const ExprNode = Arc::as_ptr(inner);
         ptr.hash(state);
     }
 }
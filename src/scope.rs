@@ -0,0 +1,1411 @@
+//! Lexical scoping constructs.
+//!
+//! The following constructs bind local symbols, whose scope is part of the
+//! construct:
+//!
+//! Construct                            | Bound symbols      | Scope
+//! -------------------------------------|--------------------|--------------------
+//! `Function[{x, ...}, body]`           | `x, ...`           | `body`
+//! `With[{x = value, ...}, body]`       | `x, ...`           | `body`
+//! `Module[{x, y = value, ...}, body]`  | `x, y, ...`        | `body`
+//! `Block[{x, y = value, ...}, body]`   | `x, y, ...`        | `body`
+//! `Table[body, {i, ...}, ...]`         | `i, ...`           | `body` and iterators
+//! `lhs -> rhs`, `lhs :> rhs`           | Names of `x_` etc. | `lhs` and `rhs`
+//!
+//! The initial values in the declarations of `With`, `Module` and `Block` are outside
+//! the scope of the bound symbols, like the heads of the declarations (`List`, `Set`
+//! and `SetDelayed`) and of the iterators. The variable of an iterator is only bound
+//! in the body and in the later iterators, so the bounds of `{i, i}` refer to the
+//! outer `i`. `Do`, `Sum` and `Product` bind their iterator variables like `Table`.
+//!
+//! [`substitute()`] replaces the free occurrences of symbols in an expression, renaming
+//! bound symbols where necessary to avoid capturing symbols in the replacement values.
+//! [`apply_with()`] uses it to expand a
+//! [`With`](https://reference.wolfram.com/language/ref/With.html) <sub>WL</sub>
+//! expression, and [`SymbolGenerator::apply_module()`] to expand a
+//! [`Module`](https://reference.wolfram.com/language/ref/Module.html) <sub>WL</sub>
+//! expression, by giving its local symbols unique names like `x$1`.
+//!
+//! [`free_symbols()`], [`occurs_free()`], [`bound_symbols()`] and
+//! [`symbol_occurrences()`] determine which symbols in an expression are free and which
+//! are bound. Like [`substitute()`], they walk the expression once without recursion,
+//! so they can be used on arbitrarily deep expressions.
+//!
+//! # Example
+//!
+//! ```
+//! use wolfram_expr::{scope::apply_with, wl};
+//!
+//! // With[{x = y}, Function[{y}, x + y]]
+//! let with = wl!(With[
+//!     {Set[Global::x, Global::y]},
+//!     Function[{Global::y}, Plus[Global::x, Global::y]]
+//! ]);
+//!
+//! // The parameter y is renamed so that it does not capture the value of x.
+//! assert_eq!(
+//!     apply_with(&with)?.to_string(),
+//!     "System`Function[System`List[Global`y$], System`Plus[Global`y, Global`y$]]"
+//! );
+//! # Ok::<(), wolfram_expr::scope::ScopeError>(())
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt, mem,
+    rc::Rc,
+};
+
+use crate::{ptr_cmp::ExprRefCmp, Expr, ExprKind, Normal, Symbol};
+
+
+/// Error returned when a scoping construct is not valid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScopeError {
+    /// The expression does not have the expected head, or its declarations are not a
+    /// list of symbols and `symbol = value` assignments.
+    InvalidConstruct(Expr),
+}
+
+/// Occurrence of a symbol in an expression, returned by [`symbol_occurrences()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolOccurrence {
+    /// Position of the occurrence, as a list of indices in the style of
+    /// [`Position`](https://reference.wolfram.com/language/ref/Position.html)
+    /// <sub>WL</sub>, where `0` is the head and `n` is the `n`-th element.
+    pub position: Vec<usize>,
+    /// Whether the occurrence is free or bound.
+    pub kind: OccurrenceKind,
+}
+
+/// Kind of a [`SymbolOccurrence`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OccurrenceKind {
+    /// The occurrence is not inside the scope of a construct that binds the symbol.
+    Free,
+    /// The occurrence declares the symbol as bound, like `x` in `Function[{x}, body]`
+    /// or in the pattern `x_`.
+    Binding,
+    /// The occurrence is inside the scope of a construct that binds the symbol.
+    Bound,
+}
+
+/// Generates unique symbols for the local variables of `Module`.
+///
+/// Like [`$ModuleNumber`](https://reference.wolfram.com/language/ref/$ModuleNumber.html)
+/// <sub>WL</sub>, the generator has a module number, which is appended to the names of
+/// generated symbols and is incremented each time it is used.
+#[derive(Debug, Clone)]
+pub struct SymbolGenerator {
+    module_number: u64,
+}
+
+/// Replace the free occurrences of the symbols in `bindings` in `expr` by their values.
+///
+/// Occurrences of a symbol inside a scoping construct that binds the same symbol are
+/// not replaced. A symbol bound by a scoping construct that occurs free in one of the
+/// values is renamed, like `x` to `x$`, so that it does not capture that occurrence.
+/// The new name is the first of `x$`, `x$$`, ... that does not occur anywhere in
+/// `expr` or `bindings`, and that does not occur free in a value substituted into the
+/// construct, so it can not collide with an existing symbol.
+///
+/// The result is not evaluated. The substitution is done without recursion, so this
+/// can be used on arbitrarily deep expressions without overflowing the stack.
+pub fn substitute(expr: &Expr, bindings: &[(Symbol, Expr)]) -> Expr {
+    if bindings.is_empty() {
+        return expr.clone();
+    }
+
+    Substitution::new(expr, bindings).run(expr, bindings)
+}
+
+/// Expand `With[{x = value, ...}, body]`, by substituting each value for the
+/// corresponding symbol in `body` using [`substitute()`].
+///
+/// Both `x = value` and `x := value` declarations are supported.
+pub fn apply_with(with: &Expr) -> Result<Expr, ScopeError> {
+    let invalid = || ScopeError::InvalidConstruct(with.clone());
+
+    let (declarations, body) =
+        parse_construct(with, "System`With").ok_or_else(invalid)?;
+
+    let bindings = declarations
+        .into_iter()
+        .map(|declaration| {
+            let (_, value) = declaration.assignment?;
+            Some((declaration.symbol, value.clone()))
+        })
+        .collect::<Option<Vec<(Symbol, Expr)>>>()
+        .ok_or_else(invalid)?;
+
+    Ok(substitute(body, &bindings))
+}
+
+/// Returns `true` if `symbol` occurs in `expr` outside the scope of any construct that
+/// binds it.
+pub fn occurs_free(expr: &Expr, symbol: &Symbol) -> bool {
+    let mut free = false;
+
+    walk_symbols(expr, Some(symbol), |found, _| match found {
+        Found::Occurrence(other, false) if other == symbol => {
+            free = true;
+            false
+        },
+        _ => true,
+    });
+
+    free
+}
+
+/// Returns the symbols bound by the scoping constructs in `expr`, in the order they
+/// first appear.
+pub fn bound_symbols(expr: &Expr) -> Vec<Symbol> {
+    Scopes::default().bound_symbols(expr)
+}
+
+/// Returns the symbols that occur free in `expr`, in the order they first appear.
+///
+/// Heads are included, so the free symbols of `Plus[x, 1]` are `Plus` and `x`.
+///
+/// # Example
+///
+/// ```
+/// use wolfram_expr::{scope::free_symbols, wl, Symbol};
+///
+/// // Table[f[i, n], {i, n}]
+/// let expr = wl!(Table[Global::f[Global::i, Global::n], {Global::i, Global::n}]);
+///
+/// assert_eq!(free_symbols(&expr), vec![
+///     Symbol::new("System`Table"),
+///     Symbol::new("Global`f"),
+///     Symbol::new("Global`n"),
+///     Symbol::new("System`List"),
+/// ]);
+/// ```
+pub fn free_symbols(expr: &Expr) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    let mut seen = HashSet::new();
+
+    walk_symbols(expr, None, |found, _| {
+        if let Found::Occurrence(symbol, false) = found {
+            if !seen.contains(symbol) {
+                seen.insert(symbol.clone());
+                symbols.push(symbol.clone());
+            }
+        }
+        true
+    });
+
+    symbols
+}
+
+/// Returns the occurrences of `symbol` in `expr`, in depth-first order, and whether
+/// each occurrence is free or bound.
+pub fn symbol_occurrences(expr: &Expr, symbol: &Symbol) -> Vec<SymbolOccurrence> {
+    let mut occurrences = Vec::new();
+    let mut bindings = HashSet::new();
+
+    walk_symbols(expr, Some(symbol), |found, position| {
+        match found {
+            Found::Occurrence(other, bound) if other == symbol => {
+                occurrences.push(SymbolOccurrence {
+                    position: position.to_vec(),
+                    kind: if bound {
+                        OccurrenceKind::Bound
+                    } else {
+                        OccurrenceKind::Free
+                    },
+                })
+            },
+            Found::Binding(other, path) if other == symbol => {
+                bindings.insert([position, path].concat());
+            },
+            Found::Occurrence(..) | Found::Binding(..) => (),
+        }
+        true
+    });
+
+    // Declarations inside the scope, like pattern names, were visited as bound
+    // occurrences.
+    occurrences.retain(|occurrence| !bindings.contains(&occurrence.position));
+    occurrences.extend(bindings.into_iter().map(|position| SymbolOccurrence {
+        position,
+        kind: OccurrenceKind::Binding,
+    }));
+
+    occurrences.sort_by(|a, b| a.position.cmp(&b.position));
+    occurrences
+}
+
+impl SymbolGenerator {
+    /// Construct a generator with a module number of 1.
+    pub fn new() -> Self {
+        SymbolGenerator::starting_at(1)
+    }
+
+    /// Construct a generator with the given module number.
+    pub fn starting_at(module_number: u64) -> Self {
+        SymbolGenerator { module_number }
+    }
+
+    /// Get the module number that will be used for the next generated symbol.
+    pub fn module_number(&self) -> u64 {
+        self.module_number
+    }
+
+    /// Generate the symbol `x$n` for the symbol `x`, where `n` is the current module
+    /// number, and increment the module number.
+    pub fn unique_symbol(&mut self, symbol: &Symbol) -> Symbol {
+        let number = self.next_module_number();
+        local_symbol(symbol, number)
+    }
+
+    /// Expand `Module[{x, y = value, ...}, body]`.
+    ///
+    /// Every local symbol is renamed to `x$n` in `body` using [`substitute()`], where
+    /// `n` is the current module number, which is then incremented. If any local symbol
+    /// has an initial value, the result is
+    /// `CompoundExpression[x$n = value, ..., body]`, which assigns the initial values
+    /// when it is evaluated. Otherwise, the result is the renamed `body`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wolfram_expr::{scope::SymbolGenerator, wl, Expr, Symbol};
+    ///
+    /// let mut generator = SymbolGenerator::starting_at(7);
+    ///
+    /// // Module[{x = 1, y}, f[x, y]]
+    /// let module = wl!(Module[
+    ///     {Set[Global::x, 1], Global::y},
+    ///     Global::f[Global::x, Global::y]
+    /// ]);
+    ///
+    /// let x7 = Expr::symbol(Symbol::new("Global`x$7"));
+    /// let y7 = Expr::symbol(Symbol::new("Global`y$7"));
+    ///
+    /// assert_eq!(
+    ///     generator.apply_module(&module)?,
+    ///     wl!(CompoundExpression[Set[#(x7.clone()), 1], Global::f[#x7, #y7]])
+    /// );
+    /// assert_eq!(generator.module_number(), 8);
+    /// # Ok::<(), wolfram_expr::scope::ScopeError>(())
+    /// ```
+    pub fn apply_module(&mut self, module: &Expr) -> Result<Expr, ScopeError> {
+        let (declarations, body) = parse_construct(module, "System`Module")
+            .ok_or_else(|| ScopeError::InvalidConstruct(module.clone()))?;
+
+        let number = self.next_module_number();
+
+        let renames: Vec<(Symbol, Expr)> = declarations
+            .iter()
+            .map(|declaration| {
+                let local = local_symbol(&declaration.symbol, number);
+                (declaration.symbol.clone(), Expr::symbol(local))
+            })
+            .collect();
+
+        let mut elements: Vec<Expr> = declarations
+            .iter()
+            .zip(&renames)
+            .filter_map(|(declaration, (_, local))| {
+                let (assignment, value) = declaration.assignment?;
+                Some(Expr::normal(assignment.clone(), vec![
+                    local.clone(),
+                    value.clone(),
+                ]))
+            })
+            .collect();
+
+        let body = substitute(body, &renames);
+
+        if elements.is_empty() {
+            return Ok(body);
+        }
+
+        elements.push(body);
+
+        Ok(Expr::normal(
+            crate::symbol!("System`CompoundExpression"),
+            elements,
+        ))
+    }
+
+    fn next_module_number(&mut self) -> u64 {
+        let number = self.module_number;
+        self.module_number += 1;
+        number
+    }
+}
+
+impl Default for SymbolGenerator {
+    fn default() -> Self {
+        SymbolGenerator::new()
+    }
+}
+
+//======================================
+// Scoping constructs
+//======================================
+
+/// Callbacks used by [`map_scope()`] to rebuild a scoping construct.
+///
+/// Every bound symbol is declared before any part of the construct that is inside its
+/// scope is visited.
+///
+/// Each callback is passed the position of the part within the construct, as a list of
+/// indices in the style of
+/// [`Position`](https://reference.wolfram.com/language/ref/Position.html) <sub>WL</sub>.
+trait ScopeVisitor {
+    /// Called for each occurrence of a symbol that declares a symbol bound by the
+    /// construct.
+    fn variable(&mut self, symbol: &Symbol, position: &[usize]) -> Symbol;
+    /// Called for the left-hand side of a rule, before its parts are visited, to
+    /// declare the pattern names in it. Returns `false` if there are none, in which
+    /// case the rule is not a scoping construct.
+    ///
+    /// The pattern names are part of the scope, so they are renamed by
+    /// [`ScopeVisitor::inner()`] along with the rest of the left-hand side.
+    fn patterns(&mut self, lhs: &Expr) -> bool;
+    /// Called for each part of the construct that is outside the scope of the bound
+    /// symbols.
+    fn outer(&mut self, expr: &Expr, position: &[usize]) -> Expr;
+    /// Called for each part of the construct that is inside the scope of the bound
+    /// symbols.
+    fn inner(&mut self, expr: &Expr, position: &[usize]) -> Expr;
+    /// Called for each part of the construct that is only inside the scope of the
+    /// symbols declared by the first `declarations` calls to
+    /// [`ScopeVisitor::variable()`], like the bounds of an iterator.
+    fn partial(&mut self, expr: &Expr, position: &[usize], declarations: usize) -> Expr;
+}
+
+/// If `normal` is a scoping construct, rebuild it by calling `visitor` for each of its
+/// parts.
+fn map_scope(normal: &Normal, visitor: &mut dyn ScopeVisitor) -> Option<Expr> {
+    let name = normal.head().try_as_symbol()?.as_str();
+
+    let elements = match (name, normal.elements()) {
+        ("System`Function", [parameters, body, rest @ ..])
+            if rest.len() <= 1 && *parameters != Expr::null() =>
+        {
+            let parameters = match parameters.kind() {
+                ExprKind::Symbol(symbol) => Expr::symbol(visitor.variable(symbol, &[1])),
+                ExprKind::Normal(list)
+                    if list.has_head(&crate::symbol!("System`List")) =>
+                {
+                    let head = visitor.outer(list.head(), &[1, 0]);
+                    let parameters = list
+                        .elements()
+                        .iter()
+                        .enumerate()
+                        .map(|(index, parameter)| {
+                            let symbol = parameter.try_as_symbol()?;
+                            Some(Expr::symbol(visitor.variable(symbol, &[1, index + 1])))
+                        })
+                        .collect::<Option<Vec<Expr>>>()?;
+                    Expr::normal(head, parameters)
+                },
+                _ => return None,
+            };
+
+            let mut elements = vec![parameters, visitor.inner(body, &[2])];
+            elements.extend(rest.iter().map(|element| visitor.outer(element, &[3])));
+            elements
+        },
+        ("System`With" | "System`Module" | "System`Block", [declarations, body]) => {
+            let list = declarations.try_as_normal()?;
+            let declarations = parse_declarations(declarations)?;
+
+            let head = visitor.outer(list.head(), &[1, 0]);
+            let declarations = declarations
+                .into_iter()
+                .enumerate()
+                .map(|(index, declaration)| match declaration.assignment {
+                    Some((assignment, value)) => {
+                        let assignment = visitor.outer(assignment, &[1, index + 1, 0]);
+                        let symbol =
+                            visitor.variable(&declaration.symbol, &[1, index + 1, 1]);
+                        Expr::normal(assignment, vec![
+                            Expr::symbol(symbol),
+                            visitor.outer(value, &[1, index + 1, 2]),
+                        ])
+                    },
+                    None => Expr::symbol(
+                        visitor.variable(&declaration.symbol, &[1, index + 1]),
+                    ),
+                })
+                .collect();
+
+            vec![Expr::normal(head, declarations), visitor.inner(body, &[2])]
+        },
+        // Each iterator variable is bound in the body and in the later iterators, so
+        // that they can depend on earlier ones, like `{j, i}` in
+        // `Table[f[i, j], {i, 3}, {j, i}]`. The bounds of an iterator are outside the
+        // scope of its own variable.
+        (
+            "System`Table" | "System`Do" | "System`Sum" | "System`Product",
+            [body, iterators @ ..],
+        ) => {
+            if iterators
+                .iter()
+                .all(|iterator| iterator_variable(iterator).is_none())
+            {
+                return None;
+            }
+
+            let variables: Vec<Option<Symbol>> = iterators
+                .iter()
+                .enumerate()
+                .map(|(index, iterator)| {
+                    let variable = iterator_variable(iterator)?;
+                    Some(visitor.variable(variable, &[index + 2, 1]))
+                })
+                .collect();
+
+            let mut elements = vec![visitor.inner(body, &[1])];
+
+            // Number of variables declared by the earlier iterators.
+            let mut declared = 0;
+
+            for (index, iterator) in iterators.iter().enumerate() {
+                let position = index + 2;
+                let variable = variables[index].clone();
+
+                let (list, variable) = match (iterator.try_as_normal(), variable) {
+                    (Some(list), Some(variable)) => (list, variable),
+                    _ => {
+                        elements.push(visitor.partial(iterator, &[position], declared));
+                        continue;
+                    },
+                };
+
+                let head = visitor.partial(list.head(), &[position, 0], declared);
+
+                let mut list_elements = vec![Expr::symbol(variable)];
+                list_elements.extend(list.elements()[1..].iter().enumerate().map(
+                    |(bound_index, bound)| {
+                        visitor.partial(bound, &[position, bound_index + 2], declared)
+                    },
+                ));
+
+                elements.push(Expr::normal(head, list_elements));
+                declared += 1;
+            }
+
+            elements
+        },
+        // The pattern names in the left-hand side of a rule are bound in the whole
+        // rule.
+        ("System`Rule" | "System`RuleDelayed", [lhs, rhs]) => {
+            if !visitor.patterns(lhs) {
+                return None;
+            }
+
+            vec![visitor.inner(lhs, &[1]), visitor.inner(rhs, &[2])]
+        },
+        _ => return None,
+    };
+
+    Some(Expr::normal(normal.head().clone(), elements))
+}
+
+/// Parts of a scoping construct, in the order they are passed to a [`ScopeVisitor`].
+struct ScopeParts {
+    /// Symbols bound by the construct, once for each declaration, with the position of
+    /// the declaration within the construct. The pattern names of a rule have no
+    /// position, since they are found while its left-hand side is visited.
+    variables: Vec<(Symbol, Option<Vec<usize>>)>,
+    parts: Vec<ScopePart>,
+    /// Whether the construct is a rule, whose first part is its left-hand side.
+    rule: bool,
+}
+
+/// Part of a scoping construct.
+struct ScopePart {
+    expr: Expr,
+    /// Position of the part within the construct.
+    position: Vec<usize>,
+    /// Number of the declarations in [`ScopeParts::variables`] whose scope contains
+    /// the part. Parts outside the scope of the bound symbols have none.
+    declarations: usize,
+}
+
+impl ScopeParts {
+    /// Symbols bound by the construct, without duplicates.
+    fn symbols(&self) -> Vec<Symbol> {
+        unique_variables(&self.variables)
+    }
+
+    /// Symbols bound by the construct whose scope contains `part`, without duplicates.
+    fn bound_in(&self, part: &ScopePart) -> Vec<Symbol> {
+        unique_variables(&self.variables[..part.declarations])
+    }
+}
+
+fn unique_variables(variables: &[(Symbol, Option<Vec<usize>>)]) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+
+    for (variable, _) in variables {
+        if !symbols.contains(variable) {
+            symbols.push(variable.clone());
+        }
+    }
+
+    symbols
+}
+
+/// Scoping constructs of an expression, so that each is only analyzed once during a
+/// traversal.
+#[derive(Default)]
+struct Scopes {
+    /// Parts of each scoping construct that has been analyzed.
+    constructs: HashMap<ExprRefCmp, Rc<ScopeParts>>,
+    /// Pattern names of the normal subexpressions of left-hand sides of rules.
+    pattern_names: PatternNames,
+}
+
+impl Scopes {
+    /// If `expr` is a scoping construct, return its parts.
+    fn parts(&mut self, expr: &Expr) -> Option<Rc<ScopeParts>> {
+        struct Parts<'a> {
+            scope: ScopeParts,
+            pattern_names: &'a mut PatternNames,
+        }
+
+        impl ScopeVisitor for Parts<'_> {
+            fn variable(&mut self, symbol: &Symbol, position: &[usize]) -> Symbol {
+                self.scope
+                    .variables
+                    .push((symbol.clone(), Some(position.to_vec())));
+                symbol.clone()
+            }
+
+            fn patterns(&mut self, lhs: &Expr) -> bool {
+                let names = find_pattern_names(lhs, self.pattern_names);
+
+                self.scope
+                    .variables
+                    .extend(names.iter().map(|name| (name.clone(), None)));
+                self.scope.rule = true;
+
+                !names.is_empty()
+            }
+
+            fn outer(&mut self, expr: &Expr, position: &[usize]) -> Expr {
+                self.partial(expr, position, 0)
+            }
+
+            fn inner(&mut self, expr: &Expr, position: &[usize]) -> Expr {
+                let declarations = self.scope.variables.len();
+                self.partial(expr, position, declarations)
+            }
+
+            fn partial(
+                &mut self,
+                expr: &Expr,
+                position: &[usize],
+                declarations: usize,
+            ) -> Expr {
+                self.scope.parts.push(ScopePart {
+                    expr: expr.clone(),
+                    position: position.to_vec(),
+                    declarations,
+                });
+                expr.clone()
+            }
+        }
+
+        let normal = expr.try_as_normal()?;
+        let key = ExprRefCmp(expr.clone());
+
+        if let Some(scope) = self.constructs.get(&key) {
+            return Some(Rc::clone(scope));
+        }
+
+        let mut visitor = Parts {
+            scope: ScopeParts {
+                variables: Vec::new(),
+                parts: Vec::new(),
+                rule: false,
+            },
+            pattern_names: &mut self.pattern_names,
+        };
+        map_scope(normal, &mut visitor)?;
+
+        let scope = Rc::new(visitor.scope);
+        self.constructs.insert(key, Rc::clone(&scope));
+        Some(scope)
+    }
+
+    /// Returns the symbols bound by the scoping constructs in `expr`, in the order they
+    /// first appear.
+    fn bound_symbols(&mut self, expr: &Expr) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = Vec::new();
+        let mut stack = vec![expr];
+
+        while let Some(expr) = stack.pop() {
+            let normal = match expr.try_as_normal() {
+                Some(normal) => normal,
+                None => continue,
+            };
+
+            if let Some(scope) = self.parts(expr) {
+                for variable in scope.symbols() {
+                    if !symbols.contains(&variable) {
+                        symbols.push(variable);
+                    }
+                }
+            }
+
+            stack.extend(normal.elements().iter().rev());
+            stack.push(normal.head());
+        }
+
+        symbols
+    }
+}
+
+/// Visitor that rebuilds a scoping construct from new values for its parts, given in
+/// the order they are returned by [`Scopes::parts()`].
+struct Rebuild<'a, I> {
+    renames: &'a [(Symbol, Symbol)],
+    values: I,
+}
+
+impl<I: Iterator<Item = Expr>> ScopeVisitor for Rebuild<'_, I> {
+    fn variable(&mut self, symbol: &Symbol, _: &[usize]) -> Symbol {
+        match self.renames.iter().find(|(variable, _)| variable == symbol) {
+            Some((_, renamed)) => renamed.clone(),
+            None => symbol.clone(),
+        }
+    }
+
+    fn patterns(&mut self, _: &Expr) -> bool {
+        // Only rules that declare pattern names are rebuilt.
+        true
+    }
+
+    fn outer(&mut self, _: &Expr, _: &[usize]) -> Expr {
+        self.values.next().expect("Rebuild: missing value for part")
+    }
+
+    fn inner(&mut self, _: &Expr, _: &[usize]) -> Expr {
+        self.values.next().expect("Rebuild: missing value for part")
+    }
+
+    fn partial(&mut self, _: &Expr, _: &[usize], _: usize) -> Expr {
+        self.values.next().expect("Rebuild: missing value for part")
+    }
+}
+
+/// Returns the variable of an iterator like `{i, max}`, `{i, min, max}`,
+/// `{i, min, max, step}` or `{i, {values...}}`.
+fn iterator_variable(iterator: &Expr) -> Option<&Symbol> {
+    let list = iterator.try_as_normal()?;
+
+    if !list.has_head(&crate::symbol!("System`List")) {
+        return None;
+    }
+
+    match list.elements() {
+        [variable, _] | [variable, _, _] | [variable, _, _, _] => {
+            variable.try_as_symbol()
+        },
+        _ => None,
+    }
+}
+
+/// Returns the names of the `Pattern[name, pattern]` subexpressions of `lhs`.
+pub(crate) fn pattern_names(lhs: &Expr) -> Vec<Symbol> {
+    find_pattern_names(lhs, &mut PatternNames::new()).to_vec()
+}
+
+/// Pattern names of normal expressions, in the order they first appear.
+type PatternNames = HashMap<ExprRefCmp, Rc<Vec<Symbol>>>;
+
+/// Returns the names of the `Pattern[name, pattern]` subexpressions of `lhs`, in the
+/// order they first appear.
+///
+/// The names in each normal subexpression are added to `memo`, so that the left-hand
+/// side of a rule nested in `lhs` is not searched again.
+fn find_pattern_names(lhs: &Expr, memo: &mut PatternNames) -> Rc<Vec<Symbol>> {
+    let mut stack: Vec<(&Expr, bool)> = vec![(lhs, false)];
+
+    while let Some((expr, elements_visited)) = stack.pop() {
+        let normal = match expr.try_as_normal() {
+            Some(normal) => normal,
+            None => continue,
+        };
+
+        let key = ExprRefCmp(expr.clone());
+
+        if memo.contains_key(&key) {
+            continue;
+        }
+
+        if !elements_visited {
+            stack.push((expr, true));
+            stack.extend(
+                normal
+                    .elements()
+                    .iter()
+                    .rev()
+                    .map(|element| (element, false)),
+            );
+            continue;
+        }
+
+        let element_names: Vec<&Rc<Vec<Symbol>>> = normal
+            .elements()
+            .iter()
+            .filter_map(|element| memo.get(&ExprRefCmp(element.clone())))
+            .filter(|names| !names.is_empty())
+            .collect();
+
+        let names = match (pattern_name(normal), element_names.as_slice()) {
+            (None, []) => Rc::default(),
+            // Share the names of the only element that has any.
+            (None, [names]) => Rc::clone(names),
+            (name, _) => {
+                let mut names: Vec<Symbol> = name.into_iter().cloned().collect();
+
+                for name in element_names.into_iter().flat_map(|names| names.iter()) {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+
+                Rc::new(names)
+            },
+        };
+
+        memo.insert(key, names);
+    }
+
+    match lhs.try_as_normal() {
+        Some(_) => Rc::clone(&memo[&ExprRefCmp(lhs.clone())]),
+        None => Rc::default(),
+    }
+}
+
+/// If `normal` is `Pattern[name, pattern]`, return `name`.
+fn pattern_name(normal: &Normal) -> Option<&Symbol> {
+    match normal.elements() {
+        [name, _] if normal.has_head(&crate::symbol!("System`Pattern")) => {
+            name.try_as_symbol()
+        },
+        _ => None,
+    }
+}
+
+/// Declaration of a local symbol, like `x` or `x = value`.
+struct Declaration<'a> {
+    symbol: Symbol,
+    /// Head (`Set` or `SetDelayed`) and value of the assignment, if the declaration
+    /// has a value.
+    assignment: Option<(&'a Expr, &'a Expr)>,
+}
+
+/// Parse the declarations of a `With` or `Module`.
+fn parse_declarations(list: &Expr) -> Option<Vec<Declaration<'_>>> {
+    let list = list.try_as_normal()?;
+
+    if !list.has_head(&crate::symbol!("System`List")) {
+        return None;
+    }
+
+    list.elements()
+        .iter()
+        .map(|declaration| match declaration.kind() {
+            ExprKind::Symbol(symbol) => Some(Declaration {
+                symbol: symbol.clone(),
+                assignment: None,
+            }),
+            ExprKind::Normal(assignment)
+                if assignment.has_head(&crate::symbol!("System`Set"))
+                    || assignment.has_head(&crate::symbol!("System`SetDelayed")) =>
+            {
+                match assignment.elements() {
+                    [symbol, value] => Some(Declaration {
+                        symbol: symbol.try_as_symbol()?.clone(),
+                        assignment: Some((assignment.head(), value)),
+                    }),
+                    _ => None,
+                }
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse `head[{declarations}, body]`.
+fn parse_construct<'a>(
+    expr: &'a Expr,
+    head: &str,
+) -> Option<(Vec<Declaration<'a>>, &'a Expr)> {
+    let normal = expr.try_as_normal()?;
+
+    if !normal.has_head(&Symbol::new(head)) {
+        return None;
+    }
+
+    match normal.elements() {
+        [declarations, body] => Some((parse_declarations(declarations)?, body)),
+        _ => None,
+    }
+}
+
+//======================================
+// Substitution
+//======================================
+
+/// Binding of a symbol to a value during [`substitute()`].
+#[derive(Clone)]
+struct Binding {
+    symbol: Symbol,
+    value: Expr,
+    /// Symbols that occur free in `value`.
+    free: Rc<HashSet<Symbol>>,
+}
+
+/// Bindings in effect for a part of the expression.
+type Environment = Rc<Vec<Binding>>;
+
+/// Step of the traversal performed by [`Substitution::run()`].
+enum Step {
+    /// Substitute the bindings into an expression, and push the result.
+    Visit(Expr, Environment),
+    /// Pop a head and `len` elements, and push the normal expression built from them.
+    Normal(usize),
+    /// Pop the new values of the parts of the scoping construct `expr`, and push the
+    /// construct rebuilt from them.
+    Scope {
+        expr: Expr,
+        renames: Vec<(Symbol, Symbol)>,
+        parts: usize,
+    },
+}
+
+/// State of a call to [`substitute()`].
+struct Substitution {
+    /// Free symbols of each scoping construct in the expression, restricted to the
+    /// symbols that can be bound during the substitution.
+    construct_free: HashMap<ExprRefCmp, BTreeSet<Symbol>>,
+    /// Symbols that occur in the expression or the bindings.
+    used: HashSet<Symbol>,
+    /// Scoping constructs in the expression, shared by each pass over it.
+    scopes: Scopes,
+}
+
+impl Substitution {
+    fn new(expr: &Expr, bindings: &[(Symbol, Expr)]) -> Self {
+        let mut used = HashSet::new();
+        collect_symbols(expr, &mut used);
+
+        for (symbol, value) in bindings {
+            used.insert(symbol.clone());
+            collect_symbols(value, &mut used);
+        }
+
+        let mut scopes = Scopes::default();
+
+        // Bindings are only made for the symbols in `bindings`, and for bound symbols
+        // that are renamed.
+        let mut candidates: HashSet<Symbol> =
+            scopes.bound_symbols(expr).into_iter().collect();
+        candidates.extend(bindings.iter().map(|(symbol, _)| symbol.clone()));
+
+        Substitution {
+            construct_free: construct_free_symbols(expr, &candidates, &mut scopes),
+            used,
+            scopes,
+        }
+    }
+
+    fn run(mut self, expr: &Expr, bindings: &[(Symbol, Expr)]) -> Expr {
+        let environment: Environment = Rc::new(
+            bindings
+                .iter()
+                .map(|(symbol, value)| Binding {
+                    symbol: symbol.clone(),
+                    value: value.clone(),
+                    free: Rc::new(free_symbols(value).into_iter().collect()),
+                })
+                .collect(),
+        );
+
+        // Results of completed steps that have not been consumed by their parent.
+        let mut values: Vec<Expr> = Vec::new();
+
+        let mut stack = vec![Step::Visit(expr.clone(), environment)];
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Visit(expr, environment) => {
+                    self.visit(expr, environment, &mut stack, &mut values)
+                },
+                Step::Normal(len) => {
+                    let elements = values.split_off(values.len() - len);
+                    let head = values.pop().expect("substitute: head was not visited");
+
+                    values.push(Expr::normal(head, elements));
+                },
+                Step::Scope {
+                    expr,
+                    renames,
+                    parts,
+                } => {
+                    let parts = values.split_off(values.len() - parts);
+                    let normal = expr
+                        .try_as_normal()
+                        .expect("substitute: scoping construct is not normal");
+
+                    let mut visitor = Rebuild {
+                        renames: &renames,
+                        values: parts.into_iter(),
+                    };
+                    let rebuilt = map_scope(normal, &mut visitor)
+                        .expect("substitute: scoping construct could not be rebuilt");
+
+                    values.push(rebuilt);
+                },
+            }
+        }
+
+        debug_assert!(values.len() == 1);
+
+        values
+            .pop()
+            .expect("substitute: no value computed for root expression")
+    }
+
+    fn visit(
+        &mut self,
+        expr: Expr,
+        environment: Environment,
+        stack: &mut Vec<Step>,
+        values: &mut Vec<Expr>,
+    ) {
+        if environment.is_empty() {
+            values.push(expr);
+            return;
+        }
+
+        let normal = match expr.kind() {
+            ExprKind::Symbol(symbol) => {
+                let value = match environment.iter().find(|b| b.symbol == *symbol) {
+                    Some(binding) => binding.value.clone(),
+                    None => expr.clone(),
+                };
+                values.push(value);
+                return;
+            },
+            ExprKind::Normal(normal) => normal,
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => {
+                values.push(expr.clone());
+                return;
+            },
+        };
+
+        let scope = match self.scopes.parts(&expr) {
+            Some(scope) => scope,
+            None => {
+                stack.push(Step::Normal(normal.elements().len()));
+
+                for element in normal.elements().iter().rev() {
+                    stack.push(Step::Visit(element.clone(), Rc::clone(&environment)));
+                }
+                stack.push(Step::Visit(normal.head().clone(), environment));
+
+                return;
+            },
+        };
+
+        let free = &self.construct_free[&ExprRefCmp(expr.clone())];
+
+        // Bindings for symbols that do not occur free in the construct are dropped, so
+        // that they do not cause unnecessary renaming.
+        let mut inner: Vec<Binding> = environment
+            .iter()
+            .filter(|binding| free.contains(&binding.symbol))
+            .cloned()
+            .collect();
+        let outer_len = inner.len();
+
+        let bound: Vec<Vec<Symbol>> = scope
+            .parts
+            .iter()
+            .map(|part| scope.bound_in(part))
+            .collect();
+
+        // Symbols bound by the construct shadow the outer bindings in their scope.
+        // Rename bound symbols that would capture a free symbol in one of the values
+        // substituted into their scope.
+        let captured: Vec<Symbol> = scope
+            .symbols()
+            .into_iter()
+            .filter(|variable| {
+                bound
+                    .iter()
+                    .filter(|bound| bound.contains(variable))
+                    .any(|bound| {
+                        inner.iter().any(|binding| {
+                            !bound.contains(&binding.symbol)
+                                && binding.free.contains(variable)
+                        })
+                    })
+            })
+            .collect();
+
+        let mut renames = Vec::with_capacity(captured.len());
+
+        for variable in captured {
+            let fresh = self.fresh(&variable, &inner);
+
+            inner.push(Binding {
+                symbol: variable.clone(),
+                value: Expr::symbol(fresh.clone()),
+                free: Rc::new(std::iter::once(fresh.clone()).collect()),
+            });
+            renames.push((variable, fresh));
+        }
+
+        stack.push(Step::Scope {
+            expr: expr.clone(),
+            renames,
+            parts: scope.parts.len(),
+        });
+
+        for (part, bound) in scope.parts.iter().zip(bound).rev() {
+            // The outer bindings for the symbols that are not bound in the part, and the
+            // renames of the symbols that are.
+            let (outer, renamed) = inner.split_at(outer_len);
+            let environment: Vec<Binding> = outer
+                .iter()
+                .filter(|binding| !bound.contains(&binding.symbol))
+                .chain(
+                    renamed
+                        .iter()
+                        .filter(|binding| bound.contains(&binding.symbol)),
+                )
+                .cloned()
+                .collect();
+
+            stack.push(Step::Visit(part.expr.clone(), Rc::new(environment)));
+        }
+    }
+
+    /// Returns the first of `x$`, `x$$`, ... that does not occur in the expression or
+    /// the bindings, and that does not occur free in the values of `inner`.
+    ///
+    /// Symbols introduced by renaming only occur in the values of bindings, so the
+    /// returned symbol can not be captured by, or capture, any other symbol.
+    fn fresh(&self, variable: &Symbol, inner: &[Binding]) -> Symbol {
+        let mut fresh = Symbol::new(&format!("{}$", variable));
+
+        while self.used.contains(&fresh)
+            || inner.iter().any(|binding| binding.free.contains(&fresh))
+        {
+            fresh = Symbol::new(&format!("{}$", fresh));
+        }
+
+        fresh
+    }
+}
+
+/// Returns the free symbols of each scoping construct in `expr`, restricted to
+/// `candidates`.
+fn construct_free_symbols(
+    expr: &Expr,
+    candidates: &HashSet<Symbol>,
+    scopes: &mut Scopes,
+) -> HashMap<ExprRefCmp, BTreeSet<Symbol>> {
+    enum Step {
+        Visit(Expr),
+        /// Pop the sets of a head and `len` elements, and push their union.
+        Normal(usize),
+        /// Pop the sets of the head and the parts of a scoping construct, and push the
+        /// free symbols of the construct, given the symbols bound in each part.
+        Scope(Expr, Vec<Vec<Symbol>>),
+    }
+
+    fn union(mut a: BTreeSet<Symbol>, mut b: BTreeSet<Symbol>) -> BTreeSet<Symbol> {
+        if a.len() < b.len() {
+            mem::swap(&mut a, &mut b);
+        }
+        a.extend(b);
+        a
+    }
+
+    let mut constructs = HashMap::new();
+
+    // Free symbols of completed subexpressions that have not been consumed by their
+    // parent.
+    let mut sets: Vec<BTreeSet<Symbol>> = Vec::new();
+
+    let mut stack = vec![Step::Visit(expr.clone())];
+
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Visit(expr) => {
+                let normal = match expr.kind() {
+                    ExprKind::Normal(normal) => normal,
+                    ExprKind::Symbol(symbol) if candidates.contains(symbol) => {
+                        sets.push(std::iter::once(symbol.clone()).collect());
+                        continue;
+                    },
+                    ExprKind::Symbol(_)
+                    | ExprKind::Integer(_)
+                    | ExprKind::Real(_)
+                    | ExprKind::String(_) => {
+                        sets.push(BTreeSet::new());
+                        continue;
+                    },
+                };
+
+                match scopes.parts(&expr) {
+                    Some(scope) => {
+                        let bound = scope
+                            .parts
+                            .iter()
+                            .map(|part| scope.bound_in(part))
+                            .collect();
+                        stack.push(Step::Scope(expr.clone(), bound));
+
+                        for part in scope.parts.iter().rev() {
+                            stack.push(Step::Visit(part.expr.clone()));
+                        }
+                    },
+                    None => {
+                        stack.push(Step::Normal(normal.elements().len()));
+
+                        for element in normal.elements().iter().rev() {
+                            stack.push(Step::Visit(element.clone()));
+                        }
+                    },
+                }
+
+                stack.push(Step::Visit(normal.head().clone()));
+            },
+            Step::Normal(len) => {
+                let free = sets
+                    .split_off(sets.len() - len - 1)
+                    .into_iter()
+                    .fold(BTreeSet::new(), union);
+                sets.push(free);
+            },
+            Step::Scope(expr, bound) => {
+                let parts = sets.split_off(sets.len() - bound.len());
+                let mut free = sets.pop().expect("construct_free_symbols: missing head");
+
+                for (mut set, bound) in parts.into_iter().zip(bound) {
+                    set.retain(|symbol| !bound.contains(symbol));
+                    free = union(free, set);
+                }
+
+                constructs.insert(ExprRefCmp(expr), free.clone());
+                sets.push(free);
+            },
+        }
+    }
+
+    constructs
+}
+
+//======================================
+// Free symbols and occurrences
+//======================================
+
+/// Symbol found by [`walk_symbols()`].
+enum Found<'a> {
+    /// Occurrence of a symbol, and whether it is inside the scope of a construct that
+    /// binds it.
+    Occurrence(&'a Symbol, bool),
+    /// Declaration of a symbol bound by a scoping construct, with the position of the
+    /// declaration within the construct, or within the `Pattern[name, pattern]`
+    /// expression for pattern names.
+    Binding(&'a Symbol, &'a [usize]),
+}
+
+/// Walk `expr` in depth-first order, calling `found` for each symbol in it, with the
+/// position of the symbol within `expr`, or of the construct for declarations.
+///
+/// If `only` is given, scoping constructs that do not bind it are walked like other
+/// normal expressions. The walk stops early if `found` returns `false`.
+///
+/// The walk is done in a single pass without recursion, keeping track of the symbols
+/// bound by the enclosing constructs.
+fn walk_symbols<F>(expr: &Expr, only: Option<&Symbol>, mut found: F)
+where
+    F: FnMut(Found, &[usize]) -> bool,
+{
+    enum Step {
+        /// Visit an expression, whose position is the first `depth` indices of the
+        /// current position followed by `path`, and whether it is part of the
+        /// left-hand side of a rule.
+        Visit(Expr, usize, Vec<usize>, bool),
+        /// Enter the scope of the symbols bound by a construct.
+        Bind(Rc<Vec<Symbol>>),
+        /// Leave the scope of the symbols bound by a construct.
+        Unbind(Rc<Vec<Symbol>>),
+    }
+
+    let mut position: Vec<usize> = Vec::new();
+
+    // Number of enclosing constructs that bind each symbol.
+    let mut bound: HashMap<Symbol, usize> = HashMap::new();
+
+    let mut scopes = Scopes::default();
+
+    let mut stack = vec![Step::Visit(expr.clone(), 0, Vec::new(), false)];
+
+    while let Some(step) = stack.pop() {
+        let (expr, lhs) = match step {
+            Step::Visit(expr, depth, path, lhs) => {
+                position.truncate(depth);
+                position.extend(path);
+                (expr, lhs)
+            },
+            Step::Bind(variables) => {
+                for variable in variables.iter() {
+                    *bound.entry(variable.clone()).or_insert(0) += 1;
+                }
+                continue;
+            },
+            Step::Unbind(variables) => {
+                for variable in variables.iter() {
+                    if let Some(count) = bound.get_mut(variable) {
+                        *count -= 1;
+
+                        if *count == 0 {
+                            bound.remove(variable);
+                        }
+                    }
+                }
+                continue;
+            },
+        };
+
+        let normal = match expr.kind() {
+            ExprKind::Symbol(symbol) => {
+                if !found(
+                    Found::Occurrence(symbol, bound.contains_key(symbol)),
+                    &position,
+                ) {
+                    return;
+                }
+                continue;
+            },
+            ExprKind::Normal(normal) => normal,
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => continue,
+        };
+
+        let depth = position.len();
+
+        // The pattern names in the left-hand side of a rule declare the symbols bound
+        // by the rule.
+        if let (true, Some(name)) = (lhs, pattern_name(normal)) {
+            if !found(Found::Binding(name, &[1]), &position) {
+                return;
+            }
+        }
+
+        let scope = scopes.parts(&expr).filter(|scope| match only {
+            Some(symbol) => scope
+                .variables
+                .iter()
+                .any(|(variable, _)| variable == symbol),
+            None => true,
+        });
+
+        match scope {
+            Some(scope) => {
+                for (variable, path) in &scope.variables {
+                    let path = match path {
+                        Some(path) => path,
+                        None => continue,
+                    };
+
+                    if !found(Found::Binding(variable, path), &position) {
+                        return;
+                    }
+                }
+
+                let bound: Vec<Vec<Symbol>> = scope
+                    .parts
+                    .iter()
+                    .map(|part| scope.bound_in(part))
+                    .collect();
+
+                for (part, bound) in scope.parts.iter().zip(bound).rev() {
+                    let lhs = lhs || (scope.rule && part.position == [1]);
+                    let visit =
+                        Step::Visit(part.expr.clone(), depth, part.position.clone(), lhs);
+
+                    if bound.is_empty() {
+                        stack.push(visit);
+                    } else {
+                        let bound = Rc::new(bound);
+
+                        stack.push(Step::Unbind(Rc::clone(&bound)));
+                        stack.push(visit);
+                        stack.push(Step::Bind(bound));
+                    }
+                }
+            },
+            None => {
+                for (index, element) in normal.elements().iter().enumerate().rev() {
+                    stack.push(Step::Visit(element.clone(), depth, vec![index + 1], lhs));
+                }
+            },
+        }
+
+        // Pattern names are only searched for in elements, not in heads.
+        stack.push(Step::Visit(normal.head().clone(), depth, vec![0], false));
+    }
+}
+
+//======================================
+// Helpers
+//======================================
+
+/// `x$n`
+fn local_symbol(symbol: &Symbol, number: u64) -> Symbol {
+    Symbol::new(&format!("{}${}", symbol, number))
+}
+
+/// Add every symbol that appears in `expr`, whether free or bound, to `symbols`.
+fn collect_symbols(expr: &Expr, symbols: &mut HashSet<Symbol>) {
+    let mut stack = vec![expr];
+
+    while let Some(expr) = stack.pop() {
+        match expr.kind() {
+            ExprKind::Symbol(symbol) => {
+                if !symbols.contains(symbol) {
+                    symbols.insert(symbol.clone());
+                }
+            },
+            ExprKind::Normal(normal) => {
+                stack.push(normal.head());
+                stack.extend(normal.elements());
+            },
+            ExprKind::Integer(_) | ExprKind::Real(_) | ExprKind::String(_) => (),
+        }
+    }
+}
+
+//======================================
+// Formatting
+//======================================
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScopeError::InvalidConstruct(expr) => {
+                write!(f, "invalid scoping construct: {}", expr)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
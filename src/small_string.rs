@@ -0,0 +1,258 @@
+use std::{
+    borrow::{Borrow, Cow},
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    ops::Deref,
+    str,
+    sync::Arc,
+};
+
+
+/// Immutable string that stores short values inline.
+///
+/// Strings of up to [`SmallString::INLINE_CAPACITY`] bytes are stored directly inside
+/// the `SmallString` value, without any heap allocation. Longer strings are stored in a
+/// single shared [`Arc<str>`] allocation, so cloning a `SmallString` never copies
+/// more than a few machine words.
+///
+/// `SmallString` is used to store the value of
+/// [`ExprKind::String`][crate::ExprKind::String] expressions, and the names of
+/// [`Symbol`][crate::Symbol]s and contexts.
+///
+/// `SmallString` dereferences to [`str`], and compares, orders, hashes and formats the
+/// same way as the `str` it contains.
+///
+/// ```
+/// use wolfram_expr::SmallString;
+///
+/// let short = SmallString::from("x");
+/// assert!(short.is_inline());
+/// assert_eq!(short.as_str(), "x");
+///
+/// let long = SmallString::from("a string that is too long to be stored inline");
+/// assert!(!long.is_inline());
+/// assert_eq!(long, "a string that is too long to be stored inline");
+/// ```
+#[derive(Clone)]
+pub struct SmallString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; SmallString::INLINE_CAPACITY],
+    },
+    Heap(Arc<str>),
+}
+
+// Assert that SmallString is no larger than a `String`.
+const _: () = assert!(mem::size_of::<SmallString>() == 3 * mem::size_of::<usize>());
+const _: () = assert!(mem::size_of::<SmallString>() == mem::size_of::<String>());
+
+impl SmallString {
+    /// Maximum length in bytes of a string that can be stored inline.
+    ///
+    /// This is 22 bytes on 64-bit platforms.
+    pub const INLINE_CAPACITY: usize = 3 * mem::size_of::<usize>() - 2;
+
+    /// Construct a new `SmallString` from a string slice.
+    pub fn new(string: &str) -> Self {
+        if string.len() > SmallString::INLINE_CAPACITY {
+            return SmallString(Repr::Heap(Arc::from(string)));
+        }
+
+        let mut bytes = [0; SmallString::INLINE_CAPACITY];
+        bytes[..string.len()].copy_from_slice(string.as_bytes());
+
+        SmallString(Repr::Inline {
+            len: string.len() as u8,
+            bytes,
+        })
+    }
+
+    /// Get the underlying `&str` representation of this string.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Inline { len, bytes } => {
+                let bytes = &bytes[..usize::from(*len)];
+
+                // SAFETY: `bytes` was copied from a valid `&str` in SmallString::new().
+                unsafe { str::from_utf8_unchecked(bytes) }
+            },
+            Repr::Heap(string) => string,
+        }
+    }
+
+    /// Returns `true` if this string is stored inline, without a heap allocation.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+
+    /// Approximate number of bytes of heap memory used by this string.
+    ///
+    /// Strings that share a single heap allocation each report its full size.
+    pub(crate) fn heap_size(&self) -> usize {
+        match &self.0 {
+            Repr::Inline { .. } => 0,
+            // The allocation stores the strong and weak counts alongside the string
+            // data.
+            Repr::Heap(string) => 2 * mem::size_of::<usize>() + string.len(),
+        }
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> Self {
+        SmallString::new("")
+    }
+}
+
+impl Deref for SmallString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SmallString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for SmallString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+//=======================================
+// Conversion trait impl's
+//=======================================
+
+impl From<&str> for SmallString {
+    fn from(string: &str) -> Self {
+        SmallString::new(string)
+    }
+}
+
+impl From<&String> for SmallString {
+    fn from(string: &String) -> Self {
+        SmallString::new(string)
+    }
+}
+
+impl From<String> for SmallString {
+    fn from(string: String) -> Self {
+        if string.len() <= SmallString::INLINE_CAPACITY {
+            return SmallString::new(&string);
+        }
+
+        SmallString(Repr::Heap(Arc::from(string)))
+    }
+}
+
+impl From<Box<str>> for SmallString {
+    fn from(string: Box<str>) -> Self {
+        SmallString::from(String::from(string))
+    }
+}
+
+impl From<Cow<'_, str>> for SmallString {
+    fn from(string: Cow<str>) -> Self {
+        match string {
+            Cow::Borrowed(string) => SmallString::new(string),
+            Cow::Owned(string) => SmallString::from(string),
+        }
+    }
+}
+
+impl From<Arc<str>> for SmallString {
+    /// Long strings will share the allocation of `string`.
+    fn from(string: Arc<str>) -> Self {
+        if string.len() <= SmallString::INLINE_CAPACITY {
+            return SmallString::new(&string);
+        }
+
+        SmallString(Repr::Heap(string))
+    }
+}
+
+impl From<char> for SmallString {
+    fn from(char: char) -> Self {
+        SmallString::new(char.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(string: SmallString) -> String {
+        String::from(string.as_str())
+    }
+}
+
+//=======================================
+// Display & Debug impl/s
+//=======================================
+
+impl fmt::Display for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+//======================================
+// Comparision trait impls
+//======================================
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &SmallString) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+impl PartialOrd for SmallString {
+    fn partial_cmp(&self, other: &SmallString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallString {
+    fn cmp(&self, other: &SmallString) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for SmallString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialEq<str> for SmallString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SmallString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for SmallString {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
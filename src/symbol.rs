@@ -1,11 +1,12 @@
 //! Representation of Wolfram Language symbols.
 //!
-//! This module provides four primary types:
+//! This module provides five primary types:
 //!
 //! * [`Symbol`]
 //! * [`SymbolName`]
 //! * [`Context`]
 //! * [`RelativeContext`]
+//! * [`RelativeSymbol`]
 //!
 //! These types are used for storing a string value that has been validated to conform
 //! to the syntax of Wolfram Language [symbols and contexts][ref/SymbolNamesAndContexts].
@@ -17,7 +18,8 @@
 //! * [`SymbolRef`]
 //! * [`SymbolNameRef`]
 //! * [`ContextRef`]
-// * TODO: `RelativeContextRef`
+//! * [`RelativeContextRef`]
+//! * [`RelativeSymbolRef`]
 //!
 //! ## Related Links
 //!
@@ -30,9 +32,10 @@ pub(crate) mod parse;
 use std::{
     fmt::{self, Debug, Display},
     mem,
-    sync::Arc,
 };
 
+use crate::SmallString;
+
 
 /* Notes
 
@@ -53,9 +56,13 @@ Operations on Symbols
 // Owned Data
 //======================================
 
-// TODO: Change these types to be Arc<str>. This has the consequence of increasing the
-//       size of these types from 64-bits to 128 bits, so first take care that they are
-//       not passed through a C FFI anywhere as a pointer-sized type.
+// These types store their string value in a `SmallString`, so that the names of most
+// symbols and contexts are stored inline, without a separate heap allocation.
+//
+// This makes them the size of a `String` instead of pointer-sized. None of these types
+// are passed through a C FFI by this crate, and their layout is not part of its public
+// API: code that needs to pass a symbol across an FFI boundary should pass its string
+// value (e.g. `Symbol::as_str()`) instead.
 
 /// Wolfram Language symbol.
 ///
@@ -67,29 +74,33 @@ Operations on Symbols
 /// This type implements `PartialOrd`/`Ord` primarily for the purposes of allowing
 /// instances of this type to be included in ordered sets (e.g. `BTreeMap`).
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[repr(C)]
-pub struct Symbol(Arc<String>);
+pub struct Symbol(SmallString);
 
 /// The identifier portion of a symbol. This contains no context marks ('`').
 ///
 /// In the symbol `` Global`foo ``, the `SymbolName` is `"foo"`.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct SymbolName(Arc<String>);
+pub struct SymbolName(SmallString);
 
 /// Wolfram Language context.
 ///
 /// Examples: `` System` ``, `` Global` ``, `` MyPackage`Utils` ``, etc.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Context(Arc<String>);
+pub struct Context(SmallString);
 
 /// Context begining with a `` ` ``.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct RelativeContext(Arc<String>);
+pub struct RelativeContext(SmallString);
+
+/// Symbol begining with a `` ` ``.
+///
+/// Examples: `` `foo ``, `` `Private`foo ``, etc.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelativeSymbol(SmallString);
 
-// By using `usize` here, we guarantee that we can later change this to be a pointer
-// instead without changing the sizes of a lot of Expr types. This is good for FFI/ABI
-// compatibility if I decide to change the way Symbol works.
-const _: () = assert!(mem::size_of::<Symbol>() == mem::size_of::<usize>());
+// Assert that Symbol is no larger than a `String`, so that it doesn't increase the size
+// of `ExprKind`.
+const _: () = assert!(mem::size_of::<Symbol>() == mem::size_of::<String>());
 const _: () = assert!(mem::align_of::<Symbol>() == mem::align_of::<usize>());
 
 //======================================
@@ -108,6 +119,14 @@ pub struct SymbolNameRef<'s>(&'s str);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ContextRef<'s>(pub(super) &'s str);
 
+/// Borrowed string containing a valid relative context.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelativeContextRef<'s>(&'s str);
+
+/// Borrowed string containing a valid relative symbol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelativeSymbolRef<'s>(&'s str);
+
 //==========================================================
 // Impls -- Owned Types
 //==========================================================
@@ -156,19 +175,19 @@ impl Symbol {
     }
 
     /// Get a borrowed [`SymbolRef`] from this [`Symbol`].
-    pub fn as_symbol_ref(&self) -> SymbolRef {
+    pub fn as_symbol_ref(&self) -> SymbolRef<'_> {
         let Symbol(arc_string) = self;
 
         SymbolRef(arc_string.as_str())
     }
 
     /// Get the context path part of a symbol as an [`ContextRef`].
-    pub fn context(&self) -> ContextRef {
+    pub fn context(&self) -> ContextRef<'_> {
         self.as_symbol_ref().context()
     }
 
     /// Get the symbol name part of a symbol as a [`SymbolNameRef`].
-    pub fn symbol_name(&self) -> SymbolNameRef {
+    pub fn symbol_name(&self) -> SymbolNameRef<'_> {
         self.as_symbol_ref().symbol_name()
     }
 }
@@ -184,7 +203,7 @@ impl SymbolName {
     }
 
     /// Get a borrowed [`SymbolNameRef`] from this `SymbolName`.
-    pub fn as_symbol_name_ref(&self) -> SymbolNameRef {
+    pub fn as_symbol_name_ref(&self) -> SymbolNameRef<'_> {
         SymbolNameRef(self.as_str())
     }
 }
@@ -225,12 +244,12 @@ impl Context {
 
     /// The `` Global` `` context.
     pub fn global() -> Self {
-        Context(Arc::new(String::from("Global`")))
+        Context(SmallString::new("Global`"))
     }
 
     /// The `` System` `` context.
     pub fn system() -> Self {
-        Context(Arc::new(String::from("System`")))
+        Context(SmallString::new("System`"))
     }
 
     /// Construct a new [`Context`] by appending a new context component to this
@@ -250,38 +269,23 @@ impl Context {
             .expect("Context::join(): invalid Context")
     }
 
-    /// Return the components of this [`Context`].
+    /// Return an iterator over the components of this [`Context`].
     ///
     /// ```
     /// use wolfram_expr::symbol::Context;
     ///
     /// let context = Context::new("MyPackage`Sub`Module`");
     ///
-    /// let components = context.components();
+    /// let components: Vec<&str> = context.components().map(|c| c.as_str()).collect();
     ///
-    /// assert!(components.len() == 3);
-    /// assert!(components[0].as_str() == "MyPackage");
-    /// assert!(components[1].as_str() == "Sub");
-    /// assert!(components[2].as_str() == "Module");
+    /// assert_eq!(components, ["MyPackage", "Sub", "Module"]);
     /// ```
-    pub fn components(&self) -> Vec<SymbolNameRef> {
-        let Context(string) = self;
-
-        let comps: Vec<SymbolNameRef> = string
-            .split('`')
-            // Remove the last component, which will always be the empty string
-            .filter(|comp| !comp.is_empty())
-            .map(|comp| {
-                SymbolNameRef::try_new(comp)
-                    .expect("Context::components(): invalid context component")
-            })
-            .collect();
-
-        comps
+    pub fn components(&self) -> impl Iterator<Item = SymbolNameRef<'_>> {
+        self.as_context_ref().components()
     }
 
     /// Get a borrowed [`ContextRef`] from this `Context`.
-    pub fn as_context_ref(&self) -> ContextRef {
+    pub fn as_context_ref(&self) -> ContextRef<'_> {
         ContextRef(self.as_str())
     }
 
@@ -294,36 +298,72 @@ impl Context {
 impl RelativeContext {
     /// Attempt to parse `input` as a relative context.
     pub fn try_new(input: &str) -> Option<Self> {
-        crate::symbol::parse::RelativeContext_try_new(input)
+        let context_ref = RelativeContextRef::try_new(input)?;
+
+        Some(context_ref.to_relative_context())
     }
 
-    /// Return the components of this [`RelativeContext`].
+    /// Return an iterator over the components of this [`RelativeContext`].
     ///
     /// ```
     /// use wolfram_expr::symbol::RelativeContext;
     ///
     /// let context = RelativeContext::try_new("`Sub`Module`").unwrap();
     ///
-    /// let components = context.components();
+    /// let components: Vec<&str> = context.components().map(|c| c.as_str()).collect();
     ///
-    /// assert!(components.len() == 2);
-    /// assert!(components[0].as_str() == "Sub");
-    /// assert!(components[1].as_str() == "Module");
+    /// assert_eq!(components, ["Sub", "Module"]);
     /// ```
-    pub fn components(&self) -> Vec<SymbolNameRef> {
-        let RelativeContext(string) = self;
+    pub fn components(&self) -> impl Iterator<Item = SymbolNameRef<'_>> {
+        self.as_relative_context_ref().components()
+    }
 
-        let comps: Vec<SymbolNameRef> = string
-            .split('`')
-            // Remove the last component, which will always be the empty string
-            .filter(|comp| !comp.is_empty())
-            .map(|comp| {
-                SymbolNameRef::try_new(comp)
-                    .expect("RelativeContext::components(): invalid context component")
-            })
-            .collect();
+    /// Get a borrowed [`RelativeContextRef`] from this `RelativeContext`.
+    pub fn as_relative_context_ref(&self) -> RelativeContextRef<'_> {
+        RelativeContextRef(self.as_str())
+    }
 
-        comps
+    /// Resolve this relative context against `context`, producing an absolute
+    /// [`Context`].
+    ///
+    /// See [`RelativeContextRef::to_absolute()`].
+    pub fn to_absolute(&self, context: &Context) -> Context {
+        self.as_relative_context_ref().to_absolute(context)
+    }
+}
+
+impl RelativeSymbol {
+    /// Attempt to parse `input` as a relative symbol.
+    ///
+    /// A relative symbol is a symbol whose context path begins with a `` ` ``, e.g.
+    /// ``"`foo"`` or ``"`Private`foo"``.
+    pub fn try_new(input: &str) -> Option<Self> {
+        let symbol_ref = RelativeSymbolRef::try_new(input)?;
+
+        Some(symbol_ref.to_relative_symbol())
+    }
+
+    /// Get a borrowed [`RelativeSymbolRef`] from this `RelativeSymbol`.
+    pub fn as_relative_symbol_ref(&self) -> RelativeSymbolRef<'_> {
+        RelativeSymbolRef(self.as_str())
+    }
+
+    /// Get the context path part of this symbol as a [`RelativeContextRef`].
+    pub fn context(&self) -> RelativeContextRef<'_> {
+        self.as_relative_symbol_ref().context()
+    }
+
+    /// Get the symbol name part of this symbol as a [`SymbolNameRef`].
+    pub fn symbol_name(&self) -> SymbolNameRef<'_> {
+        self.as_relative_symbol_ref().symbol_name()
+    }
+
+    /// Resolve this relative symbol against `context`, producing an absolute
+    /// [`Symbol`].
+    ///
+    /// See [`RelativeSymbolRef::to_absolute()`].
+    pub fn to_absolute(&self, context: &Context) -> Symbol {
+        self.as_relative_symbol_ref().to_absolute(context)
     }
 }
 
@@ -357,9 +397,8 @@ macro_rules! common_impls {
             /// rustc were the function not marked `unsafe`. However, this function is so
             /// often *not* what is really needed, it's marked unsafe as a deterent to
             /// possible users.
-            pub(crate) unsafe fn unchecked_new<S: Into<String>>(input: S) -> $ty {
-                let inner: Arc<String> = Arc::new(input.into());
-                $ty(inner)
+            pub(crate) unsafe fn unchecked_new<S: Into<SmallString>>(input: S) -> $ty {
+                $ty(input.into())
             }
         }
     };
@@ -369,6 +408,15 @@ common_impls!(impl Symbol);
 common_impls!(impl SymbolName);
 common_impls!(impl Context);
 common_impls!(impl RelativeContext);
+common_impls!(impl RelativeSymbol);
+
+impl Symbol {
+    /// Approximate number of bytes of heap memory used by this symbol.
+    pub(crate) fn heap_size(&self) -> usize {
+        let Symbol(string) = self;
+        string.heap_size()
+    }
+}
 
 //==========================================================
 // Impls -- Borrowed Types
@@ -399,7 +447,7 @@ impl<'s> SymbolRef<'s> {
     /// Convert this borrowed string into an owned [`Symbol`].
     pub fn to_symbol(&self) -> Symbol {
         let SymbolRef(string) = self;
-        unsafe { Symbol::unchecked_new(string.to_owned()) }
+        unsafe { Symbol::unchecked_new(*string) }
     }
 
     // TODO: Document this method
@@ -455,7 +503,7 @@ impl<'s> SymbolNameRef<'s> {
     /// Convert this borrowed string into an owned [`SymbolName`].
     pub fn to_symbol_name(&self) -> SymbolName {
         let SymbolNameRef(string) = self;
-        unsafe { SymbolName::unchecked_new(string.to_owned()) }
+        unsafe { SymbolName::unchecked_new(*string) }
     }
 
     #[doc(hidden)]
@@ -479,15 +527,196 @@ impl<'s> ContextRef<'s> {
     /// Convert this borrowed string into an owned [`Context`].
     pub fn to_context(&self) -> Context {
         let ContextRef(string) = self;
-        unsafe { Context::unchecked_new(string.to_owned()) }
+        unsafe { Context::unchecked_new(*string) }
+    }
+
+    /// Return an iterator over the components of this context.
+    ///
+    /// ```
+    /// use wolfram_expr::symbol::ContextRef;
+    ///
+    /// let context = ContextRef::try_new("MyPackage`Sub`").unwrap();
+    ///
+    /// let mut components = context.components();
+    ///
+    /// assert_eq!(components.next().unwrap().as_str(), "MyPackage");
+    /// assert_eq!(components.next().unwrap().as_str(), "Sub");
+    /// assert!(components.next().is_none());
+    /// ```
+    pub fn components(&self) -> impl Iterator<Item = SymbolNameRef<'s>> {
+        self.as_str()
+            .split('`')
+            // Remove the last component, which will always be the empty string.
+            .filter(|comp| !comp.is_empty())
+            // SAFETY: Every non-empty component of a valid context is a valid symbol
+            //         name.
+            .map(|comp| unsafe { SymbolNameRef::unchecked_new(comp) })
     }
 
     #[doc(hidden)]
-    pub unsafe fn unchecked_new(string: &'s str) -> Self {
+    pub const unsafe fn unchecked_new(string: &'s str) -> Self {
         ContextRef(string)
     }
 }
 
+impl<'s> RelativeContextRef<'s> {
+    /// Attempt to parse `string` as a relative context.
+    pub fn try_new(string: &'s str) -> Option<Self> {
+        crate::symbol::parse::RelativeContextRef_try_new(string)
+    }
+
+    /// Get the borrowed string data.
+    pub fn as_str(&self) -> &'s str {
+        let RelativeContextRef(string) = self;
+        string
+    }
+
+    /// Convert this borrowed string into an owned [`RelativeContext`].
+    pub fn to_relative_context(&self) -> RelativeContext {
+        let RelativeContextRef(string) = self;
+        unsafe { RelativeContext::unchecked_new(*string) }
+    }
+
+    /// Return an iterator over the components of this relative context.
+    ///
+    /// ```
+    /// use wolfram_expr::symbol::RelativeContextRef;
+    ///
+    /// let context = RelativeContextRef::try_new("`Sub`Module`").unwrap();
+    ///
+    /// let mut components = context.components();
+    ///
+    /// assert_eq!(components.next().unwrap().as_str(), "Sub");
+    /// assert_eq!(components.next().unwrap().as_str(), "Module");
+    /// assert!(components.next().is_none());
+    /// ```
+    pub fn components(&self) -> impl Iterator<Item = SymbolNameRef<'s>> {
+        self.as_str()
+            .split('`')
+            // Remove the first and last components, which will always be the empty
+            // string.
+            .filter(|comp| !comp.is_empty())
+            // SAFETY: Every non-empty component of a valid relative context is a valid
+            //         symbol name.
+            .map(|comp| unsafe { SymbolNameRef::unchecked_new(comp) })
+    }
+
+    /// Resolve this relative context against `context`, producing an absolute
+    /// [`Context`].
+    ///
+    /// ```
+    /// use wolfram_expr::symbol::{Context, RelativeContextRef};
+    ///
+    /// let relative = RelativeContextRef::try_new("`Private`").unwrap();
+    ///
+    /// let absolute = relative.to_absolute(&Context::new("MyPackage`"));
+    ///
+    /// assert_eq!(absolute.as_str(), "MyPackage`Private`");
+    /// ```
+    pub fn to_absolute(&self, context: &Context) -> Context {
+        // Strip the leading '`'; what remains is either empty or a sequence of
+        // components each terminated by a '`', so appending it to a valid absolute
+        // context produces another valid absolute context.
+        let (_, rest) = self.as_str().split_at(1);
+
+        unsafe { Context::unchecked_new(format!("{}{}", context, rest)) }
+    }
+
+    #[doc(hidden)]
+    pub unsafe fn unchecked_new(string: &'s str) -> Self {
+        RelativeContextRef(string)
+    }
+}
+
+impl<'s> RelativeSymbolRef<'s> {
+    /// Attempt to parse `string` as a relative symbol.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wolfram_expr::symbol::RelativeSymbolRef;
+    ///
+    /// assert!(matches!(RelativeSymbolRef::try_new("`foo"), Some(_)));
+    /// assert!(matches!(RelativeSymbolRef::try_new("`Private`foo"), Some(_)));
+    /// assert!(matches!(RelativeSymbolRef::try_new("Global`foo"), None));
+    /// assert!(matches!(RelativeSymbolRef::try_new("foo"), None));
+    /// ```
+    pub fn try_new(string: &'s str) -> Option<Self> {
+        crate::symbol::parse::RelativeSymbolRef_try_new(string)
+    }
+
+    /// Get the borrowed string data.
+    pub fn as_str(&self) -> &'s str {
+        let RelativeSymbolRef(string) = self;
+        string
+    }
+
+    /// Convert this borrowed string into an owned [`RelativeSymbol`].
+    pub fn to_relative_symbol(&self) -> RelativeSymbol {
+        let RelativeSymbolRef(string) = self;
+        unsafe { RelativeSymbol::unchecked_new(*string) }
+    }
+
+    /// Get the context path part of this symbol as a [`RelativeContextRef`].
+    ///
+    /// The context of `` `foo `` is `` ` ``.
+    pub fn context(&self) -> RelativeContextRef<'s> {
+        let (context, _) = self.split();
+        context
+    }
+
+    /// Get the symbol name part of this symbol as a [`SymbolNameRef`].
+    pub fn symbol_name(&self) -> SymbolNameRef<'s> {
+        let (_, name) = self.split();
+        name
+    }
+
+    /// Resolve this relative symbol against `context`, producing an absolute
+    /// [`Symbol`].
+    ///
+    /// ```
+    /// use wolfram_expr::symbol::{Context, RelativeSymbolRef};
+    ///
+    /// let relative = RelativeSymbolRef::try_new("`Private`foo").unwrap();
+    ///
+    /// let symbol = relative.to_absolute(&Context::new("MyPackage`"));
+    ///
+    /// assert_eq!(symbol.as_str(), "MyPackage`Private`foo");
+    /// ```
+    pub fn to_absolute(&self, context: &Context) -> Symbol {
+        // Strip the leading '`'; appending the remaining components and symbol name to a
+        // valid absolute context produces a valid absolute symbol.
+        let (_, rest) = self.as_str().split_at(1);
+
+        unsafe { Symbol::unchecked_new(format!("{}{}", context, rest)) }
+    }
+
+    fn split(&self) -> (RelativeContextRef<'s>, SymbolNameRef<'s>) {
+        let string = self.as_str();
+
+        let last_grave = string
+            .rfind('`')
+            .expect("Failed to find grave '`' character in relative symbol");
+
+        // SAFETY: All valid relative symbols begin with a grave mark '`', will have at
+        //         least 1 character after the last grave mark, and the string up to and
+        //         including the last grave mark will be a valid relative context.
+        let (context, name) = string.split_at(last_grave + 1);
+
+        unsafe {
+            (
+                RelativeContextRef::unchecked_new(context),
+                SymbolNameRef::unchecked_new(name),
+            )
+        }
+    }
+
+    #[doc(hidden)]
+    pub unsafe fn unchecked_new(string: &'s str) -> Self {
+        RelativeSymbolRef(string)
+    }
+}
+
 //======================================
 // Formatting impls
 //======================================
@@ -1,7 +1,9 @@
 // TODO(!): Replace all of this symbol parsing logic with functionality from
 //          wolfram-code-parse, once that is available.
 
-use crate::symbol::{ContextRef, RelativeContext, SymbolNameRef, SymbolRef};
+use crate::symbol::{
+    ContextRef, RelativeContextRef, RelativeSymbolRef, SymbolNameRef, SymbolRef,
+};
 
 #[allow(non_snake_case)]
 pub(super) fn SymbolRef_try_new<'s>(string: &'s str) -> Option<SymbolRef<'s>> {
@@ -30,11 +32,23 @@ pub(super) fn ContextRef_try_new<'s>(string: &'s str) -> Option<ContextRef<'s>>
     }
 }
 
-// TODO(cleanup): Add RelativeContextRef type, use here instead.
 #[allow(non_snake_case)]
-pub(super) fn RelativeContext_try_new(input: &str) -> Option<RelativeContext> {
-    if parse_symbol_like(input)? == SymbolLike::RelativeContext {
-        Some(unsafe { RelativeContext::unchecked_new(input.to_owned()) })
+pub(super) fn RelativeContextRef_try_new<'s>(
+    string: &'s str,
+) -> Option<RelativeContextRef<'s>> {
+    if parse_symbol_like(string)? == SymbolLike::RelativeContext {
+        Some(RelativeContextRef(string))
+    } else {
+        None
+    }
+}
+
+#[allow(non_snake_case)]
+pub(super) fn RelativeSymbolRef_try_new<'s>(
+    string: &'s str,
+) -> Option<RelativeSymbolRef<'s>> {
+    if parse_symbol_like(string)? == SymbolLike::RelativeSymbol {
+        Some(RelativeSymbolRef(string))
     } else {
         None
     }
@@ -59,10 +73,10 @@ fn parse_symbol_like(input: &str) -> Option<SymbolLike> {
         return None;
     }
 
-    let components: Vec<&str> = input.split("`").collect();
+    let components: Vec<&str> = input.split('`').collect();
 
     let like = match components.as_slice() {
-        [only] if is_symbol_component(*only) => SymbolLike::SymbolName,
+        [only] if is_symbol_component(only) => SymbolLike::SymbolName,
         // "`...`"
         ["", inner @ .., ""] if inner.iter().copied().all(is_symbol_component) => {
             SymbolLike::RelativeContext
@@ -106,7 +120,7 @@ fn is_symbol_component(str: &str) -> bool {
         match char {
             '_' | '-' => return false,
             _ if char.is_alphabetic() => (),
-            _ if char.is_digit(10) => (),
+            _ if char.is_ascii_digit() => (),
             '$' => (),
             _ => return false,
         }
@@ -115,6 +129,80 @@ fn is_symbol_component(str: &str) -> bool {
     true
 }
 
+//======================================
+// Compile-time validation
+//======================================
+
+/// `const` equivalent of [`SymbolRef_try_new()`], used to validate symbols at compile
+/// time.
+///
+/// Only ASCII input is supported: symbols containing non-ASCII letters are rejected,
+/// because `char::is_alphabetic()` cannot be called in a `const` context.
+pub(crate) const fn is_absolute_symbol(string: &str) -> bool {
+    let bytes = string.as_bytes();
+
+    // An absolute symbol has at least two components.
+    contains_grave(bytes, bytes.len()) && are_symbol_components(bytes, bytes.len())
+}
+
+/// `const` equivalent of [`ContextRef_try_new()`], used to validate contexts at compile
+/// time.
+///
+/// Only ASCII input is supported; see [`is_absolute_symbol()`].
+pub(crate) const fn is_absolute_context(string: &str) -> bool {
+    let bytes = string.as_bytes();
+
+    match bytes.last() {
+        Some(b'`') => are_symbol_components(bytes, bytes.len() - 1),
+        _ => false,
+    }
+}
+
+/// Returns `true` if `bytes[..end]` contains a `` ` ``.
+const fn contains_grave(bytes: &[u8], end: usize) -> bool {
+    let mut index = 0;
+
+    while index < end {
+        if bytes[index] == b'`' {
+            return true;
+        }
+        index += 1;
+    }
+
+    false
+}
+
+/// Returns `true` if `bytes[..end]` is a non-empty sequence of valid ASCII symbol
+/// components separated by `` ` ``.
+const fn are_symbol_components(bytes: &[u8], end: usize) -> bool {
+    // Whether the next byte is the first byte of a component.
+    let mut component_start = true;
+    let mut index = 0;
+
+    while index < end {
+        let byte = bytes[index];
+
+        if byte == b'`' {
+            if component_start {
+                // Empty component.
+                return false;
+            }
+            component_start = true;
+        } else if component_start {
+            if !byte.is_ascii_alphabetic() && byte != b'$' {
+                return false;
+            }
+            component_start = false;
+        } else if !byte.is_ascii_alphanumeric() && byte != b'$' {
+            return false;
+        }
+
+        index += 1;
+    }
+
+    !component_start
+}
+
 #[test]
 fn test_is_symbol_component() {
     assert!(is_symbol_component("foo"));
@@ -125,3 +213,34 @@ fn test_is_symbol_component() {
 fn test_parse_symbol_like() {
     assert_eq!(parse_symbol_like("foo"), Some(SymbolLike::SymbolName));
 }
+
+#[test]
+#[rustfmt::skip]
+fn test_const_validation_matches_parse_symbol_like() {
+    let inputs = [
+        "", "`", "``", "a", "a`", "`a", "a`b", "a`b`", "`a`b", "a``b", "a`b``",
+        "System`List", "Global`$x1", "$Context`x", "a`1", "a`b_c", "1a`b", "a`b-c",
+        "a b`c", "My`Package`Private`",
+    ];
+
+    for input in inputs {
+        let symbol_like = parse_symbol_like(input);
+
+        assert_eq!(
+            is_absolute_symbol(input),
+            symbol_like == Some(SymbolLike::AbsoluteSymbol),
+            "{}",
+            input
+        );
+        assert_eq!(
+            is_absolute_context(input),
+            symbol_like == Some(SymbolLike::AbsoluteContext),
+            "{}",
+            input
+        );
+    }
+
+    // Non-ASCII symbols are rejected.
+    assert!(parse_symbol_like("Global`α") == Some(SymbolLike::AbsoluteSymbol));
+    assert!(!is_absolute_symbol("Global`α"));
+}
@@ -1,45 +1,54 @@
-use crate::symbol::{ContextRef, RelativeContext, SymbolNameRef, SymbolRef};
+use crate::symbol::{
+    ContextRef, RelativeContext, RelativeSymbolRef, SymbolNameRef, SymbolRef,
+};
 
-/// `(input, is Symbol, is SymbolName, is Context, is RelativeContext)`
+use crate::{
+    algebra::NumericError,
+    eval::Evaluator,
+    scope::{OccurrenceKind, SymbolOccurrence},
+    Expr, Number, Symbol,
+};
+
+/// `(input, is Symbol, is SymbolName, is Context, is RelativeContext, is RelativeSymbol)`
 #[rustfmt::skip]
-const DATA: &[(&str, bool, bool, bool, bool)] = &[
+const DATA: &[(&str, bool, bool, bool, bool, bool)] = &[
     // Symbol-like
-    ("foo`bar",     true , false, false, false),
-    ("foo`bar`baz", true , false, false, false),
-    ("foo`bar5",    true , false, false, false),
-    ("foo`5bar",    false, false, false, false),
-    ("5foo`bar",    false, false, false, false),
-    ("foo``bar",    false, false, false, false),
-    ("foo`$bar",    true , false, false, false),
-    ("$foo`$bar",   true , false, false, false),
-    ("$foo`$$$",    true , false, false, false),
-    ("$$$`$$$",     true , false, false, false),
+    ("foo`bar",     true , false, false, false, false),
+    ("foo`bar`baz", true , false, false, false, false),
+    ("foo`bar5",    true , false, false, false, false),
+    ("foo`5bar",    false, false, false, false, false),
+    ("5foo`bar",    false, false, false, false, false),
+    ("foo``bar",    false, false, false, false, false),
+    ("foo`$bar",    true , false, false, false, false),
+    ("$foo`$bar",   true , false, false, false, false),
+    ("$foo`$$$",    true , false, false, false, false),
+    ("$$$`$$$",     true , false, false, false, false),
 
     // SymbolName-like
-    ("foo",         false, true,  false, false),
-    ("foo5",        false, true,  false, false),
-    ("foo5bar",     false, true,  false, false),
-    ("$foo",        false, true,  false, false),
-    ("5foo",        false, false, false, false),
-    ("foo_bar",     false, false, false, false),
-    ("_foo",        false, false, false, false),
-
-    // TODO: RelativeSymbol-like
-    ("`foo",        false, false, false, false),
-    ("`foo`bar",    false, false, false, false),
+    ("foo",         false, true,  false, false, false),
+    ("foo5",        false, true,  false, false, false),
+    ("foo5bar",     false, true,  false, false, false),
+    ("$foo",        false, true,  false, false, false),
+    ("5foo",        false, false, false, false, false),
+    ("foo_bar",     false, false, false, false, false),
+    ("_foo",        false, false, false, false, false),
+
+    // RelativeSymbol-like
+    ("`foo",        false, false, false, false, true ),
+    ("`foo`bar",    false, false, false, false, true ),
 
     // Context-like
-    ("foo`",        false, false, true,  false),
-    ("foo`bar`",    false, false, true,  false),
+    ("foo`",        false, false, true,  false, false),
+    ("foo`bar`",    false, false, true,  false, false),
 
     // RelativeContext-like
-    ("`foo`",       false, false, false, true),
-    ("`foo`bar`",   false, false, false, true),
+    ("`foo`",       false, false, false, true,  false),
+    ("`foo`bar`",   false, false, false, true,  false),
 ];
 
 #[test]
 pub fn test_symbol_like_parsing() {
-    for (input, is_symbol, is_symbol_name, is_context, is_rel_context) in
+    for (input, is_symbol, is_symbol_name, is_context, is_rel_context, is_rel_symbol) in
         DATA.iter().copied()
     {
         println!("input: {input}");
@@ -47,5 +56,2838 @@ pub fn test_symbol_like_parsing() {
         assert_eq!(SymbolNameRef::try_new(input).is_some(), is_symbol_name);
         assert_eq!(ContextRef::try_new(input).is_some(), is_context);
         assert_eq!(RelativeContext::try_new(input).is_some(), is_rel_context);
+        assert_eq!(RelativeSymbolRef::try_new(input).is_some(), is_rel_symbol);
+    }
+}
+
+#[test]
+pub fn test_dag_round_trip() {
+    use crate::{dag, Expr, Symbol};
+
+    let shared = Expr::normal(Symbol::new("Global`g"), vec![Expr::from("x")]);
+
+    let expr = Expr::normal(Symbol::new("Global`f"), vec![
+        Expr::from(i64::MIN),
+        Expr::from(-1),
+        Expr::from(i64::MAX),
+        Expr::real(-2.5),
+        Expr::from("héllo\n"),
+        shared.clone(),
+        Expr::list(vec![shared.clone(), Expr::normal(shared.clone(), vec![])]),
+    ]);
+
+    let bytes = dag::to_bytes(&expr);
+    let decoded = dag::from_bytes(&bytes).unwrap();
+
+    assert_eq!(decoded, expr);
+
+    let elements = decoded.try_as_normal().unwrap().elements();
+    let nested = elements[6].try_as_normal().unwrap().elements();
+    assert!(std::ptr::eq(elements[5].kind(), nested[0].kind()));
+    assert!(std::ptr::eq(
+        elements[5].kind(),
+        nested[1].normal_head().unwrap().kind()
+    ));
+
+    // Truncated input
+    assert!(matches!(
+        dag::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(dag::DecodeError::Io(_))
+    ));
+
+    // Trailing data
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        dag::from_bytes(&trailing),
+        Err(dag::DecodeError::TrailingData)
+    ));
+
+    // Back-reference to an undefined expression
+    assert!(matches!(
+        dag::from_bytes(b"WXD\x01\x06\x00"),
+        Err(dag::DecodeError::InvalidBackReference(0))
+    ));
+}
+
+#[test]
+pub fn test_cached_hash_invalidation() {
+    use std::collections::HashSet;
+
+    use crate::{Expr, ExprKind, Normal, Symbol};
+
+    let mut expr = Expr::list(vec![Expr::from(1), Expr::from(2)]);
+    let original = expr.clone();
+
+    let mut set = HashSet::new();
+    set.insert(original.clone());
+    assert!(set.contains(&expr));
+
+    // Mutating a shared expression must not reuse the cached hash of the original.
+    match expr.kind_mut() {
+        ExprKind::Normal(normal) => {
+            *normal = Normal::new(Symbol::new("Global`f"), vec![])
+        },
+        _ => unreachable!(),
+    }
+
+    assert_ne!(expr, original);
+    assert!(!set.contains(&expr));
+    assert!(set.contains(&Expr::list(vec![Expr::from(1), Expr::from(2)])));
+}
+
+#[test]
+pub fn test_dag_limits() {
+    use crate::{dag, Expr, LimitExceeded, Limits, Symbol};
+
+    fn decode(expr: &Expr, limits: Limits) -> Result<Expr, dag::DecodeError> {
+        dag::from_bytes_with_limits(&dag::to_bytes(expr), &limits)
+    }
+
+    let f = Symbol::new("Global`f");
+
+    // f[f[f[f[1]]]]
+    let mut deep = Expr::from(1);
+    for _ in 0..4 {
+        deep = Expr::normal(&f, vec![deep]);
+    }
+
+    let exact = Limits {
+        max_nesting: deep.nesting(),
+        max_node_count: deep.node_count(),
+        ..Limits::default()
+    };
+    assert_eq!(decode(&deep, exact).unwrap(), deep);
+
+    let too_shallow = Limits {
+        max_nesting: 4,
+        ..exact
+    };
+    assert!(matches!(
+        decode(&deep, too_shallow),
+        Err(dag::DecodeError::LimitExceeded(LimitExceeded::Nesting(4)))
+    ));
+
+    // f[1][1][1][1], where nested heads count towards the nesting but not the depth.
+    let mut curried = Expr::from(f.clone());
+    for _ in 0..4 {
+        curried = Expr::normal(curried, vec![Expr::from(1)]);
+    }
+    assert_eq!(curried.depth(), 2);
+    assert_eq!(curried.nesting(), 5);
+    assert!(matches!(
+        decode(&curried, too_shallow),
+        Err(dag::DecodeError::LimitExceeded(LimitExceeded::Nesting(4)))
+    ));
+
+    let too_few_nodes = Limits {
+        max_node_count: exact.max_node_count - 1,
+        ..exact
+    };
+    assert!(matches!(
+        decode(&deep, too_few_nodes),
+        Err(dag::DecodeError::LimitExceeded(LimitExceeded::NodeCount(_)))
+    ));
+
+    // Each level doubles the size of the expression, but not of the encoding.
+    let mut wide = Expr::from("x");
+    for _ in 0..40 {
+        wide = Expr::list(vec![wide.clone(), wide.clone()]);
+    }
+    assert!(dag::to_bytes(&wide).len() < 1000);
+    assert!(matches!(
+        decode(&wide, Limits::default()),
+        Err(dag::DecodeError::LimitExceeded(LimitExceeded::NodeCount(_)))
+    ));
+
+    let long = Expr::from("a".repeat(100));
+
+    let short_strings = Limits {
+        max_string_length: 99,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(&long, short_strings),
+        Err(dag::DecodeError::LimitExceeded(
+            LimitExceeded::StringLength(99)
+        ))
+    ));
+
+    let few_bytes = Limits {
+        max_total_bytes: 100,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(&long, few_bytes),
+        Err(dag::DecodeError::LimitExceeded(LimitExceeded::TotalBytes(
+            100
+        )))
+    ));
+}
+
+/// Depth of the nested expressions that check that a traversal does not recurse.
+const DEEP: usize = 200_000;
+
+/// Apply `wrap` to `leaf` `depth` times, like `Nest[wrap, leaf, depth]`.
+fn nested(leaf: Expr, depth: usize, mut wrap: impl FnMut(Expr) -> Expr) -> Expr {
+    let mut expr = leaf;
+    for _ in 0..depth {
+        expr = wrap(expr);
+    }
+    expr
+}
+
+#[test]
+pub fn test_deeply_nested_expr() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use crate::{dag, Expr, Limits, Symbol};
+
+    const DEPTH: usize = 1_000_000;
+
+    let nest = || {
+        nested(Expr::from(0), DEPTH, |expr| {
+            Expr::normal(Symbol::new("Global`f"), vec![expr])
+        })
+    };
+
+    let hash = |expr: &Expr| {
+        let mut hasher = DefaultHasher::new();
+        expr.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let expr = nest();
+    let other = nest();
+
+    let copy = expr.clone();
+    assert_eq!(expr, copy);
+    assert_eq!(expr, other);
+    assert_eq!(hash(&expr), hash(&other));
+
+    assert_eq!(expr.depth(), DEPTH + 1);
+    assert_eq!(expr.tag(), Some(Symbol::new("Global`f")));
+
+    let string = expr.to_string();
+    assert_eq!(string.len(), DEPTH * "Global`f[]".len() + 1);
+    assert!(string.starts_with("Global`f[Global`f["));
+    assert!(string.contains("Global`f[0]]]"));
+    assert_eq!(format!("{:?}", expr), string);
+
+    let bytes = dag::to_bytes(&expr);
+    let decoded = dag::from_bytes_with_limits(&bytes, &Limits::unlimited()).unwrap();
+    assert_eq!(decoded, expr);
+
+    // Owned conversion to `ExprKind` must not recursively drop the original node.
+    drop(copy);
+    let kind = expr.to_kind();
+    drop(kind);
+
+    drop(decoded);
+    drop(other);
+}
+
+#[test]
+pub fn test_local_expr() {
+    use std::collections::HashSet;
+
+    use crate::{Expr, LocalExpr, Symbol};
+
+    let shared = Expr::list(vec![Expr::from(1), Expr::real(2.5)]);
+    let expr = Expr::normal(Symbol::new("Global`f"), vec![
+        shared.clone(),
+        Expr::from("x"),
+        shared.clone(),
+    ]);
+
+    let local = expr.to_local();
+    assert_eq!(local.to_string(), expr.to_string());
+
+    // Shared subexpressions remain shared.
+    let elements = local.kind().try_as_normal().unwrap().elements();
+    assert!(std::ptr::eq(elements[0].kind(), elements[2].kind()));
+    assert!(local
+        .kind()
+        .try_as_normal()
+        .unwrap()
+        .has_head(&Symbol::new("Global`f")));
+    assert_eq!(local.to_expr(), expr);
+    assert_eq!(local.to_expr().shared_node_count(), 1);
+
+    let mut set = HashSet::new();
+    set.insert(local.clone());
+    assert!(set.contains(&expr.to_local()));
+    assert!(!set.contains(&LocalExpr::list(vec![])));
+
+    // Deep expressions are compared, formatted and dropped without recursion.
+    let nest = || {
+        let mut expr = LocalExpr::from(0);
+        for _ in 0..1_000_000 {
+            expr = LocalExpr::normal(Symbol::new("Global`f"), vec![expr]);
+        }
+        expr
+    };
+
+    let deep = nest();
+    assert_eq!(deep, nest());
+    assert!(deep.to_string().starts_with("Global`f[Global`f["));
+    assert_eq!(deep.to_expr().to_local(), deep);
+}
+
+#[test]
+pub fn test_arena_expr() {
+    use crate::{Expr, ExprArena, Symbol};
+
+    let shared = Expr::list(vec![Expr::from(1), Expr::real(2.5), Expr::from("s")]);
+    let expr = Expr::normal(Symbol::new("Global`f"), vec![
+        shared.clone(),
+        Expr::from(true),
+        shared.clone(),
+    ]);
+
+    let arena = ExprArena::new();
+    let imported = arena.import(&expr);
+
+    // Shared subexpressions are only allocated once.
+    assert_eq!(arena.len(), 8);
+    let elements = imported.kind().try_as_normal().unwrap().elements();
+    assert!(elements[0].ptr_eq(&elements[2]));
+
+    assert_eq!(imported.to_string(), expr.to_string());
+    assert_eq!(imported.to_expr(), expr);
+    assert_eq!(imported.to_expr().shared_node_count(), 1);
+
+    let x = arena.symbol(Symbol::new("Global`x"));
+    let mut deep = arena.number(crate::Number::Integer(0));
+    for _ in 0..100_000 {
+        deep = arena.normal(x, vec![deep]);
+    }
+    assert_eq!(deep.to_expr().depth(), 100_001);
+    assert_eq!(arena.import(&deep.to_expr()), deep);
+}
+
+#[test]
+pub fn test_small_string() {
+    use std::collections::HashSet;
+
+    use crate::{metrics::ALLOCATION_OVERHEAD, Expr, SmallString, Symbol};
+
+    let max_inline = "a".repeat(SmallString::INLINE_CAPACITY);
+    let min_heap = "a".repeat(SmallString::INLINE_CAPACITY + 1);
+
+    assert!(SmallString::from(max_inline.as_str()).is_inline());
+    assert!(!SmallString::from(min_heap.as_str()).is_inline());
+    assert!(!SmallString::from(min_heap.clone()).is_inline());
+    assert!(SmallString::from("héllo\n").is_inline());
+
+    assert_eq!(SmallString::from(min_heap.clone()), min_heap);
+    // Inline and heap strings are ordered by their contents.
+    let (b, long) = (SmallString::from("b"), SmallString::from(min_heap.clone()));
+    assert!(b > long);
+
+    let mut set = HashSet::new();
+    set.insert(SmallString::from("x"));
+    set.insert(SmallString::from(min_heap.clone()));
+    assert!(set.contains("x"));
+    assert!(set.contains(min_heap.as_str()));
+
+    // Short strings and symbols don't require an allocation in addition to the `Expr`.
+    assert_eq!(Expr::from("x").byte_count(), ALLOCATION_OVERHEAD);
+    let list = Expr::symbol(Symbol::new("System`List"));
+    assert_eq!(list.byte_count(), ALLOCATION_OVERHEAD);
+    let long_string = Expr::from(min_heap.as_str());
+    assert!(long_string.byte_count() > ALLOCATION_OVERHEAD + min_heap.len());
+
+    assert_eq!(Expr::from("a\"b").to_string(), r#""a\"b""#);
+    assert_eq!(Expr::from("x").try_as_str(), Some("x"));
+
+    let long = Symbol::new("MyPackage`Private`aVeryLongSymbolName");
+    assert_eq!(long.as_str(), "MyPackage`Private`aVeryLongSymbolName");
+    assert_eq!(long.context().as_str(), "MyPackage`Private`");
+    assert_eq!(long.symbol_name().as_str(), "aVeryLongSymbolName");
+}
+
+#[test]
+pub fn test_diff() {
+    use crate::{
+        diff::{apply_patch, diff, Edit, PatchError},
+        Expr, Symbol,
+    };
+
+    let sym = |name: &str| Expr::symbol(Symbol::new(name));
+    let f = |elements: Vec<Expr>| Expr::normal(Symbol::new("Global`f"), elements);
+    let g = |elements: Vec<Expr>| Expr::normal(Symbol::new("Global`g"), elements);
+    let int = Expr::from;
+
+    let cases = vec![
+        (int(1), int(1)),
+        (int(1), int(2)),
+        (f(vec![]), f(vec![int(1), int(2)])),
+        (
+            f(vec![int(1), int(2), int(3)]),
+            f(vec![int(3), int(2), int(1)]),
+        ),
+        (f(vec![int(1), int(2)]), g(vec![int(1), int(3)])),
+        (f(vec![int(1)]), g(vec![int(1), int(2)])),
+        (
+            Expr::normal(f(vec![int(1)]), vec![sym("Global`x")]),
+            Expr::normal(f(vec![int(2)]), vec![sym("Global`x"), sym("Global`y")]),
+        ),
+        (
+            f(vec![
+                int(0),
+                f(vec![int(1), g(vec![int(2)])]),
+                int(3),
+                int(4),
+            ]),
+            f(vec![f(vec![g(vec![int(5)]), int(1)]), int(4), int(6)]),
+        ),
+    ];
+
+    for (old, new) in cases {
+        let edits = diff(&old, &new);
+        assert_eq!(edits.is_empty(), old == new);
+        assert_eq!(
+            apply_patch(&old, &edits).unwrap(),
+            new,
+            "{} -> {}",
+            old,
+            new
+        );
+    }
+
+    // Inserting an element is a single edit, keyed by its Part position.
+    let old = f(vec![int(1), f(vec![int(2), int(3)])]);
+    let new = f(vec![int(1), f(vec![int(2), sym("Global`x"), int(3)])]);
+    assert_eq!(diff(&old, &new), vec![Edit::Inserted {
+        position: vec![2, 2],
+        new: sym("Global`x"),
+    }]);
+    assert_eq!(diff(&f(vec![int(1)]), &g(vec![int(1)])), vec![
+        Edit::HeadChanged {
+            position: vec![],
+            old: sym("Global`f"),
+            new: sym("Global`g"),
+        }
+    ]);
+    assert_eq!(
+        diff(&f(vec![int(1)]), &g(vec![int(1)]))[0].to_string(),
+        "{0}: head changed from Global`f to Global`g"
+    );
+
+    // Patches are checked against the expression they are applied to.
+    let edits = diff(&old, &new);
+    assert_eq!(
+        apply_patch(&new, &diff(&f(vec![int(5)]), &f(vec![int(6)]))),
+        Err(PatchError::Mismatch(vec![1]))
+    );
+    assert_eq!(
+        apply_patch(&int(1), &edits),
+        Err(PatchError::InvalidPosition(vec![2]))
+    );
+}
+
+#[test]
+pub fn test_wl_macro() {
+    use crate::{wl, Expr, Symbol};
+
+    let sym = |name: &str| Expr::symbol(Symbol::new(name));
+
+    assert_eq!(wl!(1), Expr::from(1));
+    assert_eq!(wl!(-2.5), Expr::real(-2.5));
+    assert_eq!(wl!("s"), Expr::from("s"));
+    assert_eq!(wl!(True), Expr::from(true));
+    assert_eq!(wl!({}), Expr::list(vec![]));
+    assert_eq!(wl!(f[]), Expr::normal(sym("System`f"), vec![]));
+
+    assert_eq!(
+        wl!(Derivative[1][Global::f][x, {-1, {}}]),
+        Expr::normal(
+            Expr::normal(
+                Expr::normal(sym("System`Derivative"), vec![Expr::from(1)]),
+                vec![sym("Global`f")]
+            ),
+            vec![
+                sym("System`x"),
+                Expr::list(vec![Expr::from(-1), Expr::list(vec![])])
+            ]
+        )
+    );
+
+    let x = sym("Global`x");
+    let head = sym("Global`g");
+    assert_eq!(
+        wl!(context = "MyContext`Private`"; f[#(x.clone()), #head[y], My::Other::z]),
+        Expr::normal(sym("MyContext`Private`f"), vec![
+            x,
+            Expr::normal(sym("Global`g"), vec![sym("MyContext`Private`y")]),
+            sym("My`Other`z"),
+        ])
+    );
+}
+
+#[test]
+pub fn test_symbol_macro() {
+    use crate::{context, symbol, symbol::Context, Symbol};
+
+    const LIST: &str = "System`List";
+
+    assert_eq!(symbol!(LIST), Symbol::new("System`List"));
+    assert_eq!(symbol!("Global`$x1"), Symbol::new("Global`$x1"));
+    assert_eq!(
+        symbol!("MyPackage`Private`aVeryLongSymbolName"),
+        Symbol::new("MyPackage`Private`aVeryLongSymbolName")
+    );
+
+    assert_eq!(context!("System`"), Context::system());
+    assert_eq!(context!("A`B`"), Context::new("A`B`"));
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_arbitrary {
+    use proptest::prelude::*;
+
+    use crate::{dag, symbol::Context, ArbitraryConfig, Expr, Symbol};
+
+    proptest! {
+        #[test]
+        fn dag_round_trip(expr in any::<Expr>()) {
+            let bytes = dag::to_bytes(&expr);
+            prop_assert_eq!(dag::from_bytes(&bytes).unwrap(), expr);
+        }
+
+        #[test]
+        fn local_round_trip(expr in any::<Expr>()) {
+            prop_assert_eq!(expr.to_local().to_expr(), expr);
+        }
+
+        #[test]
+        fn respects_config(expr in any_with::<Expr>(ArbitraryConfig {
+            max_nesting: 3,
+            max_width: 2,
+            max_string_length: 4,
+        })) {
+            prop_assert!(expr.nesting() <= 3);
+            prop_assert!(expr.leaf_count() <= 1 + 3 * 3);
+        }
+
+        #[test]
+        fn valid_symbols(symbol in any::<Symbol>(), context in any::<Context>()) {
+            prop_assert_eq!(Symbol::try_new(symbol.as_str()), Some(symbol.clone()));
+            prop_assert_eq!(Context::try_new(context.as_str()), Some(context));
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod quickcheck_arbitrary {
+    use quickcheck::{quickcheck, Arbitrary};
+
+    use crate::{dag, symbol::Context, Expr, Symbol};
+
+    quickcheck! {
+        fn dag_round_trip(expr: Expr) -> bool {
+            dag::from_bytes(&dag::to_bytes(&expr)).unwrap() == expr
+        }
+
+        fn shrinks_to_valid_symbols(symbol: Symbol, context: Context) -> bool {
+            symbol.shrink().all(|symbol| Symbol::try_new(symbol.as_str()).is_some())
+                && context
+                    .shrink()
+                    .all(|context| Context::try_new(context.as_str()).is_some())
+        }
+
+        fn shrinks_to_smaller_exprs(expr: Expr) -> bool {
+            expr.shrink()
+                .take(100)
+                .all(|smaller| smaller.node_count() <= expr.node_count())
+        }
+    }
+}
+
+#[test]
+pub fn test_real_conversions() {
+    use crate::{Expr, Number, Symbol};
+
+    let sym = |name: &str| Expr::symbol(Symbol::new(name));
+    let directed_infinity = |elements: Vec<Expr>| {
+        Expr::normal(Symbol::new("System`DirectedInfinity"), elements)
+    };
+
+    assert_eq!(Number::try_real(f64::NAN), None);
+    assert_eq!(
+        Expr::try_real(f64::INFINITY),
+        Some(Expr::real(f64::INFINITY))
+    );
+
+    assert_eq!(Expr::from(-0.5), Expr::real(-0.5));
+    assert_eq!(Expr::from(f64::NAN), sym("System`Indeterminate"));
+    assert_eq!(
+        Expr::from(f64::INFINITY),
+        directed_infinity(vec![Expr::from(1)])
+    );
+    assert_eq!(
+        Expr::from(f64::NEG_INFINITY),
+        directed_infinity(vec![Expr::from(-1)])
+    );
+
+    for real in [0.0, -1.5, f64::MAX, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(Expr::from(real).try_as_real(), Some(real));
+    }
+    assert!(Expr::from(f64::NAN).try_as_real().unwrap().is_nan());
+
+    assert_eq!(sym("System`Infinity").try_as_real(), Some(f64::INFINITY));
+    assert_eq!(sym("System`ComplexInfinity").try_as_real(), None);
+    assert_eq!(directed_infinity(vec![]).try_as_real(), None);
+    assert_eq!(directed_infinity(vec![Expr::from(2)]).try_as_real(), None);
+    assert_eq!(Expr::from(1).try_as_real(), None);
+    assert_eq!(Expr::from("1.0").try_as_real(), None);
+}
+
+fn hash_cons_g_x() -> Expr {
+    use crate::Symbol;
+
+    Expr::normal(Symbol::new("Global`g"), vec![Expr::from("x")])
+}
+
+#[test]
+pub fn test_hash_cons_intern_shares_equal_subexpressions() {
+    use crate::{
+        hash_cons::{ptr_eq, HashCons},
+        metrics::allocation_size,
+        Symbol,
+    };
+
+    let second = hash_cons_g_x();
+    let expr = Expr::normal(Symbol::new("Global`f"), vec![
+        hash_cons_g_x(),
+        second.clone(),
+    ]);
+
+    let mut table = HashCons::new();
+    let shared = table.intern(&expr);
+
+    assert_eq!(shared, expr);
+
+    let elements = shared.try_as_normal().unwrap().elements();
+    assert!(ptr_eq(&elements[0], &elements[1]));
+
+    // The second g["x"], its head and its element are replaced by the first.
+    let second_normal = second.try_as_normal().unwrap();
+    let expected_saved = allocation_size(&second)
+        + allocation_size(second_normal.head())
+        + allocation_size(&second_normal.elements()[0]);
+
+    let stats = table.stats();
+    assert_eq!(stats.shared_nodes, 3);
+    assert_eq!(stats.bytes_saved, expected_saved);
+    assert_eq!(stats.unique_nodes, table.len());
+}
+
+#[test]
+pub fn test_hash_cons_intern_interned_expression() {
+    use crate::{
+        hash_cons::{ptr_eq, HashCons},
+        Symbol,
+    };
+
+    let expr = Expr::normal(Symbol::new("Global`f"), vec![
+        hash_cons_g_x(),
+        hash_cons_g_x(),
+    ]);
+
+    let mut table = HashCons::new();
+    let shared = table.intern(&expr);
+
+    // Interning an already interned expression returns the same allocation.
+    let again = table.intern(&shared);
+    assert!(ptr_eq(&again, &shared));
+    assert_eq!(table.stats().shared_nodes, 3);
+}
+
+#[test]
+pub fn test_hash_cons_constructors_intern_nodes() {
+    use crate::{
+        hash_cons::{ptr_eq, HashCons},
+        Number,
+    };
+
+    let mut table = HashCons::new();
+
+    let one = table.number(Number::Integer(1));
+    let another_one = table.number(Number::Integer(1));
+
+    assert!(ptr_eq(&one, &another_one));
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+pub fn test_hash_cons_intern_keeps_negative_zero_distinct() {
+    use crate::{
+        hash_cons::{ptr_eq, HashCons},
+        ExprKind, Number,
+    };
+
+    let mut table = HashCons::new();
+
+    // 0.0 and -0.0 compare equal, but must not be interned as the same node.
+    let zero = table.number(Number::real(0.0));
+    let negative_zero = table.number(Number::real(-0.0));
+
+    assert!(!ptr_eq(&zero, &negative_zero));
+    match (zero.kind(), negative_zero.kind()) {
+        (ExprKind::Real(zero), ExprKind::Real(negative_zero)) => {
+            assert!(zero.is_sign_positive());
+            assert!(negative_zero.is_sign_negative());
+        },
+        _ => panic!("expected real numbers"),
+    }
+
+    let again = table.number(Number::real(-0.0));
+    assert!(ptr_eq(&again, &negative_zero));
+}
+
+#[test]
+pub fn test_metrics_shared_dag() {
+    use std::mem;
+
+    use crate::{metrics::allocation_size, Expr, Symbol};
+
+    let f = Expr::symbol(Symbol::new("Global`f"));
+    let x = Expr::from(0);
+
+    // e[k + 1] = f[e[k], e[k]], where both elements and every head are shared.
+    const LEVELS: usize = 20;
+
+    let mut dag = x.clone();
+    for _ in 0..LEVELS {
+        dag = Expr::normal(f.clone(), vec![dag.clone(), dag]);
+    }
+
+    assert_eq!(dag.depth(), LEVELS + 1);
+    assert_eq!(dag.leaf_count(), (1 << (LEVELS + 1)) - 1);
+    assert_eq!(dag.node_count(), 3 * (1 << LEVELS) - 2);
+    // Every e[k] below the root, and the head `f`.
+    assert_eq!(dag.shared_node_count(), LEVELS + 1);
+
+    // Every distinct allocation is counted once.
+    let normal_size = allocation_size(&x) + 2 * mem::size_of::<Expr>();
+    assert_eq!(
+        dag.byte_count(),
+        allocation_size(&x) + allocation_size(&f) + LEVELS * normal_size
+    );
+}
+
+#[test]
+pub fn test_metrics_saturate() {
+    use crate::{Expr, Symbol};
+
+    let f = Expr::symbol(Symbol::new("Global`f"));
+
+    // Counts that overflow `usize` saturate instead of wrapping.
+    let mut huge = Expr::from(0);
+    for _ in 0..70 {
+        huge = Expr::normal(f.clone(), vec![huge.clone(), huge]);
     }
+
+    assert_eq!(huge.leaf_count(), usize::MAX);
+    assert_eq!(huge.node_count(), usize::MAX);
+    assert_eq!(huge.depth(), 71);
+}
+
+#[test]
+pub fn test_metrics_deep_chain() {
+    use std::mem;
+
+    use crate::{metrics::allocation_size, Expr, Symbol};
+
+    // f[f[...f[0]...]], where no allocation is shared.
+    let chain = nested(Expr::from(0), DEEP, |expr| {
+        Expr::normal(Symbol::new("Global`f"), vec![expr])
+    });
+
+    let overhead = allocation_size(&Expr::from(0));
+    let head_size = allocation_size(&Expr::symbol(Symbol::new("Global`f")));
+
+    assert_eq!(chain.depth(), DEEP + 1);
+    assert_eq!(chain.nesting(), DEEP + 1);
+    assert_eq!(chain.leaf_count(), DEEP + 1);
+    assert_eq!(chain.node_count(), 2 * DEEP + 1);
+    assert_eq!(chain.shared_node_count(), 0);
+    assert_eq!(
+        chain.byte_count(),
+        overhead + DEEP * (overhead + mem::size_of::<Expr>() + head_size)
+    );
+}
+
+fn eval_x_() -> Expr {
+    use crate::wl;
+
+    wl!(Pattern[Global::x, Blank[]])
+}
+
+#[test]
+pub fn test_eval_own_values() {
+    use crate::{eval::Evaluator, wl, Symbol};
+
+    let mut evaluator = Evaluator::new();
+
+    // The right-hand side of Set is evaluated when it is defined.
+    evaluator.set(wl!(Global::a), wl!(Global::b)).unwrap();
+    evaluator.set(wl!(Global::b), wl!(2)).unwrap();
+
+    assert_eq!(
+        evaluator.own_value(&Symbol::new("Global`a")),
+        Some(&wl!(Global::b))
+    );
+    assert_eq!(evaluator.evaluate(&wl!(Global::a)), Ok(wl!(2)));
+}
+
+#[test]
+pub fn test_eval_down_values_literal_before_pattern() {
+    use crate::{eval::Evaluator, wl, Symbol};
+
+    let mut evaluator = Evaluator::new();
+
+    evaluator
+        .set_delayed(
+            wl!(context = "Global`"; f[#(eval_x_())]),
+            wl!(Global::generic),
+        )
+        .unwrap();
+    evaluator
+        .set_delayed(wl!(context = "Global`"; f[0]), wl!(Global::zero))
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(context = "Global`"; {f[0], f[1]})),
+        Ok(wl!(context = "Global`"; {zero, generic}))
+    );
+    assert_eq!(evaluator.down_values(&Symbol::new("Global`f")).len(), 2);
+}
+
+#[test]
+pub fn test_eval_sequence_patterns() {
+    use crate::{eval::Evaluator, wl};
+
+    let xs__ = wl!(Pattern[Global::xs, BlankSequence[]]);
+
+    let mut evaluator = Evaluator::new();
+
+    // The bound Sequence[...] is spliced into the right-hand side.
+    evaluator
+        .set_delayed(
+            wl!(context = "Global`"; rev[#xs__, #(eval_x_())]),
+            wl!(context = "Global`"; g[x, xs]),
+        )
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(context = "Global`"; rev[1, 2, 3])),
+        Ok(wl!(context = "Global`"; g[3, 1, 2]))
+    );
+    assert_eq!(
+        evaluator.evaluate(&wl!(context = "Global`"; rev[])),
+        Ok(wl!(context = "Global`"; rev[]))
+    );
+}
+
+#[test]
+pub fn test_eval_sub_values() {
+    use crate::{eval::Evaluator, wl};
+
+    let mut evaluator = Evaluator::new();
+
+    evaluator
+        .set_delayed(
+            wl!(context = "Global`"; curry[#(eval_x_())][y]),
+            wl!(context = "Global`"; {x, y}),
+        )
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(context = "Global`"; {curry[1][y], curry[1][z]})),
+        Ok(wl!(context = "Global`"; {{1, y}, curry[1][z]}))
+    );
+}
+
+#[test]
+pub fn test_eval_definitions_in_program() {
+    use crate::{eval::Evaluator, wl, Symbol};
+
+    let mut evaluator = Evaluator::new();
+
+    let program = wl!(CompoundExpression[
+        Set[Global::b, 2],
+        SetDelayed[Global::h[#(eval_x_())], Global::k[Global::x, Global::x]],
+        Set[Global::c, Global::h[Global::b]],
+        Global::c
+    ]);
+
+    assert_eq!(evaluator.evaluate(&program), Ok(wl!(Global::k[2, 2])));
+    assert_eq!(
+        evaluator.own_value(&Symbol::new("Global`c")),
+        Some(&wl!(Global::k[2, 2]))
+    );
+
+    evaluator.clear(&Symbol::new("Global`c"));
+    assert_eq!(evaluator.evaluate(&wl!(Global::c)), Ok(wl!(Global::c)));
+}
+
+#[test]
+pub fn test_eval_recursion_limit() {
+    use crate::{
+        eval::{EvalError, Evaluator},
+        wl,
+    };
+
+    let mut evaluator = Evaluator::new();
+
+    evaluator
+        .set_delayed(wl!(Global::loop), wl!(Global::wrap[Global::loop]))
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::loop)),
+        Err(EvalError::RecursionLimitExceeded(
+            Evaluator::DEFAULT_RECURSION_LIMIT
+        ))
+    );
+}
+
+#[test]
+pub fn test_eval_iteration_limit() {
+    use crate::{
+        eval::{EvalError, Evaluator},
+        wl,
+    };
+
+    let mut evaluator = Evaluator::new();
+
+    evaluator
+        .set_delayed(wl!(Global::p), wl!(Global::q))
+        .unwrap();
+    evaluator
+        .set_delayed(wl!(Global::q), wl!(Global::p))
+        .unwrap();
+    evaluator.set_iteration_limit(10);
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::p)),
+        Err(EvalError::IterationLimitExceeded(10))
+    );
+}
+
+#[test]
+pub fn test_eval_hold_pattern_definitions() {
+    use crate::{eval::Evaluator, wl, Symbol};
+
+    let mut evaluator = Evaluator::new();
+
+    // Definitions are associated with the symbol inside HoldPattern.
+    evaluator
+        .set_delayed(
+            wl!(HoldPattern[Global::held[#(eval_x_())]]),
+            wl!(Global::k[Global::x]),
+        )
+        .unwrap();
+    assert_eq!(evaluator.down_values(&Symbol::new("Global`held")).len(), 1);
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::held[1])),
+        Ok(wl!(Global::k[1]))
+    );
+
+    evaluator
+        .set(wl!(HoldPattern[Global::d]), Expr::from(4))
+        .unwrap();
+    assert_eq!(evaluator.evaluate(&wl!(Global::d)), Ok(wl!(4)));
+}
+
+#[test]
+pub fn test_eval_conditional_definitions() {
+    use crate::{
+        eval::{EvalError, Evaluator},
+        wl, Symbol,
+    };
+
+    let mut evaluator = Evaluator::new();
+
+    // Conditions and pattern tests are not evaluated, so definitions that contain
+    // them are rejected instead of never matching.
+    let conditional =
+        wl!(Condition[HoldPattern[Global::cond[#(eval_x_())]], Global::test]);
+    assert_eq!(
+        evaluator.set_delayed(conditional.clone(), wl!(Global::x)),
+        Err(EvalError::UnsupportedPattern(conditional))
+    );
+
+    let tested = wl!(Global::cond[PatternTest[#(eval_x_()), Global::test]]);
+    assert_eq!(
+        evaluator.set_delayed(tested.clone(), wl!(Global::x)),
+        Err(EvalError::UnsupportedPattern(tested))
+    );
+
+    let own_value = wl!(Condition[Global::e, Global::test]);
+    assert_eq!(
+        evaluator.set(own_value.clone(), wl!(1)),
+        Err(EvalError::UnsupportedPattern(own_value))
+    );
+
+    assert_eq!(evaluator.down_values(&Symbol::new("Global`cond")), &[]);
+    assert_eq!(evaluator.own_value(&Symbol::new("Global`e")), None);
+}
+
+#[test]
+pub fn test_eval_scoped_right_hand_side() {
+    use crate::{eval::Evaluator, wl};
+
+    let mut evaluator = Evaluator::new();
+
+    // The parameter x of the Function is not replaced by the value bound to x_.
+    evaluator
+        .set_delayed(
+            wl!(Global::f[#(eval_x_())]),
+            wl!(Global::g[Global::x, Function[{Global::x}, Global::x]]),
+        )
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::f[1])),
+        Ok(wl!(Global::g[1, Function[{Global::x}, Global::x]]))
+    );
+}
+
+#[test]
+pub fn test_eval_deep_right_hand_side() {
+    use crate::{
+        eval::{EvalError, Evaluator},
+        wl,
+    };
+
+    let nest = |leaf: Expr| nested(leaf, DEEP, |expr| wl!(Global::h[#expr]));
+
+    // f[x_] := h[h[...x...]]
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .set_delayed(wl!(Global::f[#(eval_x_())]), nest(wl!(Global::x)))
+        .unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::f[1])),
+        Err(EvalError::RecursionLimitExceeded(
+            Evaluator::DEFAULT_RECURSION_LIMIT
+        ))
+    );
+}
+
+#[test]
+pub fn test_eval_attributes() {
+    use crate::{eval::Evaluator, wl};
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set(wl!(Global::x), wl!(Global::y)).unwrap();
+
+    // Flat and Orderless.
+    assert_eq!(
+        evaluator.evaluate(&wl!(Plus[Global::x, Plus[Global::b, Global::a]])),
+        Ok(wl!(Plus[Global::a, Global::b, Global::y]))
+    );
+
+    // Listable.
+    assert_eq!(
+        evaluator.evaluate(&wl!(Plus[{1, Global::x}, Global::c])),
+        Ok(wl!({Plus[1, Global::c], Plus[Global::c, Global::y]}))
+    );
+}
+
+#[test]
+pub fn test_eval_hold_attributes() {
+    use crate::{attributes::Attributes, eval::Evaluator, wl, Symbol};
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set(wl!(Global::x), wl!(Global::y)).unwrap();
+
+    assert_eq!(
+        evaluator.evaluate(&wl!(Hold[Global::x, Sequence[1]])),
+        Ok(wl!(Hold[Global::x, 1]))
+    );
+    assert_eq!(
+        evaluator.evaluate(&wl!(HoldComplete[Global::x, Sequence[1]])),
+        Ok(wl!(HoldComplete[Global::x, Sequence[1]]))
+    );
+
+    evaluator
+        .attributes_mut()
+        .insert(Symbol::new("Global`h"), Attributes::HOLD_FIRST);
+    assert_eq!(
+        evaluator.evaluate(&wl!(Global::h[Global::x, Global::x])),
+        Ok(wl!(Global::h[Global::x, Global::y]))
+    );
+}
+
+#[test]
+pub fn test_eval_protected() {
+    use crate::{
+        eval::{EvalError, Evaluator},
+        wl, Symbol,
+    };
+
+    let mut evaluator = Evaluator::new();
+
+    assert_eq!(
+        evaluator.set_delayed(wl!(Plus[1, 1]), Expr::from(3)),
+        Err(EvalError::Protected(Symbol::new("System`Plus")))
+    );
+    assert_eq!(
+        evaluator.set_delayed(wl!(HoldPattern[Plus[1, 1]]), Expr::from(3)),
+        Err(EvalError::Protected(Symbol::new("System`Plus")))
+    );
+}
+
+#[test]
+pub fn test_eval_invalid_definition() {
+    use crate::eval::{EvalError, Evaluator};
+
+    let mut evaluator = Evaluator::new();
+
+    assert_eq!(
+        evaluator.set_delayed(Expr::from(1), Expr::from(2)),
+        Err(EvalError::InvalidDefinition(Expr::from(1)))
+    );
+}
+
+fn pattern_xs___() -> Expr {
+    use crate::wl;
+
+    wl!(Pattern[Global::xs, BlankNullSequence[Integer]])
+}
+
+fn pattern_bindings(pattern: &Expr, expr: &Expr) -> Option<Vec<String>> {
+    use crate::eval::{pattern::match_pattern, Evaluator};
+
+    match_pattern(pattern, expr, 0, Evaluator::DEFAULT_RECURSION_LIMIT)
+        .unwrap()
+        .map(|bindings| {
+            bindings
+                .into_iter()
+                .map(|(name, value)| format!("{} -> {}", name, value))
+                .collect()
+        })
+}
+
+#[test]
+pub fn test_pattern_repeated_pattern_names() {
+    use crate::wl;
+
+    // Repeated pattern names must bind equal values.
+    let same = wl!(Global::f[#(eval_x_()), #(eval_x_())]);
+
+    assert_eq!(
+        pattern_bindings(&same, &wl!(Global::f[1, 1])),
+        Some(vec!["Global`x -> 1".to_owned()])
+    );
+    assert_eq!(pattern_bindings(&same, &wl!(Global::f[1, 2])), None);
+}
+
+#[test]
+pub fn test_pattern_sequence_patterns() {
+    use crate::wl;
+
+    // Sequence patterns with a head constraint.
+    let seq = wl!(Global::f[#(pattern_xs___()), #(eval_x_())]);
+
+    assert_eq!(
+        pattern_bindings(&seq, &wl!(Global::f[1, 2, "s"])),
+        Some(vec![
+            "Global`xs -> System`Sequence[1, 2]".to_owned(),
+            "Global`x -> \"s\"".to_owned(),
+        ])
+    );
+    assert_eq!(
+        pattern_bindings(&seq, &wl!(Global::f["s"])),
+        Some(vec![
+            "Global`xs -> System`Sequence[]".to_owned(),
+            "Global`x -> \"s\"".to_owned(),
+        ])
+    );
+    assert_eq!(pattern_bindings(&seq, &wl!(Global::f["s", 1])), None);
+}
+
+#[test]
+pub fn test_pattern_alternatives() {
+    use crate::wl;
+
+    // Alternatives discard the bindings of alternatives that failed to match.
+    let alternatives = wl!(Alternatives[Global::g[#(eval_x_()), 0], Global::g[Blank[]]]);
+
+    assert_eq!(
+        pattern_bindings(&alternatives, &wl!(Global::g[1])),
+        Some(vec![])
+    );
+}
+
+#[test]
+pub fn test_pattern_deep_pattern() {
+    use crate::{
+        eval::{pattern::match_pattern, EvalError, Evaluator},
+        wl, Symbol,
+    };
+
+    let nest = |leaf: Expr| nested(leaf, DEEP, |expr| wl!(Global::h[#expr]));
+
+    // Matching h[h[...x_...]] nests more deeply than the recursion limit.
+    let limit = Evaluator::DEFAULT_RECURSION_LIMIT;
+    assert_eq!(
+        match_pattern(&nest(eval_x_()), &nest(wl!(1)), 0, limit),
+        Err(EvalError::RecursionLimitExceeded(limit))
+    );
+
+    // Patterns that match single elements do not nest.
+    let elements = vec![eval_x_(); DEEP];
+    let values = vec![wl!(1); DEEP];
+    let matched = match_pattern(
+        &Expr::normal(Symbol::new("Global`f"), elements),
+        &Expr::normal(Symbol::new("Global`f"), values),
+        0,
+        limit,
+    );
+    assert_eq!(matched.unwrap().map(|bindings| bindings.len()), Some(1));
+}
+
+#[test]
+pub fn test_pattern_is_pattern() {
+    use crate::{eval::pattern::is_pattern, wl};
+
+    assert!(is_pattern(
+        &wl!(Global::f[#(pattern_xs___()), #(eval_x_())])
+    ));
+    assert!(!is_pattern(&wl!(Global::f[1])));
+}
+
+#[test]
+pub fn test_attributes_registry() {
+    use crate::{
+        attributes::{AttributeRegistry, Attributes},
+        Symbol,
+    };
+
+    let registry = AttributeRegistry::new();
+
+    let plus = registry.get(&Symbol::new("System`Plus"));
+    assert!(
+        plus.contains(Attributes::FLAT | Attributes::ORDERLESS | Attributes::LISTABLE)
+    );
+    assert!(plus.contains(Attributes::ONE_IDENTITY | Attributes::PROTECTED));
+    assert!(registry.get(&Symbol::new("Global`f")).is_empty());
+}
+
+#[test]
+pub fn test_attributes_attribute_symbols() {
+    use crate::{attributes::Attributes, wl, Symbol};
+
+    assert_eq!(
+        Attributes::from_symbol(&Symbol::new("System`HoldAll")),
+        Some(Attributes::HOLD_ALL)
+    );
+    assert_eq!(
+        (Attributes::ORDERLESS | Attributes::FLAT).to_expr(),
+        wl!({Flat, Orderless})
+    );
+}
+
+#[test]
+pub fn test_attributes_holds() {
+    use crate::attributes::Attributes;
+
+    assert!(Attributes::HOLD_FIRST.holds(0) && !Attributes::HOLD_FIRST.holds(1));
+    assert!(!Attributes::HOLD_REST.holds(0) && Attributes::HOLD_REST.holds(1));
+    assert!(Attributes::HOLD_ALL.holds(0) && Attributes::HOLD_ALL.holds(1));
+}
+
+#[test]
+pub fn test_attributes_flatten() {
+    use crate::{attributes::flatten, wl};
+
+    assert_eq!(
+        flatten(&wl!(Global::f[1, Global::f[2, Global::f[3]], Global::g[4]])),
+        wl!(Global::f[1, 2, 3, Global::g[4]])
+    );
+}
+
+#[test]
+pub fn test_attributes_thread_listable() {
+    use crate::{attributes::thread_listable, wl};
+
+    assert_eq!(
+        thread_listable(&wl!(Global::f[{1, 2}, Global::x, {3, 4}])),
+        Some(wl!({Global::f[1, Global::x, 3], Global::f[2, Global::x, 4]}))
+    );
+    assert_eq!(thread_listable(&wl!(Global::f[{1, 2}, {3}])), None);
+    assert_eq!(thread_listable(&wl!(Global::f[1])), None);
+}
+
+#[test]
+pub fn test_attributes_sort_orderless() {
+    use crate::{attributes::sort_orderless, wl};
+
+    assert_eq!(
+        sort_orderless(&wl!(Global::f[
+            Global::g[Global::a, Global::b],
+            Global::B,
+            "b",
+            Global::g[Global::c],
+            Global::b,
+            2.5,
+            "a",
+            1
+        ])),
+        wl!(Global::f[
+            1,
+            2.5,
+            "a",
+            "b",
+            Global::b,
+            Global::B,
+            Global::g[Global::c],
+            Global::g[Global::a, Global::b]
+        ])
+    );
+}
+
+#[test]
+pub fn test_attributes_canonical_order_of_numbers() {
+    use std::cmp::Ordering;
+
+    use crate::attributes::canonical_order;
+
+    assert_eq!(
+        canonical_order(&Expr::from(1), &Expr::real(1.0)),
+        Ordering::Less
+    );
+}
+
+#[test]
+pub fn test_attributes_canonical_order_of_deep_expressions() {
+    use std::cmp::Ordering;
+
+    use crate::{
+        attributes::{canonical_order, sort_orderless},
+        wl, Symbol,
+    };
+
+    let nest = |leaf: Expr| nested(leaf, DEEP, |expr| wl!(Global::f[#expr]));
+
+    let a = nest(wl!(Global::a));
+    let b = nest(wl!(Global::b));
+
+    assert_eq!(canonical_order(&a, &b), Ordering::Less);
+    assert_eq!(canonical_order(&b, &a), Ordering::Greater);
+    assert_eq!(canonical_order(&a, &nest(wl!(Global::a))), Ordering::Equal);
+
+    assert_eq!(
+        sort_orderless(&Expr::normal(Symbol::new("Global`g"), vec![
+            b.clone(),
+            a.clone()
+        ])),
+        Expr::normal(Symbol::new("Global`g"), vec![a, b])
+    );
+}
+
+fn numeric_int(int: i64) -> Number {
+    Number::Integer(int)
+}
+
+fn numeric_real(real: f64) -> Number {
+    Number::real(real)
+}
+
+#[test]
+pub fn test_numeric_integer_arithmetic() {
+    use crate::Numeric;
+
+    // Overflow is promoted to machine reals.
+    assert_eq!(numeric_int(2) + numeric_int(3), Numeric::Integer(5));
+    assert_eq!(numeric_int(2) - numeric_int(3), Numeric::Integer(-1));
+    assert_eq!(
+        numeric_int(i64::MAX) * numeric_int(2),
+        Numeric::real(i64::MAX as f64 * 2.0)
+    );
+    assert_eq!(-numeric_int(i64::MIN), Numeric::real(-(i64::MIN as f64)));
+    assert_eq!(
+        numeric_int(i64::MIN) - numeric_int(1),
+        Numeric::real(i64::MIN as f64 - 1.0)
+    );
+    assert_eq!(numeric_int(1) + numeric_real(0.5), Numeric::real(1.5));
+}
+
+#[test]
+pub fn test_numeric_rational_arithmetic() {
+    use crate::Numeric;
+
+    assert_eq!(numeric_int(6) / numeric_int(3), Numeric::Integer(2));
+    assert_eq!(numeric_int(6) / numeric_int(-4), Numeric::rational(-3, 2));
+    assert_eq!(
+        Numeric::rational(1, 3) + Numeric::rational(1, 6),
+        Numeric::rational(1, 2)
+    );
+    assert_eq!(
+        Numeric::rational(2, 3) * Numeric::Integer(3),
+        Numeric::Integer(2)
+    );
+    assert_eq!(
+        Numeric::rational(1, i64::MAX) / Numeric::Integer(i64::MAX),
+        Numeric::real(1.0 / (i64::MAX as f64 * i64::MAX as f64))
+    );
+}
+
+#[test]
+pub fn test_numeric_real_contagion() {
+    use crate::Numeric;
+
+    assert_eq!(
+        Numeric::rational(1, 2) * Numeric::real(3.0),
+        Numeric::real(1.5)
+    );
+    // An exact zero absorbs machine reals.
+    assert_eq!(numeric_int(0) * numeric_real(2.5), Numeric::Integer(0));
+    assert_eq!(numeric_real(0.0) * numeric_int(5), Numeric::real(0.0));
+}
+
+#[test]
+pub fn test_numeric_division_by_zero() {
+    use crate::Numeric;
+
+    assert_eq!(numeric_int(1) / numeric_int(0), Numeric::ComplexInfinity);
+    assert_eq!(numeric_real(1.5) / numeric_int(0), Numeric::ComplexInfinity);
+    assert_eq!(numeric_int(0) / numeric_int(0), Numeric::Indeterminate);
+    assert_eq!(numeric_int(0) / numeric_real(0.0), Numeric::Indeterminate);
+}
+
+#[test]
+pub fn test_numeric_indeterminate_forms() {
+    use crate::Numeric;
+
+    assert_eq!(
+        Numeric::ComplexInfinity + Numeric::Integer(1),
+        Numeric::ComplexInfinity
+    );
+    assert_eq!(
+        Numeric::ComplexInfinity - Numeric::ComplexInfinity,
+        Numeric::Indeterminate
+    );
+    assert_eq!(
+        Numeric::ComplexInfinity * Numeric::Integer(0),
+        Numeric::Indeterminate
+    );
+    assert_eq!(
+        Numeric::Integer(2) / Numeric::ComplexInfinity,
+        Numeric::Integer(0)
+    );
+    assert_eq!(
+        Numeric::Indeterminate * Numeric::Integer(0),
+        Numeric::Indeterminate
+    );
+    assert_eq!(
+        Numeric::real(f64::INFINITY) - Numeric::real(f64::INFINITY),
+        Numeric::Indeterminate
+    );
+}
+
+#[test]
+pub fn test_numeric_pow() {
+    use crate::Numeric;
+
+    assert_eq!(numeric_int(2).pow(10), Numeric::Integer(1024));
+    assert_eq!(numeric_int(2).pow(-2), Numeric::rational(1, 4));
+    assert_eq!(Numeric::rational(-2, 3).pow(3), Numeric::rational(-8, 27));
+    assert_eq!(Numeric::rational(2, 3).pow(-2), Numeric::rational(9, 4));
+    assert_eq!(numeric_int(-1).pow(i64::MAX), Numeric::Integer(-1));
+    assert_eq!(numeric_int(2).pow(64), Numeric::real(2f64.powi(64)));
+    assert_eq!(numeric_real(2.0).pow(-1), Numeric::real(0.5));
+    assert_eq!(numeric_real(2.5).pow(0), Numeric::Integer(1));
+}
+
+#[test]
+pub fn test_numeric_pow_of_zero_and_infinity() {
+    use crate::Numeric;
+
+    assert_eq!(numeric_int(0).pow(0), Numeric::Indeterminate);
+    assert_eq!(numeric_int(0).pow(-1), Numeric::ComplexInfinity);
+    assert_eq!(numeric_real(0.0).pow(-1), Numeric::ComplexInfinity);
+    assert_eq!(Numeric::ComplexInfinity.pow(-1), Numeric::Integer(0));
+}
+
+#[test]
+pub fn test_numeric_rational_normalization() {
+    use crate::{Numeric, Rational};
+
+    let half = Rational::new(-2, -4).unwrap();
+    assert_eq!((half.numerator(), half.denominator()), (1, 2));
+    assert_eq!(Rational::new(3, -6), Rational::new(-1, 2));
+    assert_eq!(
+        Numeric::Rational(half),
+        Numeric::Integer(1) / Numeric::Integer(2)
+    );
+
+    // Integers, and values with no exact representation, are not rationals.
+    assert_eq!(Rational::new(4, 2), None);
+    assert_eq!(Rational::new(1, 0), None);
+    assert_eq!(Rational::new(1, i64::MIN), None);
+    assert_eq!(
+        Rational::new(i64::MIN, 3).map(Rational::numerator),
+        Some(i64::MIN)
+    );
+}
+
+#[test]
+pub fn test_numeric_to_f64() {
+    use crate::Numeric;
+
+    assert_eq!(Numeric::rational(1, 4).to_f64(), Some(0.25));
+    assert_eq!(Numeric::real(f64::INFINITY).to_f64(), Some(f64::INFINITY));
+    assert_eq!(Numeric::ComplexInfinity.to_f64(), None);
+    assert_eq!(Numeric::Indeterminate.to_f64(), None);
+}
+
+#[test]
+pub fn test_numeric_conversions() {
+    use crate::Numeric;
+
+    for value in [
+        Numeric::Integer(-3),
+        Numeric::rational(-1, 2),
+        Numeric::real(0.25),
+        Numeric::Indeterminate,
+        Numeric::ComplexInfinity,
+    ] {
+        assert_eq!(Expr::from(value).try_as_numeric(), Some(value));
+    }
+
+    assert_eq!(Numeric::rational(3, -6).to_string(), "-1/2");
+}
+
+fn algebra_check(expr: Expr, expected: Expr) {
+    use crate::algebra::canonicalize;
+
+    assert_eq!(canonicalize(&expr), expected, "canonicalize({})", expr);
+}
+
+#[test]
+pub fn test_algebra_constant_folding() {
+    use crate::wl;
+
+    algebra_check(wl!(Plus[1, 2, Times[3, 4]]), wl!(15));
+    algebra_check(wl!(Plus[Rational[1, 2], Rational[2, 4]]), wl!(1));
+    algebra_check(wl!(Plus[1, 0.5, Global::x]), wl!(Plus[1.5, Global::x]));
+    algebra_check(wl!(Divide[1, 0]), wl!(ComplexInfinity));
+    algebra_check(wl!(Times[0, Global::x]), wl!(0));
+}
+
+#[test]
+pub fn test_algebra_like_terms() {
+    use crate::wl;
+
+    algebra_check(
+        wl!(Plus[Global::x, 1, Global::x, 2]),
+        wl!(Plus[3, Times[2, Global::x]]),
+    );
+    algebra_check(
+        wl!(Plus[Global::x, Plus[Times[2, Global::x], Global::y]]),
+        wl!(Plus[Times[3, Global::x], Global::y]),
+    );
+    algebra_check(wl!(Subtract[Global::x, Global::x]), wl!(0));
+    algebra_check(
+        wl!(Plus[Times[Global::x, Global::y], Times[2, Global::y, Global::x]]),
+        wl!(Times[3, Global::x, Global::y]),
+    );
+}
+
+#[test]
+pub fn test_algebra_powers() {
+    use crate::{wl, Numeric};
+
+    algebra_check(
+        wl!(Times[Global::x, Global::y, Times[Global::x, 2]]),
+        wl!(Times[2, Power[Global::x, 2], Global::y]),
+    );
+    algebra_check(wl!(Times[Global::x, Power[Global::x, -1]]), wl!(1));
+    algebra_check(wl!(Divide[Global::x, Global::x]), wl!(1));
+    algebra_check(
+        wl!(Power[Times[2, Global::x], 2]),
+        wl!(Times[4, Power[Global::x, 2]]),
+    );
+    algebra_check(wl!(Power[Power[Global::x, 2], 3]), wl!(Power[Global::x, 6]));
+    algebra_check(
+        wl!(Times[Power[Global::x, Global::a], Power[Global::x, Global::b]]),
+        wl!(Power[Global::x, Plus[Global::a, Global::b]]),
+    );
+    algebra_check(wl!(Power[2, -2]), Expr::from(Numeric::rational(1, 4)));
+    algebra_check(wl!(Power[Global::x, 0]), wl!(1));
+}
+
+#[test]
+pub fn test_algebra_canonical_order() {
+    use crate::wl;
+
+    algebra_check(
+        wl!(Plus[Global::y, Power[Global::x, 2], Global::x, 1]),
+        wl!(Plus[1, Global::x, Power[Global::x, 2], Global::y]),
+    );
+    algebra_check(
+        wl!(Minus[Global::f[Plus[Global::b, Global::a]]]),
+        wl!(Times[-1, Global::f[Plus[Global::a, Global::b]]]),
+    );
+}
+
+#[test]
+pub fn test_algebra_deep_sum() {
+    use crate::wl;
+
+    let deep = nested(wl!(Global::x), DEEP, |expr| wl!(Plus[#expr, Global::x]));
+
+    algebra_check(deep, wl!(Times[#(DEEP as i64 + 1), Global::x]));
+}
+
+#[test]
+pub fn test_algebra_deep_operands() {
+    use crate::wl;
+
+    let nest = |leaf: Expr| nested(leaf, DEEP, |expr| wl!(Global::f[#expr]));
+
+    // Sorting the operands compares two deep chains.
+    let (a, b) = (nest(wl!(Global::a)), nest(wl!(Global::b)));
+
+    algebra_check(
+        wl!(Plus[#(b.clone()), #(a.clone())]),
+        wl!(Plus[#(a.clone()), #(b.clone())]),
+    );
+    algebra_check(
+        wl!(Times[#(b.clone()), #(a.clone()), #(a.clone())]),
+        wl!(Times[Power[#a, 2], #b]),
+    );
+}
+
+fn derivative_check(expr: Expr, expected: Expr) {
+    use crate::{algebra::derivative, Symbol};
+
+    let x = Symbol::new("Global`x");
+
+    assert_eq!(derivative(&expr, &x), expected, "D[{}, x]", expr);
+}
+
+#[test]
+pub fn test_derivative_constants() {
+    use crate::wl;
+
+    derivative_check(wl!(Global::y), wl!(0));
+    derivative_check(wl!(Plus[Times[3, Global::x], Global::y]), wl!(3));
+}
+
+#[test]
+pub fn test_derivative_product_and_power_rules() {
+    use crate::wl;
+
+    derivative_check(
+        wl!(Times[Global::x, Sin[Global::x]]),
+        wl!(Plus[Times[Global::x, Cos[Global::x]], Sin[Global::x]]),
+    );
+    derivative_check(
+        wl!(Power[Global::x, Global::n]),
+        wl!(Times[Global::n, Power[Global::x, Plus[-1, Global::n]]]),
+    );
+    derivative_check(
+        wl!(Divide[1, Global::x]),
+        wl!(Times[-1, Power[Global::x, -2]]),
+    );
+    derivative_check(wl!(Power[E, Global::x]), wl!(Power[E, Global::x]));
+    derivative_check(
+        wl!(Power[2, Global::x]),
+        wl!(Times[Power[2, Global::x], Log[2]]),
+    );
+    derivative_check(
+        wl!(Power[Global::x, Global::x]),
+        wl!(Times[
+            Power[Global::x, Global::x],
+            Plus[1, Log[Global::x]]
+        ]),
+    );
+}
+
+#[test]
+pub fn test_derivative_elementary_functions() {
+    use crate::wl;
+
+    derivative_check(
+        wl!(Exp[Times[2, Global::x]]),
+        wl!(Times[2, Exp[Times[2, Global::x]]]),
+    );
+    derivative_check(wl!(Log[Global::x]), wl!(Power[Global::x, -1]));
+    derivative_check(
+        wl!(Cos[Power[Global::x, 2]]),
+        wl!(Times[-2, Global::x, Sin[Power[Global::x, 2]]]),
+    );
+    derivative_check(wl!(Tan[Global::x]), wl!(Power[Sec[Global::x], 2]));
+    derivative_check(wl!(Cosh[Global::x]), wl!(Sinh[Global::x]));
+    derivative_check(wl!(Tanh[Global::x]), wl!(Power[Sech[Global::x], 2]));
+    derivative_check(
+        wl!(Sqrt[Global::x]),
+        wl!(Times[Rational[1, 2], Power[Global::x, Rational[-1, 2]]]),
+    );
+    derivative_check(
+        wl!(ArcTan[Global::x]),
+        wl!(Power[Plus[1, Power[Global::x, 2]], -1]),
+    );
+}
+
+#[test]
+pub fn test_derivative_unknown_functions() {
+    use crate::wl;
+
+    derivative_check(
+        wl!(Global::f[Global::x]),
+        wl!(Derivative[1][Global::f][Global::x]),
+    );
+    derivative_check(
+        wl!(Global::f[Power[Global::x, 2]]),
+        wl!(Times[2, Global::x, Derivative[1][Global::f][Power[Global::x, 2]]]),
+    );
+    derivative_check(
+        wl!(Global::g[Global::x, Global::y]),
+        wl!(Derivative[1, 0][Global::g][Global::x, Global::y]),
+    );
+    derivative_check(
+        wl!(Derivative[1][Global::f][Global::x]),
+        wl!(Derivative[2][Global::f][Global::x]),
+    );
+}
+
+#[test]
+pub fn test_derivative_normal_heads() {
+    use crate::wl;
+
+    // Heads that are not symbols, but do not contain x, are unknown functions.
+    derivative_check(
+        wl!(Global::f[Global::a][Global::x]),
+        wl!(Derivative[1][Global::f[Global::a]][Global::x]),
+    );
+    derivative_check(
+        wl!(Global::f[Global::a][Global::g[Global::x]]),
+        wl!(Times[
+            Derivative[1][Global::g][Global::x],
+            Derivative[1][Global::f[Global::a]][Global::g[Global::x]]
+        ]),
+    );
+    derivative_check(
+        wl!(Global::f[Global::a][Global::x, Global::y]),
+        wl!(Derivative[1, 0][Global::f[Global::a]][Global::x, Global::y]),
+    );
+}
+
+#[test]
+pub fn test_derivative_head_contains_x() {
+    use crate::wl;
+
+    derivative_check(wl!(Global::x[1]), wl!(D[Global::x[1], Global::x]));
+    derivative_check(
+        wl!(Global::g[Global::x[1]]),
+        wl!(Times[
+            Derivative[1][Global::g][Global::x[1]],
+            D[Global::x[1], Global::x]
+        ]),
+    );
+    derivative_check(
+        wl!(Global::f[Global::x][Global::y]),
+        wl!(D[Global::f[Global::x][Global::y], Global::x]),
+    );
+}
+
+#[test]
+pub fn test_derivative_list() {
+    use crate::wl;
+
+    derivative_check(
+        wl!({Global::x, Power[Global::x, 2]}),
+        wl!({1, Times[2, Global::x]}),
+    );
+}
+
+#[test]
+pub fn test_derivative_deep_expression() {
+    use crate::wl;
+
+    let deep = nested(wl!(Global::y), DEEP, |expr| wl!(Global::f[#expr]));
+
+    derivative_check(
+        wl!(Plus[Power[Global::x, 2], #deep]),
+        wl!(Times[2, Global::x]),
+    );
+}
+
+fn numeric_eval_xy(expr: Expr) -> Result<Number, NumericError> {
+    use std::collections::HashMap;
+
+    use crate::{algebra::numeric_eval, Symbol};
+
+    let mut values = HashMap::new();
+    values.insert(Symbol::new("Global`x"), 0.5);
+    values.insert(Symbol::new("Global`y"), -4.0);
+
+    numeric_eval(&expr, &values)
+}
+
+fn numeric_eval_real(value: f64) -> Result<Number, NumericError> {
+    Ok(Number::real(value))
+}
+
+#[test]
+pub fn test_numeric_eval_arithmetic() {
+    use crate::wl;
+
+    assert_eq!(
+        numeric_eval_xy(wl!(Plus[1, Times[2, Global::x], Divide[Global::y, 8]])),
+        numeric_eval_real(1.5)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Subtract[Minus[Global::x], Rational[1, 4]])),
+        numeric_eval_real(-0.75)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Power[Global::y, 3])),
+        numeric_eval_real(-64.0)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Power[Global::y, -2])),
+        numeric_eval_real(0.0625)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Sqrt[Power[Global::y, 2]])),
+        numeric_eval_real(4.0)
+    );
+}
+
+#[test]
+pub fn test_numeric_eval_elementary_functions() {
+    use std::f64::consts;
+
+    use crate::wl;
+
+    assert_eq!(
+        numeric_eval_xy(wl!(Times[180, Degree])),
+        numeric_eval_real(consts::PI)
+    );
+    assert_eq!(numeric_eval_xy(wl!(Log[E])), numeric_eval_real(1.0));
+    assert_eq!(numeric_eval_xy(wl!(Log[2, 8])), numeric_eval_real(3.0));
+    assert_eq!(
+        numeric_eval_xy(wl!(Sin[Global::x])),
+        numeric_eval_real(0.5f64.sin())
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(ArcTan[1, 1])),
+        numeric_eval_real(consts::FRAC_PI_4)
+    );
+    assert_eq!(numeric_eval_xy(wl!(Cosh[0])), numeric_eval_real(1.0));
+    assert_eq!(
+        numeric_eval_xy(wl!(Log[0])),
+        numeric_eval_real(f64::NEG_INFINITY)
+    );
+}
+
+#[test]
+pub fn test_numeric_eval_complex_intermediate_results() {
+    use std::f64::consts;
+
+    use crate::wl;
+
+    assert_eq!(numeric_eval_xy(wl!(Power[I, 2])), numeric_eval_real(-1.0));
+    assert_eq!(
+        numeric_eval_xy(wl!(Times[Sqrt[Global::y], Sqrt[Global::y]])),
+        numeric_eval_real(-4.0)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Im[Log[-1]])),
+        numeric_eval_real(consts::PI)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Abs[Complex[3, 4]])),
+        numeric_eval_real(5.0)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Sqrt[Global::y])),
+        Err(NumericError::ComplexResult { re: 0.0, im: 2.0 })
+    );
+    assert!(matches!(
+        numeric_eval_xy(wl!(Exp[Times[I, Pi]])),
+        Err(NumericError::ComplexResult { re, im }) if re == -1.0 && im.abs() < 1e-15
+    ));
+}
+
+#[test]
+pub fn test_numeric_eval_errors() {
+    use crate::{wl, Symbol};
+
+    assert_eq!(
+        numeric_eval_xy(wl!(Plus[Global::x, Global::z])),
+        Err(NumericError::UnknownSymbol(Symbol::new("Global`z")))
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Plus[1, Global::f[Global::x]])),
+        Err(NumericError::Unsupported(wl!(Global::f[Global::x])))
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Sin[1, 2])),
+        Err(NumericError::Unsupported(wl!(Sin[1, 2])))
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Divide[1, 0])),
+        Err(NumericError::ComplexInfinity)
+    );
+    assert_eq!(
+        numeric_eval_xy(wl!(Power[0, 0])),
+        Err(NumericError::Indeterminate)
+    );
+    assert_eq!(
+        NumericError::UnknownSymbol(Symbol::new("Global`z")).to_string(),
+        "symbol Global`z has no numeric value"
+    );
+}
+
+#[test]
+pub fn test_numeric_eval_deep_expression() {
+    use crate::wl;
+
+    let deep = nested(wl!(0), DEEP, |expr| wl!(Plus[#expr, Global::x]));
+
+    assert_eq!(numeric_eval_xy(deep), numeric_eval_real(DEEP as f64 * 0.5));
+}
+
+fn compile_parameters() -> [Symbol; 2] {
+    [Symbol::new("Global`x"), Symbol::new("Global`y")]
+}
+
+#[test]
+pub fn test_compile_agrees_with_numeric_eval() {
+    use std::collections::HashMap;
+
+    use crate::{algebra::numeric_eval, compile::compile, wl, Number};
+
+    let [x, y] = compile_parameters();
+
+    let expr = wl!(Plus[
+        Power[Global::x, 2],
+        Times[Rational[1, 2], Sin[Times[Global::x, Global::y]]],
+        Log[2, Global::y],
+        Divide[Exp[Global::x], Sqrt[Global::y]],
+        ArcTan[Global::x, Global::y],
+        Power[Global::y, Global::x],
+        Times[Pi, Degree]
+    ]);
+    let compiled = compile(&expr, &compile_parameters()).unwrap();
+
+    for (x_value, y_value) in [(0.5, 2.0), (-1.25, 3.5), (2.0, 0.75)] {
+        let mut values = HashMap::new();
+        values.insert(x.clone(), x_value);
+        values.insert(y.clone(), y_value);
+
+        let expected = match numeric_eval(&expr, &values).unwrap() {
+            Number::Integer(int) => int as f64,
+            Number::Real(real) => *real,
+        };
+        let actual = compiled.eval(&[x_value, y_value]);
+
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+pub fn test_compile_common_subexpressions() {
+    use crate::{
+        compile::{compile_function, Instruction},
+        wl,
+    };
+
+    let compiled = compile_function(&wl!(Function[
+        {Global::x, Global::y},
+        Plus[
+            Sin[Times[Global::x, Global::y]],
+            Cos[Times[Global::y, Global::x]],
+            Times[2, Pi]
+        ]
+    ]))
+    .unwrap();
+    assert_eq!(compiled.parameters(), &compile_parameters());
+
+    let constants = compiled
+        .instructions()
+        .iter()
+        .filter(|instruction| matches!(instruction, Instruction::Constant(_)))
+        .count();
+    assert_eq!(constants, 3);
+    // x, y, x y, Sin, Cos, two additions and three constants.
+    assert_eq!(compiled.instructions().len(), 10);
+
+    let f = compiled.into_fn();
+    assert_eq!(f(&[0.0, 1.0]), 1.0 + 2.0 * std::f64::consts::PI);
+}
+
+#[test]
+pub fn test_compile_signed_zero() {
+    use crate::{compile::compile, wl};
+
+    let [x, _] = compile_parameters();
+
+    // x/0.0 + x/-0.0 == Infinity - Infinity
+    let expr = wl!(Plus[Divide[Global::x, 0.0], Divide[Global::x, #(-0.0)]]);
+    let compiled = compile(&expr, &[x]).unwrap();
+    assert!(compiled.eval(&[1.0]).is_nan());
+
+    let compiled = compile(&wl!(Divide[1, #(-0.0)]), &[]).unwrap();
+    assert_eq!(compiled.eval(&[]), f64::NEG_INFINITY);
+}
+
+#[test]
+pub fn test_compile_slots() {
+    use crate::{compile::compile_function, wl};
+
+    let compiled =
+        compile_function(&wl!(Function[Subtract[Slot[2], Power[Slot[1], -1]]])).unwrap();
+    assert_eq!(compiled.parameters().len(), 2);
+    assert_eq!(compiled.eval(&[4.0, 1.0]), 0.75);
+
+    // Function[Null, body] and attributes use the same slot rules as
+    // apply_function().
+    let compiled =
+        compile_function(&wl!(Function[Null, Times[Slot[1], Slot[3]]])).unwrap();
+    assert_eq!(compiled.parameters().len(), 3);
+    assert_eq!(compiled.eval(&[2.0, 0.0, 5.0]), 10.0);
+
+    let compiled =
+        compile_function(&wl!(Function[Null, Minus[Slot[1]], Listable])).unwrap();
+    assert_eq!(compiled.eval(&[2.0]), -2.0);
+
+    let compiled =
+        compile_function(&wl!(Function[{Global::x}, Sqrt[Global::x], Listable])).unwrap();
+    assert_eq!(compiled.eval(&[4.0]), 2.0);
+}
+
+#[test]
+pub fn test_compile_invalid_slots() {
+    use crate::{
+        compile::{compile_function, CompileError},
+        wl,
+    };
+
+    // Slots in a nested Function[body] belong to that function.
+    assert_eq!(
+        compile_function(&wl!(Function[Plus[Slot[1], Function[Slot[2]]]])),
+        Err(CompileError::Unsupported(wl!(Function[Slot[2]])))
+    );
+
+    for function in [
+        wl!(Function[Plus[SlotSequence[1]]]),
+        wl!(Function[Plus[Slot[0], 1]]),
+        wl!(Function[Slot["name"]]),
+    ] {
+        assert_eq!(
+            compile_function(&function),
+            Err(CompileError::InvalidFunction(function.clone()))
+        );
+    }
+}
+
+#[test]
+pub fn test_compile_errors() {
+    use crate::{
+        compile::{compile, compile_function, CompileError},
+        wl,
+    };
+
+    assert_eq!(
+        compile(&wl!(Plus[Global::x, Global::z]), &compile_parameters()),
+        Err(CompileError::UnknownSymbol(Symbol::new("Global`z")))
+    );
+    assert_eq!(
+        compile(&wl!(Global::f[Global::x]), &compile_parameters()),
+        Err(CompileError::Unsupported(wl!(Global::f[Global::x])))
+    );
+    assert_eq!(
+        compile_function(&wl!(Function[{1}, 1])),
+        Err(CompileError::InvalidFunction(wl!(Function[{1}, 1])))
+    );
+}
+
+#[test]
+pub fn test_compile_deep_expression() {
+    use crate::{compile::compile_function, wl};
+
+    let deep = nested(wl!(Slot[1]), DEEP, |expr| wl!(Plus[#expr, 1]));
+
+    let compiled = compile_function(&wl!(Function[#deep])).unwrap();
+    assert_eq!(compiled.eval(&[0.5]), DEEP as f64 + 0.5);
+}
+
+#[test]
+pub fn test_function_slots() {
+    use crate::{function::apply_function, wl};
+
+    assert_eq!(
+        apply_function(&wl!(Function[Global::f[Slot[1], Slot[1], Slot[2]]]), &[
+            wl!(Global::a),
+            wl!(Global::b)
+        ]),
+        Ok(wl!(Global::f[Global::a, Global::a, Global::b]))
+    );
+
+    // #0 is the function itself.
+    let recursive = wl!(Function[Global::g[Slot[0], Slot[1]]]);
+    assert_eq!(
+        apply_function(&recursive, &[wl!(Global::a)]),
+        Ok(Expr::normal(wl!(Global::g), vec![
+            recursive.clone(),
+            wl!(Global::a)
+        ]))
+    );
+}
+
+#[test]
+pub fn test_function_slot_sequences() {
+    use crate::{function::apply_function, wl};
+
+    assert_eq!(
+        apply_function(&wl!(Function[Global::f[Slot[1], SlotSequence[2]]]), &[
+            wl!(Global::a),
+            wl!(Global::b),
+            wl!(Global::c)
+        ]),
+        Ok(wl!(Global::f[Global::a, Global::b, Global::c]))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[Global::f[SlotSequence[2]]]), &[wl!(
+            Global::a
+        )]),
+        Ok(wl!(Global::f[]))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[SlotSequence[1]]), &[
+            wl!(Global::a),
+            wl!(Global::b)
+        ]),
+        Ok(wl!(Sequence[Global::a, Global::b]))
+    );
+}
+
+#[test]
+pub fn test_function_nested_functions() {
+    use crate::{function::apply_function, wl};
+
+    // Slots in a nested Function[body] belong to the inner function, but slots in
+    // a nested function with named parameters belong to the outer function.
+    assert_eq!(
+        apply_function(
+            &wl!(Function[Global::f[
+                Slot[1],
+                Function[Slot[1]],
+                Function[{Global::x}, Slot[1]]
+            ]]),
+            &[wl!(Global::a)]
+        ),
+        Ok(wl!(Global::f[
+            Global::a,
+            Function[Slot[1]],
+            Function[{Global::x}, Global::a]
+        ]))
+    );
+}
+
+#[test]
+pub fn test_function_named_slots() {
+    use crate::{
+        function::{apply_function, FunctionError},
+        wl,
+    };
+
+    let association = wl!(Association[Rule["x", 1], RuleDelayed["y", Global::b]]);
+    assert_eq!(
+        apply_function(
+            &wl!(Function[Global::f[Slot["y"], Slot["x"], Slot["z"]]]),
+            &[association]
+        ),
+        Ok(wl!(Global::f[Global::b, 1, Missing["KeyAbsent", "z"]]))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[Slot["x"]]), &[wl!(Global::a)]),
+        Err(FunctionError::NotAnAssociation(wl!(Global::a)))
+    );
+}
+
+#[test]
+pub fn test_function_named_parameters() {
+    use crate::{function::apply_function, wl};
+
+    assert_eq!(
+        apply_function(
+            &wl!(Function[{Global::x, Global::y}, Global::f[Global::y, Global::x]]),
+            &[wl!(Global::a), wl!(Global::b), wl!(Global::c)]
+        ),
+        Ok(wl!(Global::f[Global::b, Global::a]))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[Global::x, Global::f[Global::x]]), &[wl!(
+            Global::a
+        )]),
+        Ok(wl!(Global::f[Global::a]))
+    );
+
+    // Inner parameters shadow outer parameters.
+    assert_eq!(
+        apply_function(
+            &wl!(Function[
+                {Global::x},
+                Global::f[Global::x, Function[{Global::x}, Global::x]]
+            ]),
+            &[wl!(Global::a)]
+        ),
+        Ok(wl!(Global::f[Global::a, Function[{Global::x}, Global::x]]))
+    );
+
+    // Inner parameters are renamed to avoid capturing symbols in the arguments.
+    let y_renamed = Expr::symbol(Symbol::new("Global`y$"));
+    assert_eq!(
+        apply_function(
+            &wl!(Function[
+                {Global::x},
+                Function[{Global::y}, Plus[Global::x, Global::y]]
+            ]),
+            &[wl!(Global::y)]
+        ),
+        Ok(wl!(Function[{#(y_renamed.clone())}, Plus[Global::y, #y_renamed]]))
+    );
+}
+
+#[test]
+pub fn test_function_deep_body() {
+    use crate::{function::apply_function, wl};
+
+    let body = nested(wl!(Slot[1]), DEEP, |expr| wl!(Global::f[#expr]));
+    let expected = nested(wl!(Global::a), DEEP, |expr| wl!(Global::f[#expr]));
+
+    assert_eq!(
+        apply_function(&wl!(Function[#body]), &[wl!(Global::a)]),
+        Ok(expected)
+    );
+}
+
+#[test]
+pub fn test_function_errors() {
+    use crate::{
+        function::{apply_function, FunctionError},
+        wl,
+    };
+
+    assert_eq!(
+        apply_function(&wl!(Function[Slot[3]]), &[wl!(Global::a), wl!(Global::b)]),
+        Err(FunctionError::SlotOutOfRange(wl!(Slot[3])))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[Slot[-1]]), &[wl!(Global::a)]),
+        Err(FunctionError::InvalidSlot(wl!(Slot[-1])))
+    );
+    assert_eq!(
+        apply_function(&wl!(Function[{Global::x, Global::y}, Global::x]), &[wl!(
+            Global::a
+        )]),
+        Err(FunctionError::TooFewArguments {
+            parameters: 2,
+            arguments: 1
+        })
+    );
+    assert_eq!(
+        apply_function(&wl!(Global::b), &[]),
+        Err(FunctionError::InvalidFunction(wl!(Global::b)))
+    );
+}
+
+fn scope_symbol(name: &str) -> Expr {
+    Expr::symbol(Symbol::new(name))
+}
+
+#[test]
+pub fn test_scope_substitute() {
+    use crate::{scope::substitute, wl};
+
+    let x = Symbol::new("Global`x");
+
+    assert_eq!(
+        substitute(&wl!(Global::f[Global::x, Global::y]), &[(
+            x.clone(),
+            wl!(1)
+        )]),
+        wl!(Global::f[1, Global::y])
+    );
+
+    // Bound occurrences are not replaced, but initial values outside the scope are.
+    assert_eq!(
+        substitute(
+            &wl!(Module[{Set[Global::x, Global::x]}, Global::f[Global::x]]),
+            &[(x, wl!(1))]
+        ),
+        wl!(Module[{Set[Global::x, 1]}, Global::f[Global::x]])
+    );
+}
+
+#[test]
+pub fn test_scope_substitute_renames_bound_symbols() {
+    use crate::{scope::substitute, wl};
+
+    let x = Symbol::new("Global`x");
+    let y_renamed = scope_symbol("Global`y$");
+    let y_renamed_twice = scope_symbol("Global`y$$");
+    let y_renamed_thrice = scope_symbol("Global`y$$$");
+
+    // Bound symbols that would capture a value are renamed.
+    assert_eq!(
+        substitute(
+            &wl!(Module[{Global::y}, Global::f[Global::x, Global::y]]),
+            &[(x.clone(), wl!(Global::y))]
+        ),
+        wl!(Module[
+            {#(y_renamed.clone())},
+            Global::f[Global::y, #(y_renamed.clone())]
+        ])
+    );
+
+    // ... choosing a name that is not already used.
+    assert_eq!(
+        substitute(
+            &wl!(Function[
+                Global::y,
+                Global::f[Global::x, Global::y, #(y_renamed.clone())]
+            ]),
+            &[(x.clone(), wl!(Global::y))]
+        ),
+        wl!(Function[
+            #(y_renamed_twice.clone()),
+            Global::f[Global::y, #(y_renamed_twice.clone()), #(y_renamed.clone())]
+        ])
+    );
+
+    // ... and that does not collide with the name chosen for another bound symbol.
+    assert_eq!(
+        substitute(
+            &wl!(Function[
+                {#(y_renamed.clone())},
+                Function[
+                    {Global::y},
+                    Global::f[Global::x, Global::y, #(y_renamed.clone())]
+                ]
+            ]),
+            &[(x, wl!(Global::g[Global::y, #(y_renamed.clone())]))]
+        ),
+        wl!(Function[
+            {#(y_renamed_twice.clone())},
+            Function[
+                {#(y_renamed_thrice.clone())},
+                Global::f[
+                    Global::g[Global::y, #y_renamed],
+                    #y_renamed_thrice,
+                    #y_renamed_twice
+                ]
+            ]
+        ])
+    );
+}
+
+#[test]
+pub fn test_scope_deep_substitute() {
+    use crate::{
+        scope::{apply_with, substitute},
+        wl,
+    };
+
+    let y_renamed = scope_symbol("Global`y$");
+
+    let deep = nested(
+        wl!(Global::x),
+        DEEP,
+        |expr| wl!(Function[{Global::y}, Global::f[Global::x, Global::y, #expr]]),
+    );
+    let expected = nested(wl!(Global::y), DEEP, |expr| {
+        wl!(Function[
+            {#(y_renamed.clone())},
+            Global::f[Global::y, #(y_renamed.clone()), #expr]
+        ])
+    });
+
+    assert_eq!(
+        substitute(&deep, &[(Symbol::new("Global`x"), wl!(Global::y))]),
+        expected
+    );
+
+    let deep_with = wl!(With[{Set[Global::x, Global::y]}, #deep]);
+    assert_eq!(apply_with(&deep_with), Ok(expected));
+
+    // Rules nested in the left-hand side of a rule, which all bind x.
+    let rules = nested(
+        wl!(Pattern[Global::x, Blank[]]),
+        DEEP,
+        |expr| wl!(Rule[#expr, Global::x]),
+    );
+    assert_eq!(
+        substitute(&rules, &[(Symbol::new("Global`x"), wl!(Global::y))]),
+        rules
+    );
+}
+
+#[test]
+pub fn test_scope_apply_with() {
+    use crate::{
+        scope::{apply_with, ScopeError},
+        wl,
+    };
+
+    assert_eq!(
+        apply_with(&wl!(With[
+            {Set[Global::x, 1], SetDelayed[Global::y, Global::g[Global::x]]},
+            Global::f[Global::x, Global::y, With[{Set[Global::x, 2]}, Global::x]]
+        ])),
+        Ok(wl!(Global::f[
+            1,
+            Global::g[Global::x],
+            With[{Set[Global::x, 2]}, Global::x]
+        ]))
+    );
+    assert_eq!(
+        apply_with(&wl!(With[{Global::x}, Global::x])),
+        Err(ScopeError::InvalidConstruct(
+            wl!(With[{Global::x}, Global::x])
+        ))
+    );
+}
+
+#[test]
+pub fn test_scope_symbol_generator() {
+    use crate::{
+        scope::{ScopeError, SymbolGenerator},
+        wl,
+    };
+
+    let mut generator = SymbolGenerator::new();
+
+    assert_eq!(
+        generator.unique_symbol(&Symbol::new("Global`x")),
+        Symbol::new("Global`x$1")
+    );
+    assert_eq!(
+        generator.apply_module(&wl!(Module[
+            {Global::x, Global::y},
+            Global::f[Global::x, Global::y, Global::z]
+        ])),
+        Ok(Expr::normal(wl!(Global::f), vec![
+            scope_symbol("Global`x$2"),
+            scope_symbol("Global`y$2"),
+            wl!(Global::z)
+        ]))
+    );
+    assert_eq!(generator.module_number(), 3);
+    assert_eq!(
+        generator.apply_module(&wl!(Module[Global::x, Global::x])),
+        Err(ScopeError::InvalidConstruct(
+            wl!(Module[Global::x, Global::x])
+        ))
+    );
+}
+
+#[test]
+pub fn test_scope_occurs_free_and_bound_symbols() {
+    use crate::{
+        scope::{bound_symbols, occurs_free},
+        wl,
+    };
+
+    let x = Symbol::new("Global`x");
+    let y = Symbol::new("Global`y");
+
+    let expr = wl!(Global::f[
+        Global::x,
+        Function[{Global::y}, Global::g[Global::y, Global::z]],
+        With[{Set[Global::z, Global::y]}, Global::z]
+    ]);
+    assert!(occurs_free(&expr, &x));
+    assert!(occurs_free(&expr, &y));
+    assert!(occurs_free(&expr, &Symbol::new("Global`z")));
+    assert!(!occurs_free(
+        &wl!(Function[{Global::y}, Global::g[Global::y]]),
+        &y
+    ));
+    assert!(!occurs_free(&wl!(With[{Set[Global::y, 1]}, Global::y]), &y));
+
+    assert_eq!(bound_symbols(&expr), vec![
+        y.clone(),
+        Symbol::new("Global`z")
+    ]);
+    assert_eq!(bound_symbols(&wl!(Global::f[Global::x])), vec![]);
+}
+
+fn scope_symbols(names: &[&str]) -> Vec<Symbol> {
+    names.iter().map(|name| Symbol::new(name)).collect()
+}
+
+fn scope_occurrence(position: &[usize], kind: OccurrenceKind) -> SymbolOccurrence {
+    SymbolOccurrence {
+        position: position.to_vec(),
+        kind,
+    }
+}
+
+#[test]
+pub fn test_scope_free_symbols() {
+    use crate::{scope::free_symbols, wl};
+
+    // Function, With, Module and Block
+    assert_eq!(
+        free_symbols(&wl!(Global::f[
+            Global::x,
+            Function[{Global::x}, Global::x],
+            Block[{Set[Global::y, Global::z]}, Global::y]
+        ])),
+        scope_symbols(&[
+            "Global`f",
+            "Global`x",
+            "System`Function",
+            "System`List",
+            "System`Block",
+            "System`Set",
+            "Global`z"
+        ])
+    );
+
+    // The heads of the declarations are free, like the heads of iterators.
+    assert_eq!(
+        free_symbols(&wl!(Function[{Global::x}, Global::x])),
+        scope_symbols(&["System`Function", "System`List"])
+    );
+
+    // Table iterators, including iterators that depend on earlier ones.
+    assert_eq!(
+        free_symbols(&wl!(Table[
+            Global::f[Global::i, Global::j],
+            {Global::i, Global::n},
+            {Global::j, Global::i}
+        ])),
+        scope_symbols(&["System`Table", "Global`f", "System`List", "Global`n"])
+    );
+}
+
+#[test]
+pub fn test_scope_iterator_scope() {
+    use crate::{
+        scope::{free_symbols, occurs_free, substitute},
+        wl,
+    };
+
+    let i = Symbol::new("Global`i");
+
+    // The bounds of an iterator are outside the scope of its own variable.
+    let table = wl!(Table[Global::i, {Global::i, Global::i}]);
+    assert_eq!(
+        free_symbols(&table),
+        scope_symbols(&["System`Table", "System`List", "Global`i"])
+    );
+    assert!(occurs_free(&table, &i));
+    assert_eq!(
+        substitute(&table, &[(i.clone(), wl!(3))]),
+        wl!(Table[Global::i, {Global::i, 3}])
+    );
+
+    // ... but inside the scope of the variables of earlier iterators.
+    assert!(!occurs_free(
+        &wl!(Sum[Global::j, {Global::i, 3}, {Global::j, Global::i}]),
+        &i
+    ));
+
+    // Iterator variables that would capture a value are renamed.
+    let i_renamed = scope_symbol("Global`i$");
+    assert_eq!(
+        substitute(
+            &wl!(Table[Global::f[Global::i, Global::n], {Global::i, Global::n}]),
+            &[(Symbol::new("Global`n"), wl!(Global::i))]
+        ),
+        wl!(Table[
+            Global::f[#(i_renamed.clone()), Global::i],
+            {#(i_renamed), Global::i}
+        ])
+    );
+}
+
+#[test]
+pub fn test_scope_rule_patterns() {
+    use crate::{
+        scope::{free_symbols, occurs_free, substitute},
+        wl,
+    };
+
+    let x = Symbol::new("Global`x");
+
+    let rule = wl!(RuleDelayed[
+        Global::f[Pattern[Global::x, Blank[]], Global::y],
+        Global::g[Global::x, Global::y]
+    ]);
+    assert_eq!(
+        free_symbols(&rule),
+        scope_symbols(&[
+            "System`RuleDelayed",
+            "Global`f",
+            "System`Pattern",
+            "System`Blank",
+            "Global`y",
+            "Global`g"
+        ])
+    );
+    assert!(!occurs_free(&rule, &x));
+
+    // Rules without patterns are not scoping constructs.
+    assert!(occurs_free(&wl!(Rule[Global::x, Global::x]), &x));
+
+    // Substitution renames pattern names that would capture a value.
+    let x_renamed = scope_symbol("Global`x$");
+    assert_eq!(
+        substitute(&rule, &[(Symbol::new("Global`y"), wl!(Global::x))]),
+        wl!(RuleDelayed[
+            Global::f[Pattern[#(x_renamed.clone()), Blank[]], Global::x],
+            Global::g[#(x_renamed), Global::x]
+        ])
+    );
+}
+
+#[test]
+pub fn test_scope_symbol_occurrences() {
+    use crate::{scope::symbol_occurrences, wl};
+
+    let x = Symbol::new("Global`x");
+
+    assert_eq!(
+        symbol_occurrences(
+            &wl!(Global::f[
+                Global::x,
+                Module[{Set[Global::x, Global::x]}, Global::g[Global::x]],
+                Rule[Pattern[Global::x, Blank[]], Global::x]
+            ]),
+            &x
+        ),
+        vec![
+            scope_occurrence(&[1], OccurrenceKind::Free),
+            scope_occurrence(&[2, 1, 1, 1], OccurrenceKind::Binding),
+            scope_occurrence(&[2, 1, 1, 2], OccurrenceKind::Free),
+            scope_occurrence(&[2, 2, 1], OccurrenceKind::Bound),
+            scope_occurrence(&[3, 1, 1], OccurrenceKind::Binding),
+            scope_occurrence(&[3, 2], OccurrenceKind::Bound),
+        ]
+    );
+    assert_eq!(
+        symbol_occurrences(
+            &wl!(Sum[Global::x, {Global::x, 1, Global::x}, {Global::y, 2}]),
+            &x
+        ),
+        vec![
+            scope_occurrence(&[1], OccurrenceKind::Bound),
+            scope_occurrence(&[2, 1], OccurrenceKind::Binding),
+            scope_occurrence(&[2, 3], OccurrenceKind::Free),
+        ]
+    );
+    assert_eq!(
+        symbol_occurrences(
+            &wl!(Module[{Global::x, SetDelayed[Global::y, 1]}, Global::x]),
+            &Symbol::new("System`SetDelayed")
+        ),
+        vec![scope_occurrence(&[1, 2, 0], OccurrenceKind::Free)]
+    );
+    assert_eq!(symbol_occurrences(&wl!(Global::f[1]), &x), vec![]);
+}
+
+#[test]
+pub fn test_scope_deep_free_symbols() {
+    use crate::{
+        scope::{free_symbols, occurs_free, symbol_occurrences},
+        wl,
+    };
+
+    let x = Symbol::new("Global`x");
+    let y = Symbol::new("Global`y");
+
+    let deep = nested(
+        wl!(Global::x),
+        DEEP,
+        |expr| wl!(Function[{Global::y}, Global::f[Global::y, #expr]]),
+    );
+    assert_eq!(
+        free_symbols(&deep),
+        scope_symbols(&["System`Function", "System`List", "Global`f", "Global`x"])
+    );
+    assert!(occurs_free(&deep, &x));
+    assert!(!occurs_free(&deep, &y));
+
+    let deep = nested(wl!(Global::y), DEEP, |expr| wl!(Global::f[#expr]));
+    assert_eq!(
+        symbol_occurrences(&wl!(Function[{Global::x}, Global::g[Global::x, #deep]]), &x),
+        vec![
+            scope_occurrence(&[1, 1], OccurrenceKind::Binding),
+            scope_occurrence(&[2, 1], OccurrenceKind::Bound),
+        ]
+    );
+}
+
+/// Evaluator with the definitions:
+///
+/// ```text
+/// even[n_] := odd[n - 1]
+/// odd[n_] := even[n - 1]
+/// fact[n_] := n fact[n - 1]
+/// g[n_] := Function[{fact}, fact[n]][h[n]] + fact[n]
+/// h[n_] := n
+/// ```
+fn dependency_evaluator() -> Evaluator {
+    use crate::wl;
+
+    let mut evaluator = Evaluator::new();
+
+    let n_ = wl!(Pattern[Global::n, Blank[]]);
+
+    evaluator
+        .set_delayed(
+            wl!(Global::even[#(n_.clone())]),
+            wl!(Global::odd[Plus[Global::n, -1]]),
+        )
+        .unwrap();
+    evaluator
+        .set_delayed(
+            wl!(Global::odd[#(n_.clone())]),
+            wl!(Global::even[Plus[Global::n, -1]]),
+        )
+        .unwrap();
+    evaluator
+        .set_delayed(
+            wl!(Global::fact[#(n_.clone())]),
+            wl!(Times[Global::n, Global::fact[Plus[Global::n, -1]]]),
+        )
+        .unwrap();
+    evaluator
+        .set_delayed(
+            wl!(Global::g[#(n_.clone())]),
+            wl!(Plus[
+                Function[{Global::fact}, Global::fact[Global::n]][
+                    Global::h[Global::n]
+                ],
+                Global::fact[Global::n]
+            ]),
+        )
+        .unwrap();
+    evaluator
+        .set_delayed(wl!(Global::h[#n_]), wl!(Global::n))
+        .unwrap();
+
+    evaluator
+}
+
+#[test]
+pub fn test_dependency_dependencies() {
+    let graph = dependency_evaluator().dependency_graph();
+
+    let even = Symbol::new("Global`even");
+    let odd = Symbol::new("Global`odd");
+    let fact = Symbol::new("Global`fact");
+    let g = Symbol::new("Global`g");
+    let h = Symbol::new("Global`h");
+
+    assert_eq!(graph.symbols().cloned().collect::<Vec<_>>(), vec![
+        even.clone(),
+        fact.clone(),
+        g.clone(),
+        h.clone(),
+        odd.clone()
+    ]);
+    assert_eq!(graph.dependencies(&g), &[h.clone(), fact.clone()]);
+    assert_eq!(graph.dependencies(&h), &[]);
+    assert_eq!(graph.dependents(&fact), vec![fact.clone(), g.clone()]);
+    assert_eq!(graph.transitive_dependencies(&even), vec![
+        odd,
+        even.clone()
+    ]);
+    assert!(graph.is_recursive(&even));
+    assert!(graph.is_recursive(&fact));
+    assert!(!graph.is_recursive(&g));
+}
+
+#[test]
+pub fn test_dependency_topological_order() {
+    let mut evaluator = dependency_evaluator();
+
+    // Mutual recursion has no topological order, but direct recursion does.
+    assert_eq!(evaluator.dependency_graph().topological_order(), None);
+
+    evaluator.clear(&Symbol::new("Global`even"));
+    evaluator.clear(&Symbol::new("Global`odd"));
+
+    assert_eq!(
+        evaluator.dependency_graph().topological_order(),
+        Some(vec![
+            Symbol::new("Global`fact"),
+            Symbol::new("Global`h"),
+            Symbol::new("Global`g")
+        ])
+    );
+}
+
+#[test]
+pub fn test_dependency_deep_dependencies() {
+    use crate::{
+        eval::{Definition, DependencyGraph},
+        wl,
+    };
+
+    let f = |index: usize| Symbol::new(&format!("Global`f{}", index));
+    let symbols: Vec<Symbol> = (0..DEEP).map(f).collect();
+
+    // f0[x_] := f1[x], f1[x_] := f2[x], ..., where the last definition calls `last`.
+    let chain = |last: Symbol| {
+        let definitions: Vec<Vec<Definition>> = (0..DEEP)
+            .map(|index| {
+                let next = if index + 1 < DEEP {
+                    f(index + 1)
+                } else {
+                    last.clone()
+                };
+
+                vec![Definition {
+                    lhs: Expr::normal(f(index), vec![wl!(Pattern[Global::x, Blank[]])]),
+                    rhs: Expr::normal(next, vec![wl!(Global::x)]),
+                }]
+            })
+            .collect();
+
+        DependencyGraph::from_down_values(
+            symbols
+                .iter()
+                .zip(&definitions)
+                .map(|(symbol, definitions)| (symbol, definitions.as_slice())),
+        )
+    };
+
+    let graph = chain(f(DEEP));
+    assert_eq!(
+        graph.topological_order(),
+        Some(symbols.iter().rev().cloned().collect())
+    );
+    assert_eq!(graph.transitive_dependencies(&symbols[0]), &symbols[1..]);
+    assert!(!graph.is_recursive(&symbols[0]));
+
+    let cycle = chain(f(0));
+    assert_eq!(cycle.topological_order(), None);
+    assert_eq!(cycle.transitive_dependencies(&symbols[0]).len(), DEEP);
+    assert!(cycle.is_recursive(&symbols[0]));
+    assert!(cycle.is_recursive(&symbols[DEEP - 1]));
 }